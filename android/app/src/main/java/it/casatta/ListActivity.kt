@@ -22,7 +22,7 @@ import java.io.Serializable
 
 class ListActivity : AppCompatActivity() , ItemsAdapter.ItemGesture {
     val itemsAdapter = ItemsAdapter()
-    var listOutput = Rust.ListOutput( emptyList(),  emptyList(),  emptyList())
+    var listOutput = Rust.ListOutput( emptyList(),  emptyList(),  emptyList(),  emptyList())
     val mapper = ObjectMapper().registerModule(KotlinModule())
 
     companion object {
@@ -110,12 +110,15 @@ class ListActivity : AppCompatActivity() , ItemsAdapter.ItemGesture {
         integrator.initiateScan()
     }
 
-    private fun updateKeys() {
-        update("keys")
+    private fun updateKeys(passphrase: String? = null) {
+        update("keys", passphrase)
         for (key in listOutput.keys) {
             val details = mapper.writerWithDefaultPrettyPrinter().writeValueAsString(key);
             itemsAdapter.list.add(Item(key.key.name, key.key.fingerprint, details, key.public_qr_files))
         }
+        for (keyFile in listOutput.encrypted_keys) {
+            itemsAdapter.list.add(Item(File(keyFile).parentFile!!.name, "encrypted", null, emptyList()))
+        }
         itemsAdapter.notifyDataSetChanged()
     }
 
@@ -137,10 +140,10 @@ class ListActivity : AppCompatActivity() , ItemsAdapter.ItemGesture {
         itemsAdapter.notifyDataSetChanged()
     }
 
-    private fun update(kind: String) {
+    private fun update(kind: String, passphrase: String? = null) {
         try {
             itemsAdapter.list.clear()
-            listOutput = Rust().list(filesDir.toString(), intent.network!!, kind)
+            listOutput = Rust().list(filesDir.toString(), intent.network!!, kind, passphrase)
         } catch (e: RustException) {
             Toast.makeText(this, e.toString(), Toast.LENGTH_LONG).show()
         }
@@ -162,6 +165,11 @@ class ListActivity : AppCompatActivity() , ItemsAdapter.ItemGesture {
                 keyNameDialog(item.name)
             }
             KEYS -> {
+                if (item.json == null) {
+                    // encrypted key, shown after decryption
+                    passphraseDialog(this) { passphrase -> updateKeys(passphrase) }
+                    return
+                }
                 val newIntent = Intent(this, KeyActivity::class.java)
                 newIntent.putExtra(C.NETWORK, intent.network)
                 newIntent.putExtra(C.KEY, item.json)
@@ -250,6 +258,12 @@ class ListActivity : AppCompatActivity() , ItemsAdapter.ItemGesture {
         try {
             val json = mapper.readValue(content, Rust.WalletJson::class.java)
             val name = json.name
+            try {
+                Rust().validate_wallet(intent.network!!, content)
+            } catch (e: RustException) {
+                Toast.makeText(this, "Invalid wallet: ${e.message}", Toast.LENGTH_LONG).show()
+                return
+            }
             val networkDir = File(filesDir, intent.network!!)
             val wallets = File(networkDir, "wallets")
             val wallet = File(wallets, name)
@@ -323,12 +337,19 @@ class ListActivity : AppCompatActivity() , ItemsAdapter.ItemGesture {
                         launchScan("Next")
                     }
                 } else {
+                    rawBytes = ArrayList()
                     when (intent.getIntExtra(C.WHAT, 0)) {
                         WALLETS -> {
                             saveWallet(result.contents)
                         }
                         PSBTS -> {
-                            savePsbt(result.contents)
+                            // compact PSBTs are binary, decoded from the raw bytes
+                            try {
+                                val hexResult = Rust().merge_qrs(filesDir.toString(), intent.network!!, arrayListOf(hexString))
+                                savePsbt(decodeHexString(hexResult)!!.toString(Charsets.UTF_8))
+                            } catch (e: RustException) {
+                                Toast.makeText(this, "This is not a psbt", Toast.LENGTH_LONG).show()
+                            }
                         }
                     }
                 }
@@ -54,12 +54,24 @@ class MainActivity : AppCompatActivity() {
                 val keyFile = "$filesDir/${network()}/keys/${key_text.text}/PRIVATE.json"
                 val walletFile = "$filesDir/${network()}/wallets/${wallet_text.text}/descriptor.json"
                 val psbtFile = "$filesDir/${network()}/psbts/${psbt_text.text}/psbt.json"
-                val result = Rust().sign(filesDir.toString(), network(), keyFile,walletFile, psbtFile )
-                AlertDialog.Builder(this).setMessage(result.toString()).create().show()
+                if (Rust().isEncrypted(keyFile)) {
+                    passphraseDialog(this) { passphrase -> sign(keyFile, walletFile, psbtFile, passphrase) }
+                } else {
+                    sign(keyFile, walletFile, psbtFile, null)
+                }
             }
         }
     }
 
+    private fun sign(keyFile: String, walletFile: String, psbtFile: String, passphrase: String?) {
+        try {
+            val result = Rust().sign(filesDir.toString(), network(), keyFile, walletFile, psbtFile, passphrase)
+            AlertDialog.Builder(this).setMessage(result.toString()).create().show()
+        } catch (e: RustException) {
+            Toast.makeText(this, e.toString(), Toast.LENGTH_LONG).show()
+        }
+    }
+
     private fun reset_select() {
         key_text.text = EMPTY_KEY
         wallet_text.text = EMPTY_WALLET
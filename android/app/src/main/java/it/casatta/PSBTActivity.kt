@@ -73,7 +73,10 @@ class PSBTActivity : AppCompatActivity() {
 
         for (i in psbtPretty.inputs.indices) {
             val input = psbtPretty.inputs[i]
-            inputsAdapter.list.add(TxInOutItem("input #$i",input.outpoint!!, input.value, "${input.path} ${input.wallet}"))
+            val signedBy = input.signed_by?.joinToString() ?: ""
+            val missing = if (input.missing_signatures != null) "missing signatures: ${input.missing_signatures}" else ""
+            val invalid = if (input.invalid_signatures.isNullOrEmpty()) "" else "invalid signatures: ${input.invalid_signatures.size}"
+            inputsAdapter.list.add(TxInOutItem("input #$i",input.outpoint!!, input.value, "${input.path} ${input.wallet} $signedBy $missing $invalid"))
         }
 
         for (i in psbtPretty.outputs.indices) {
@@ -0,0 +1,22 @@
+package it.casatta
+
+import android.content.Context
+import android.text.InputType
+import android.widget.EditText
+import androidx.appcompat.app.AlertDialog
+
+fun passphraseDialog(context: Context, onPassphrase: (String) -> Unit) {
+    val passphraseEditText = EditText(context)
+    passphraseEditText.inputType = InputType.TYPE_CLASS_TEXT or InputType.TYPE_TEXT_VARIATION_PASSWORD
+
+    val dialog: AlertDialog = AlertDialog.Builder(context)
+        .setTitle("Encrypted key")
+        .setMessage("Insert the passphrase of the key.")
+        .setView(passphraseEditText)
+        .setPositiveButton("Ok") { dialog, which ->
+            onPassphrase(passphraseEditText.text.toString())
+        }
+        .setNegativeButton("Cancel", null)
+        .create()
+    dialog.show()
+}
@@ -4,6 +4,7 @@ import com.fasterxml.jackson.databind.JsonNode
 import com.fasterxml.jackson.databind.ObjectMapper
 import com.fasterxml.jackson.databind.node.JsonNodeFactory
 import com.fasterxml.jackson.module.kotlin.KotlinModule
+import java.io.File
 
 class Rust {
     val mapper = ObjectMapper().registerModule(KotlinModule())
@@ -18,7 +19,8 @@ class Rust {
     data class ListOutput(
         val keys: List<MasterKeyOutput>,
         val wallets: List<CreateWalletOutput>,
-        val psbts: List<PsbtJsonOutput>
+        val psbts: List<PsbtJsonOutput>,
+        val encrypted_keys: List<String>
     )
 
     data class MasterKeyOutput(
@@ -34,11 +36,14 @@ class Rust {
         val xprv: String,
         val seed: Seed?,
         val dice: Dice?,
-        val fingerprint: String
+        val mnemonic: Mnemonic?,
+        val fingerprint: String,
+        val created_at: Long? = null
     )
 
     data class Seed(val hex: String, val bech32: String, val network: String)
     data class Dice(val launches: String, val faces: Int)
+    data class Mnemonic(val words: String, val passphrase_protected: Boolean)
 
     data class CreateWalletOutput(
         val wallet_file: String,
@@ -51,7 +56,10 @@ class Rust {
         val descriptor_main: String,
         val descriptor_change: String,
         val fingerprints: List<String>,
-        val required_sig: Int,
+        val required_sig: Int?,
+        val policy: String?,
+        val sorted: Boolean = false,
+        val script_type: String = "wsh",
         val created_at_height: Int
     )
 
@@ -73,7 +81,10 @@ class Rust {
         val address: String?,
         val value: String,
         val path: String,
-        val wallet: String?
+        val wallet: String?,
+        val signed_by: List<String>?,
+        val invalid_signatures: List<String>?,
+        val missing_signatures: Int?
     )
 
     data class Size (
@@ -109,9 +120,16 @@ class Rust {
         return jsonResult
     }
 
-    fun list(datadir: String, network: String, kind: String): ListOutput {
+    fun isEncrypted(keyFile: String): Boolean {
+        return mapper.readTree(File(keyFile)).has("ciphertext")
+    }
+
+    fun list(datadir: String, network: String, kind: String, passphrase: String? = null): ListOutput {
         val node = JsonNodeFactory.instance.objectNode()
         node.put("kind", kind)
+        if (passphrase != null) {
+            node.put("encryption_passphrase", passphrase)
+        }
         val req = JsonRpc("list", datadir, network, node)
         val reqString = mapper.writeValueAsString(req)
         val json = callJson(reqString)
@@ -147,9 +165,19 @@ class Rust {
         callJson(reqString)
     }
 
-    fun sign(datadir: String, network: String, key: String, wallet: String, psbt: String): JsonNode {
+    fun validate_wallet(network: String, content: String) {
+        val node = mapper.readTree(content)
+        val req = JsonRpc("validate_wallet", "", network, node)
+        val reqString = mapper.writeValueAsString(req)
+        callJson(reqString)
+    }
+
+    fun sign(datadir: String, network: String, key: String, wallet: String, psbt: String, passphrase: String? = null): JsonNode {
         val node = JsonNodeFactory.instance.objectNode()
         node.put("key", key)
+        if (passphrase != null) {
+            node.put("encryption_passphrase", passphrase)
+        }
         node.put("wallet_descriptor_file", wallet)
         node.put("psbt_file", psbt)
         node.put("total_derivations", 100)
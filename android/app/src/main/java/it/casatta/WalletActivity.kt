@@ -40,7 +40,11 @@ class WalletActivity : AppCompatActivity() {
         itemsAdapter.list.add(DescItem("Fingerprints", walletJson.wallet.fingerprints.toString() ))
         itemsAdapter.list.add(DescItem("Descriptor main", walletJson.wallet.descriptor_main ))
         itemsAdapter.list.add(DescItem("Descriptor change", walletJson.wallet.descriptor_change ))
-        itemsAdapter.list.add(DescItem("Required sig", walletJson.wallet.required_sig.toString() ))
+        if (walletJson.wallet.policy != null) {
+            itemsAdapter.list.add(DescItem("Policy", walletJson.wallet.policy ))
+        } else {
+            itemsAdapter.list.add(DescItem("Required sig", walletJson.wallet.required_sig.toString() ))
+        }
         itemsAdapter.list.add(DescItem("Created at height", walletJson.wallet.created_at_height.toString() ))
     }
 }
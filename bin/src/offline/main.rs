@@ -1,7 +1,8 @@
 use bitcoin::Network;
-use firma::{common, init_logger, offline, Result};
+use firma::{common, init_logger, offline, read_key_file, Result};
 use serde_json::Value;
 use std::convert::TryInto;
+use std::path::Path;
 use structopt::StructOpt;
 use FirmaOfflineSubcommands::*;
 
@@ -33,10 +34,13 @@ enum FirmaOfflineSubcommands {
     /// Sign a PSBT with local Master Private Key (xprv)
     Sign(offline::sign::SignOptions),
 
+    /// Combine signed PSBTs and finalize them in a raw transaction, without a bitcoin node
+    Finalize(offline::finalize::FinalizeOptions),
+
     /// Decode and print a PSBT
     Print(offline::print::PrintOptions),
 
-    /// Restore a json key from xprv, hex seed or bech32 seed
+    /// Restore a json key from xprv, hex seed, bech32 seed, bip39 mnemonic or slip39 shares
     Restore(offline::restore::RestoreOptions),
 
     /// List wallets and keys
@@ -44,13 +48,49 @@ enum FirmaOfflineSubcommands {
 
     /// Hard derive a master key from a master^2 key
     DeriveKey(offline::derive_key::DeriveKeyOptions),
+
+    /// Split the seed of a master key in SLIP-39 shares
+    SplitKey(offline::split_key::SplitKeyOptions),
+
+    /// Create a printable backup sheet of a master key, to restore it and its wallets from paper
+    BackupSheet(offline::backup_sheet::BackupSheetOptions),
+
+    /// Encrypt a plaintext private key file with a passphrase
+    EncryptKey(offline::encrypt_key::EncryptKeyOptions),
+
+    /// Export the BIP48 account xpub with key origin, to be used in a multisig wallet
+    ExportXpub(offline::export_xpub::ExportXpubOptions),
+
+    /// Create a wallet descriptor from xpubs without a bitcoin node
+    CreateWallet(offline::create_wallet::CreateWalletOptions),
+
+    /// Import a multisig wallet from a Coldcard, Specter, Sparrow (Electrum) or BSMS file
+    ImportWallet(offline::import_wallet::ImportWalletOptions),
+
+    /// Export the wallet in coldcard, specter, sparrow or bsms format for other signers and apps
+    ExportWallet(offline::export_wallet::ExportWalletOptions),
+
+    /// Import a PSBT, a wallet or an xpub from the images of its QR codes
+    ImportQr(offline::import_qr::ImportQrOptions),
+
+    /// Create a BIP129 (BSMS) key record of the account xpub, signed with the account key
+    BsmsKeyRecord(offline::bsms::KeyRecordOptions),
+
+    /// Verify a BIP129 (BSMS) descriptor record contains our key and save the wallet
+    BsmsVerify(offline::bsms::VerifyDescriptorOptions),
+
+    /// Verify an address belongs to a wallet, deriving it offline from the wallet descriptors
+    VerifyAddress(offline::address::VerifyAddressOptions),
+
+    /// Show a range of addresses of a wallet, derived offline from the wallet descriptors
+    ShowAddresses(offline::address::ShowAddressesOptions),
 }
 
 fn main() -> Result<()> {
     init_logger();
     let cmd = FirmaOfflineCommands::from_args();
 
-    let value = match launch_subcommand(&cmd) {
+    let value = match launch_subcommand(cmd) {
         Ok(value) => value,
         Err(e) => e.to_json(),
     };
@@ -60,16 +100,103 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn launch_subcommand(cmd: &FirmaOfflineCommands) -> Result<Value> {
+fn launch_subcommand(cmd: FirmaOfflineCommands) -> Result<Value> {
     let net = cmd.network;
     let datadir = &cmd.firma_datadir;
-    match &cmd.subcommand {
-        Dice(opt) => offline::dice::roll(datadir, net, &opt)?.try_into(),
-        Sign(opt) => offline::sign::start(&opt, net)?.try_into(),
-        Random(opt) => offline::random::create_key(datadir, net, &opt)?.try_into(),
+    match cmd.subcommand {
+        Dice(mut opt) => {
+            opt.mnemonic_passphrase = new_passphrase(opt.passphrase, "bip39 passphrase")?;
+            opt.encryption_passphrase = new_passphrase(opt.encrypt, "encryption passphrase")?;
+            offline::dice::roll(datadir, net, &opt)?.try_into()
+        }
+        Sign(mut opt) => {
+            opt.encryption_passphrase = key_passphrase(&opt.key)?;
+            offline::sign::start(&opt, net)?.try_into()
+        }
+        Random(mut opt) => {
+            opt.mnemonic_passphrase = new_passphrase(opt.passphrase, "bip39 passphrase")?;
+            opt.encryption_passphrase = new_passphrase(opt.encrypt, "encryption passphrase")?;
+            offline::random::create_key(datadir, net, &opt)?.try_into()
+        }
+        Finalize(opt) => offline::finalize::start(&opt)?.try_into(),
         Print(opt) => offline::print::start(datadir, net, &opt)?.try_into(),
-        Restore(opt) => offline::restore::start(datadir, net, &opt)?.try_into(),
-        DeriveKey(opt) => offline::derive_key::start(datadir, net, &opt)?.try_into(),
-        List(opt) => common::list::list(datadir, net, &opt)?.try_into(),
+        Restore(mut opt) => {
+            opt.mnemonic_passphrase = new_passphrase(opt.passphrase, "mnemonic passphrase")?;
+            opt.encryption_passphrase = new_passphrase(opt.encrypt, "encryption passphrase")?;
+            offline::restore::start(datadir, net, &opt)?.try_into()
+        }
+        DeriveKey(mut opt) => {
+            opt.encryption_passphrase = key_passphrase(&opt.from_key_file)?;
+            offline::derive_key::start(datadir, net, &opt)?.try_into()
+        }
+        List(mut opt) => {
+            if opt.decrypt {
+                opt.encryption_passphrase = Some(ask_passphrase("Passphrase of the keys: ")?);
+            }
+            common::list::list(datadir, net, &opt)?.try_into()
+        }
+        SplitKey(mut opt) => {
+            opt.encryption_passphrase = key_passphrase(&opt.key_file)?;
+            opt.mnemonic_passphrase = new_passphrase(opt.passphrase, "SLIP-39 passphrase")?;
+            offline::split_key::start(&opt)?.try_into()
+        }
+        BackupSheet(mut opt) => {
+            opt.encryption_passphrase = key_passphrase(&opt.key_file)?;
+            offline::backup_sheet::start(datadir, net, &opt)?.try_into()
+        }
+        EncryptKey(mut opt) => {
+            opt.encryption_passphrase =
+                new_passphrase(true, "encryption passphrase")?.unwrap_or_default();
+            offline::encrypt_key::start(&opt)?.try_into()
+        }
+        ExportXpub(mut opt) => {
+            opt.encryption_passphrase = key_passphrase(&opt.key_file)?;
+            offline::export_xpub::start(datadir, net, &opt)?.try_into()
+        }
+        CreateWallet(opt) => offline::create_wallet::start(datadir, net, &opt)?.try_into(),
+        ImportWallet(opt) => offline::import_wallet::start(datadir, net, &opt)?.try_into(),
+        ExportWallet(opt) => offline::export_wallet::start(datadir, net, &opt)?.try_into(),
+        ImportQr(opt) => offline::import_qr::start(datadir, net, &opt)?.try_into(),
+        BsmsKeyRecord(mut opt) => {
+            opt.encryption_passphrase = key_passphrase(&opt.key_file)?;
+            offline::bsms::key_record(datadir, net, &opt)?.try_into()
+        }
+        BsmsVerify(mut opt) => {
+            opt.encryption_passphrase = key_passphrase(&opt.key_file)?;
+            offline::bsms::verify_descriptor(datadir, net, &opt)?.try_into()
+        }
+        VerifyAddress(opt) => offline::address::verify(datadir, net, &opt)?.try_into(),
+        ShowAddresses(opt) => offline::address::show(datadir, net, &opt)?.try_into(),
+    }
+}
+
+/// Passphrases are never given as arguments, visible in the shell history and to other users,
+/// they are asked at the prompt without echo or read from stdin when it is not a terminal
+fn ask_passphrase(prompt: &str) -> Result<String> {
+    Ok(rpassword::prompt_password_stderr(prompt)?)
+}
+
+/// New passphrase named `name`, asked twice to avoid typos, `None` if not asked
+fn new_passphrase(ask: bool, name: &str) -> Result<Option<String>> {
+    if !ask {
+        return Ok(None);
+    }
+    let passphrase = ask_passphrase(&format!("Enter the {}: ", name))?;
+    if passphrase.is_empty() {
+        return Err(format!("{} cannot be empty", name).into());
+    }
+    if ask_passphrase(&format!("Repeat the {}: ", name))? != passphrase {
+        return Err(format!("{}s do not match", name).into());
+    }
+    Ok(Some(passphrase))
+}
+
+/// Passphrase to decrypt `key_file`, asked only if the file is encrypted
+fn key_passphrase(key_file: &Path) -> Result<Option<String>> {
+    if read_key_file(&key_file.to_path_buf())?.is_encrypted() {
+        let prompt = format!("Passphrase of {}: ", key_file.display());
+        Ok(Some(ask_passphrase(&prompt)?))
+    } else {
+        Ok(None)
     }
 }
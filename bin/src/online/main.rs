@@ -27,6 +27,18 @@ enum FirmaOnlineSubcommands {
     /// Create a new watch-only wallet
     CreateWallet(firma::online::create_wallet::CreateWalletOptions),
 
+    /// Import as watch-only a wallet created offline with firma-offline create-wallet
+    ImportWallet(firma::online::import_wallet::ImportWalletOptions),
+
+    /// Export the wallet in coldcard, specter, sparrow or bsms format for other signers and apps
+    ExportWallet(firma::online::export_wallet::ExportWalletOptions),
+
+    /// Start a BIP129 (BSMS) multisig setup session as coordinator
+    BsmsStart(firma::online::bsms::BsmsStartOptions),
+
+    /// Verify the BIP129 key records of the signers and create the wallet descriptor record
+    BsmsFinalize(firma::online::bsms::BsmsFinalizeOptions),
+
     /// Rescan the blockchain, useful when importing an existing wallet
     Rescan(firma::online::rescan::RescanOptions),
 
@@ -61,8 +73,21 @@ fn start() -> Result<Value> {
     debug!("firma-online start");
     let cmd = FirmaOnlineCommands::from_args();
 
+    // these commands do not need the bitcoin node
+    match cmd.subcommand {
+        ExportWallet(ref opt) => {
+            return firma::online::export_wallet::start(&cmd.context, opt)?.try_into()
+        }
+        BsmsStart(ref opt) => return firma::online::bsms::start(&cmd.context, opt)?.try_into(),
+        BsmsFinalize(ref opt) => {
+            return firma::online::bsms::finalize(&cmd.context, opt)?.try_into()
+        }
+        _ => (),
+    }
+
     let daemon_opts = match &cmd.subcommand {
         CreateWallet(ref opt) => opt.daemon_opts.clone(),
+        ImportWallet(ref opt) => opt.daemon_opts.clone(),
         _ => {
             let (wallet, _) = cmd.context.load_wallet_and_index()?;
             wallet
@@ -78,10 +103,9 @@ fn start() -> Result<Value> {
         cmd.context.clone(),
     )?;
 
-    if let CreateWallet(_) = cmd.subcommand {
-        // do nothing, I need the else branch (!matches!() require too recent rust version)
-    } else {
-        wallet.load_if_unloaded(&cmd.context.wallet_name)?;
+    match cmd.subcommand {
+        CreateWallet(_) | ImportWallet(_) => (), // the wallet is created in the node by these
+        _ => wallet.load_if_unloaded(&cmd.context.wallet_name)?,
     }
 
     let result = wallet.client.get_blockchain_info()?;
@@ -102,6 +126,10 @@ fn start() -> Result<Value> {
 
     match cmd.subcommand {
         CreateWallet(ref opt) => wallet.create(&daemon_opts, opt, result.blocks)?.try_into(),
+        ImportWallet(ref opt) => wallet.import_wallet(&daemon_opts, opt)?.try_into(),
+        ExportWallet(_) | BsmsStart(_) | BsmsFinalize(_) => {
+            unreachable!("returned before connecting to the node")
+        }
         GetAddress(ref opt) => wallet.get_address(opt.index, false)?.try_into(),
         CreateTx(ref opt) => wallet.create_tx(opt)?.try_into(),
         SendTx(ref opt) => wallet.send_tx(opt)?.try_into(),
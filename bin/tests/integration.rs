@@ -93,9 +93,14 @@ fn integration_test() {
             .to_string(),
     ];
     let created_2of2_wallet = firma_2of2
-        .online_create_wallet(&node_url, &cookie_file_str, 2, &xpubs)
+        .online_create_wallet(&node_url, &cookie_file_str, 2, &xpubs, vec![])
         .unwrap();
     assert_eq!(&created_2of2_wallet.wallet.name, &name_2of2);
+    assert!(created_2of2_wallet.wallet.sorted);
+    assert!(created_2of2_wallet
+        .wallet
+        .descriptor_main
+        .starts_with("wsh(sortedmulti(2,"));
 
     // create firma 2of3 wallet
     let name_2of3 = "n2of3".to_string();
@@ -107,9 +112,14 @@ fn integration_test() {
     let xpubs_2of3: Vec<String> = vec.iter().map(|e| e.public_file_str().unwrap()).collect();
     let xprvs_2of3: Vec<String> = vec.iter().map(|e| e.private_file_str().unwrap()).collect();
     let created_2of3_wallet = firma_2of3
-        .online_create_wallet(&node_url, &cookie_file_str, 2, &xpubs_2of3)
+        .online_create_wallet(&node_url, &cookie_file_str, 2, &xpubs_2of3, vec!["--unsorted"])
         .unwrap();
     assert_eq!(&created_2of3_wallet.wallet.name, &name_2of3);
+    assert!(!created_2of3_wallet.wallet.sorted);
+    assert!(created_2of3_wallet
+        .wallet
+        .descriptor_main
+        .starts_with("wsh(multi(2,"));
 
     // create address for firma 2of2
     let address_2of2 = firma_2of2.online_get_address().unwrap().address;
@@ -121,6 +131,15 @@ fn integration_test() {
     let fund_2of3 = 100_000_000;
     client_send_to_address(&client_default, &address_2of3, fund_2of3).unwrap();
 
+    // addresses given by the node are derived also offline from the wallet descriptors
+    let verified = firma_2of2.offline_verify_address(&address_2of2).unwrap();
+    assert_eq!(verified.derived.index, 0);
+    assert!(!verified.derived.change);
+    let verified = firma_2of3.offline_verify_address(&address_2of3).unwrap();
+    assert_eq!(verified.derived.address, address_2of3);
+    let shown = firma_2of2.offline_show_addresses().unwrap();
+    assert_eq!(shown.addresses[0].address, address_2of2);
+
     // generate 1 block so funds are confirmed
     client_default.generate_to_address(1, &address).unwrap();
 
@@ -202,6 +221,18 @@ fn integration_test() {
         .info
         .iter()
         .any(|msg| msg.contains("#Sending_to_a_different_script_type"))); // core generates a different address type
+    assert!(sign_a
+        .inputs
+        .iter()
+        .all(|i| i.missing_signatures == Some(1)));
+    let sign_a_file = sign_a.psbt_file.to_str().unwrap();
+    let not_enough = firma_2of3
+        .online("send-tx", vec!["--psbt-file", sign_a_file])
+        .unwrap();
+    assert!(not_enough["error"]
+        .as_str()
+        .unwrap()
+        .contains("has 1 of the 2 required signatures"));
     let sign_b = firma_2of3
         .offline_sign(pstb_file_str, &xprvs_2of3[1])
         .unwrap();
@@ -259,6 +290,10 @@ fn integration_test() {
     let sign_b = firma_2of3
         .offline_sign(sign_a.psbt_file.to_str().unwrap(), &xprvs_2of3[2])
         .unwrap();
+    assert!(sign_b
+        .inputs
+        .iter()
+        .all(|i| i.missing_signatures == Some(0) && i.signed_by.len() == 2));
     assert_eq!(sign_a.fee.absolute, sign_b.fee.absolute);
     let sent_tx = firma_2of3
         .online_send_tx(vec![&sign_b.psbt_file.to_str().unwrap()])
@@ -269,6 +304,171 @@ fn integration_test() {
     let expected = balance_2of3_2.satoshi - value_sent - sign_a.fee.absolute;
     assert_eq!(expected, balance_2of3_3.satoshi);
 
+    // create firma 2of2 wallet with BIP48 account xpubs, fund it and spend
+    let name_bip48 = "n2of2bip48".to_string();
+    let firma_bip48 = FirmaCommand::new(&firma_exe_dir, &name_bip48).unwrap();
+    let mut xprvs_bip48 = vec![];
+    let mut xpubs_bip48 = vec![];
+    for i in 0..2 {
+        let key = firma_bip48.offline_random(&format!("a{}", i)).unwrap();
+        let account = firma_bip48
+            .offline_export_xpub(&key.private_file_str().unwrap())
+            .unwrap();
+        assert_eq!(account.xpub.fingerprint(), key.key.fingerprint);
+        xprvs_bip48.push(key.private_file_str().unwrap());
+        xpubs_bip48.push(account.public_file.to_str().unwrap().to_string());
+    }
+    let created_bip48_wallet = firma_bip48
+        .online_create_wallet(&node_url, &cookie_file_str, 2, &xpubs_bip48, vec![])
+        .unwrap();
+    assert!(created_bip48_wallet
+        .wallet
+        .descriptor_main
+        .contains("/48'/1'/0'/2']"));
+    let address_bip48 = firma_bip48.online_get_address().unwrap().address;
+    let fund_bip48 = 100_000_000;
+    client_send_to_address(&client_default, &address_bip48, fund_bip48).unwrap();
+    client_default.generate_to_address(1, &address).unwrap();
+    let value_sent = rng.gen_range(1_000, 1_000_000);
+    let recipients = vec![(address.clone(), value_sent)];
+    let create_tx = firma_bip48
+        .online_create_tx(recipients, &rnd_string())
+        .unwrap();
+    let pstb_file_str = create_tx.psbt_file.to_str().unwrap();
+    let sign_a = firma_bip48
+        .offline_sign(pstb_file_str, &xprvs_bip48[0])
+        .unwrap();
+    let sign_b = firma_bip48
+        .offline_sign(pstb_file_str, &xprvs_bip48[1])
+        .unwrap();
+    let sent_tx = firma_bip48
+        .online_send_tx(vec![
+            &sign_a.psbt_file.to_str().unwrap(),
+            &sign_b.psbt_file.to_str().unwrap(),
+        ])
+        .unwrap();
+    assert!(sent_tx.broadcasted);
+    client_default.generate_to_address(1, &address).unwrap();
+    let balance_bip48 = firma_bip48.online_balance().unwrap();
+    let expected = fund_bip48 - value_sent - sign_a.fee.absolute;
+    assert_eq!(expected, balance_bip48.satoshi);
+
+    // create firma 2of2 nested segwit and legacy wallets, fund them and spend
+    for script_type in &["sh-wsh", "sh"] {
+        let name = format!("n2of2{}", script_type);
+        let firma = FirmaCommand::new(&firma_exe_dir, &name).unwrap();
+        let keys: Vec<MasterKeyOutput> = (0..2)
+            .map(|i| firma.offline_random(&format!("s{}", i)).unwrap())
+            .collect();
+        let xpubs: Vec<String> = keys.iter().map(|e| e.public_file_str().unwrap()).collect();
+        let created = firma
+            .online_create_wallet(
+                &node_url,
+                &cookie_file_str,
+                2,
+                &xpubs,
+                vec!["--script-type", script_type],
+            )
+            .unwrap();
+        assert_eq!(created.wallet.script_type.to_string(), *script_type);
+        let address_sh = firma.online_get_address().unwrap().address;
+        assert!(address_sh.to_string().starts_with('2'));
+        let fund = 100_000_000;
+        client_send_to_address(&client_default, &address_sh, fund).unwrap();
+        client_default.generate_to_address(1, &address).unwrap();
+        let value_sent = rng.gen_range(1_000, 1_000_000);
+        let recipients = vec![(address.clone(), value_sent)];
+        let create_tx = firma.online_create_tx(recipients, &rnd_string()).unwrap();
+        let pstb_file_str = create_tx.psbt_file.to_str().unwrap();
+        let sign_a = firma
+            .offline_sign(pstb_file_str, &keys[0].private_file_str().unwrap())
+            .unwrap();
+        let sign_b = firma
+            .offline_sign(pstb_file_str, &keys[1].private_file_str().unwrap())
+            .unwrap();
+        let sent_tx = firma
+            .online_send_tx(vec![
+                &sign_a.psbt_file.to_str().unwrap(),
+                &sign_b.psbt_file.to_str().unwrap(),
+            ])
+            .unwrap();
+        assert!(sent_tx.broadcasted);
+        client_default.generate_to_address(1, &address).unwrap();
+        let balance = firma.online_balance().unwrap();
+        assert_eq!(fund - value_sent - sign_a.fee.absolute, balance.satoshi);
+    }
+
+    // create firma single signature wallets, fund them and spend
+    for (script_type, prefix) in &[("wpkh", "bcrt1q"), ("sh-wpkh", "2")] {
+        let name = format!("single{}", script_type);
+        let firma = FirmaCommand::new(&firma_exe_dir, &name).unwrap();
+        let key = firma.offline_random("single").unwrap();
+        let xpubs = vec![key.public_file_str().unwrap()];
+        let created = firma
+            .online_create_wallet(
+                &node_url,
+                &cookie_file_str,
+                1,
+                &xpubs,
+                vec!["--script-type", script_type],
+            )
+            .unwrap();
+        assert!(created.wallet.is_single_sig());
+        assert_eq!(created.wallet.required_sig, Some(1));
+        let address_single = firma.online_get_address().unwrap().address;
+        assert!(address_single.to_string().starts_with(prefix));
+        let fund = 100_000_000;
+        client_send_to_address(&client_default, &address_single, fund).unwrap();
+        client_default.generate_to_address(1, &address).unwrap();
+        let value_sent = rng.gen_range(1_000, 1_000_000);
+        let recipients = vec![(address.clone(), value_sent)];
+        let create_tx = firma.online_create_tx(recipients, &rnd_string()).unwrap();
+        let pstb_file_str = create_tx.psbt_file.to_str().unwrap();
+        let sign = firma
+            .offline_sign(pstb_file_str, &key.private_file_str().unwrap())
+            .unwrap();
+        assert!(sign.inputs.iter().all(|i| i.wallet == name));
+        let sent_tx = firma
+            .online_send_tx(vec![&sign.psbt_file.to_str().unwrap()])
+            .unwrap();
+        assert!(sent_tx.broadcasted);
+        client_default.generate_to_address(1, &address).unwrap();
+        let balance = firma.online_balance().unwrap();
+        assert_eq!(fund - value_sent - sign.fee.absolute, balance.satoshi);
+    }
+
+    // create a 2of2 wallet offline, import it in the node and receive funds
+    let name_imported = "imported".to_string();
+    let firma_imported = FirmaCommand::new(&firma_exe_dir, &name_imported).unwrap();
+    let i1 = firma_imported.offline_random("i1").unwrap();
+    let i2 = firma_imported.offline_random("i2").unwrap();
+    let xpubs = vec![i1.public_file_str().unwrap(), i2.public_file_str().unwrap()];
+    let created_offline = firma_imported.offline_create_wallet(2, &xpubs).unwrap();
+    assert!(created_offline.wallet.daemon_opts.is_none());
+    let imported = firma_imported
+        .online_import_wallet(&node_url, &cookie_file_str)
+        .unwrap();
+    assert_eq!(
+        imported.wallet.descriptor_main,
+        created_offline.wallet.descriptor_main
+    );
+    assert!(imported.wallet.daemon_opts.is_some());
+    let address_imported = firma_imported.online_get_address().unwrap().address;
+    let shown = firma_imported.offline_show_addresses().unwrap();
+    assert_eq!(shown.addresses[0].address, address_imported);
+    let exported = firma_imported.offline_export_wallet("bsms").unwrap();
+    assert_eq!(exported.content.lines().last().unwrap(), address_imported.to_string());
+    assert!(exported.export_file.exists());
+    let exported_online = firma_imported.online_export_wallet("specter").unwrap();
+    assert!(exported_online
+        .content
+        .contains(&created_offline.wallet.descriptor_main));
+    let fund = 100_000_000;
+    client_send_to_address(&client_default, &address_imported, fund).unwrap();
+    client_default.generate_to_address(1, &address).unwrap();
+    let balance = firma_imported.online_balance().unwrap();
+    assert_eq!(fund, balance.satoshi);
+
     let coins_output = firma_2of3.online_list_coins().unwrap();
     assert!(!coins_output.coins.is_empty());
 
@@ -342,6 +542,7 @@ impl FirmaCommand {
         cookie_file: &str,
         required_sig: u8,
         xpubs: &Vec<String>,
+        extra_args: Vec<&str>,
     ) -> Result<CreateWalletOutput> {
         let required_sig = format!("{}", required_sig);
         let mut args = vec![
@@ -356,12 +557,27 @@ impl FirmaCommand {
             args.push("--xpub-file");
             args.push(xpub);
         }
+        args.extend(extra_args);
         let result = self.online("create-wallet", args);
         let value = unwrap_as_json(result);
         let output = from_value(value).unwrap();
         Ok(output)
     }
 
+    fn online_import_wallet(
+        &self,
+        node_url: &str,
+        cookie_file: &str,
+    ) -> Result<CreateWalletOutput> {
+        let args = vec!["--url", node_url, "--cookie-file", cookie_file];
+        Ok(from_value(self.online("import-wallet", args).unwrap())?)
+    }
+
+    fn online_export_wallet(&self, format: &str) -> Result<ExportWalletOutput> {
+        let args = vec!["--format", format];
+        Ok(from_value(self.online("export-wallet", args).unwrap())?)
+    }
+
     fn online_get_address(&self) -> Result<GetAddressOutput> {
         Ok(from_value(self.online("get-address", vec![]).unwrap())?)
     }
@@ -468,6 +684,13 @@ impl FirmaCommand {
         Ok(output)
     }
 
+    pub fn offline_export_xpub(&self, key_file: &str) -> Result<ExportXpubOutput> {
+        let result = self.offline("export-xpub", vec!["--key-file", key_file]);
+        let value = unwrap_as_json(result);
+        let output = from_value(value).unwrap();
+        Ok(output)
+    }
+
     pub fn offline_sign(&self, psbt_file: &str, key_file: &str) -> Result<PsbtPrettyPrint> {
         let result = self.offline(
             "sign",
@@ -486,6 +709,40 @@ impl FirmaCommand {
         Ok(output)
     }
 
+    fn offline_create_wallet(
+        &self,
+        required_sig: u8,
+        xpubs: &Vec<String>,
+    ) -> Result<CreateWalletOutput> {
+        let required_sig = format!("{}", required_sig);
+        let mut args = vec!["--wallet-name", &self.wallet_name, "-r", &required_sig];
+        for xpub in xpubs.iter() {
+            args.push("--xpub-file");
+            args.push(xpub);
+        }
+        let value = map_json_error(self.offline("create-wallet", args))?;
+        Ok(from_value(value)?)
+    }
+
+    fn offline_verify_address(&self, address: &Address) -> Result<VerifyAddressOutput> {
+        let address = address.to_string();
+        let args = vec!["--wallet", &self.wallet_name, "--address", &address];
+        let value = map_json_error(self.offline("verify-address", args))?;
+        Ok(from_value(value)?)
+    }
+
+    fn offline_show_addresses(&self) -> Result<ShowAddressesOutput> {
+        let args = vec!["--wallet", &self.wallet_name, "--count", "5"];
+        let value = map_json_error(self.offline("show-addresses", args))?;
+        Ok(from_value(value)?)
+    }
+
+    fn offline_export_wallet(&self, format: &str) -> Result<ExportWalletOutput> {
+        let args = vec!["--wallet", &self.wallet_name, "--format", format];
+        let value = map_json_error(self.offline("export-wallet", args))?;
+        Ok(from_value(value)?)
+    }
+
     fn offline_list(&self, kind: Kind) -> Result<ListOutput> {
         Ok(from_value(
             self.offline("list", vec!["--kind", &kind.to_string()])
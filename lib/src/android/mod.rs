@@ -1,4 +1,16 @@
 use crate::common::list::ListOptions;
+use crate::common::qr_reader::QrData;
+use crate::common::transfer;
+use crate::offline::address::{ShowAddressesOptions, VerifyAddressOptions};
+use crate::offline::backup_sheet::BackupSheetOptions;
+use crate::offline::bsms::{KeyRecordOptions, VerifyDescriptorOptions};
+use crate::offline::create_wallet::CreateWalletOptions;
+use crate::offline::encrypt_key::EncryptKeyOptions;
+use crate::offline::export_wallet::ExportWalletOptions;
+use crate::offline::export_xpub::ExportXpubOptions;
+use crate::offline::finalize::FinalizeOptions;
+use crate::offline::import_qr::ImportQrOptions;
+use crate::offline::import_wallet::ImportWalletOptions;
 use crate::offline::print::PrintOptions;
 use crate::offline::random::RandomOptions;
 use crate::offline::restore::RestoreOptions;
@@ -51,7 +63,21 @@ fn rust_call(c_str: &CStr) -> Result<CString> {
             for string in string_values {
                 values.push(hex::decode(&string)?);
             }
-            match crate::common::qr::merge_qrs(values) {
+            let single = values.len() == 1 && values[0].first().map(|b| b >> 4) != Some(3);
+            let merged = if single {
+                QrData::from_codewords(values.remove(0)).and_then(|qr| qr.content())
+            } else {
+                crate::common::qr::merge_qrs(values)
+            };
+            // compact PSBTs are given back as PSBT json
+            let merged = merged.and_then(|bytes| {
+                if transfer::is_compact_psbt(&bytes) {
+                    Ok(serde_json::to_vec(&transfer::decode_psbt(&bytes)?)?)
+                } else {
+                    Ok(bytes)
+                }
+            });
+            match merged {
                 Ok(merged) => hex::encode(merged).into(),
                 Err(e) => e.to_json(),
             }
@@ -61,16 +87,81 @@ fn rust_call(c_str: &CStr) -> Result<CString> {
             crate::common::qr::create_qrs(&opts)?;
             Value::Null
         }
+        Some("validate_wallet") => {
+            let wallet: WalletJson = serde_json::from_value(args.clone())?;
+            wallet.validate()?;
+            Value::Null
+        }
         Some("sign") => {
             let opts: SignOptions = serde_json::from_value(args.clone())?;
             let result = crate::offline::sign::start(&opts, network)?;
             serde_json::to_value(result)?
         }
+        Some("finalize") => {
+            let opts: FinalizeOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::finalize::start(&opts)?;
+            serde_json::to_value(result)?
+        }
         Some("restore") => {
             let opts: RestoreOptions = serde_json::from_value(args.clone())?;
             let result = crate::offline::restore::start(datadir, network, &opts)?;
             serde_json::to_value(result)?
         }
+        Some("backup_sheet") => {
+            let opts: BackupSheetOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::backup_sheet::start(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("encrypt_key") => {
+            let opts: EncryptKeyOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::encrypt_key::start(&opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("export_xpub") => {
+            let opts: ExportXpubOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::export_xpub::start(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("create_wallet") => {
+            let opts: CreateWalletOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::create_wallet::start(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("import_wallet") => {
+            let opts: ImportWalletOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::import_wallet::start(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("export_wallet") => {
+            let opts: ExportWalletOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::export_wallet::start(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("import_qr") => {
+            let opts: ImportQrOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::import_qr::start(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("bsms_key_record") => {
+            let opts: KeyRecordOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::bsms::key_record(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("bsms_verify") => {
+            let opts: VerifyDescriptorOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::bsms::verify_descriptor(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("verify_address") => {
+            let opts: VerifyAddressOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::address::verify(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
+        Some("show_addresses") => {
+            let opts: ShowAddressesOptions = serde_json::from_value(args.clone())?;
+            let result = crate::offline::address::show(datadir, network, &opts)?;
+            serde_json::to_value(result)?
+        }
         Some("print") => {
             let opts: PrintOptions = serde_json::from_value(args.clone())?;
             let result = crate::offline::print::start(datadir, network, &opts)?;
@@ -0,0 +1,195 @@
+use crate::common::key_origin::XpubWithOrigin;
+use crate::*;
+use bitcoin::secp256k1::recovery::{RecoverableSignature, RecoveryId};
+use bitcoin::secp256k1::{Message, Secp256k1};
+use bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::util::misc::signed_msg_hash;
+use std::fmt;
+use std::str::FromStr;
+
+/// First line of BIP129 records
+pub const BSMS_VERSION: &str = "BSMS 1.0";
+
+/// Token of sessions where records are not encrypted, the only one supported since any other
+/// token requires records encrypted as specified in BIP129
+pub const TOKEN_NO_ENCRYPTION: &str = "00";
+
+/// Header byte of a bitcoin signed message with a compressed key, recovery id is added
+const SIGNED_MSG_HEADER: u8 = 27 + 4;
+
+/// Token must be `00`, 64 or 128 bits tokens are valid but their encrypted records are not
+/// supported
+pub fn validate_token(token: &str) -> Result<()> {
+    if token == TOKEN_NO_ENCRYPTION {
+        return Ok(());
+    }
+    let valid_length = token.len() == 16 || token.len() == 32;
+    if !valid_length || hex::decode(token).is_err() {
+        return Err(format!("invalid bsms token {}", token).into());
+    }
+    Err(format!(
+        "bsms token {} requires encrypted records, not supported, use token {}",
+        token, TOKEN_NO_ENCRYPTION
+    )
+    .into())
+}
+
+/// Signer key record of a BIP129 session, signed with the private key of `key`
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyRecord {
+    pub token: String,
+    pub key: XpubWithOrigin,
+    pub description: String,
+    /// Bitcoin signed message of the first four lines, base64 encoded
+    pub signature: String,
+}
+
+impl KeyRecord {
+    /// Create the record of the xpub of `xprv`, which must be the private key derived at `key`
+    pub fn sign(
+        token: &str,
+        key: XpubWithOrigin,
+        xprv: &ExtendedPrivKey,
+        description: &str,
+    ) -> Result<KeyRecord> {
+        validate_token(token)?;
+        if description.contains('\n') {
+            return Err("bsms key description cannot contain new lines".into());
+        }
+        let secp = Secp256k1::signing_only();
+        if key.xpub != ExtendedPubKey::from_private(&secp, xprv) {
+            return Err("private key does not match the key of the record".into());
+        }
+        let mut record = KeyRecord {
+            token: token.to_string(),
+            key,
+            description: description.to_string(),
+            signature: String::new(),
+        };
+        let signature = secp.sign_recoverable(&record.message_hash()?, &xprv.private_key.key);
+        let (recovery_id, compact) = signature.serialize_compact();
+        let mut data = vec![SIGNED_MSG_HEADER + recovery_id.to_i32() as u8];
+        data.extend_from_slice(&compact);
+        record.signature = base64::encode(&data);
+        Ok(record)
+    }
+
+    /// Check the record belongs to the session with `token` and it is signed by its key
+    pub fn verify(&self, token: &str) -> Result<()> {
+        if self.token != token {
+            return Err(format!(
+                "key record token {} does not match session token {}",
+                self.token, token
+            )
+            .into());
+        }
+        let data = base64::decode(&self.signature)?;
+        if data.len() != 65 || data[0] < 27 || data[0] > 34 {
+            return Err("invalid key record signature".into());
+        }
+        let recovery_id = RecoveryId::from_i32(((data[0] - 27) & 3) as i32)?;
+        let signature = RecoverableSignature::from_compact(&data[1..], recovery_id)?;
+        let secp = Secp256k1::verification_only();
+        let public_key = secp.recover(&self.message_hash()?, &signature)?;
+        if public_key != self.key.xpub.public_key.key {
+            return Err(format!("key record of {} has an invalid signature", self.key).into());
+        }
+        Ok(())
+    }
+
+    fn message_hash(&self) -> Result<Message> {
+        let message = format!(
+            "{}\n{}\n{}\n{}",
+            BSMS_VERSION, self.token, self.key, self.description
+        );
+        Ok(Message::from_slice(&signed_msg_hash(&message)[..])?)
+    }
+}
+
+impl fmt::Display for KeyRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}\n{}\n{}\n{}\n{}",
+            BSMS_VERSION, self.token, self.key, self.description, self.signature
+        )
+    }
+}
+
+impl FromStr for KeyRecord {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let lines: Vec<&str> = s.trim().lines().map(|l| l.trim()).collect();
+        match lines.as_slice() {
+            [version, token, key, description, signature] => {
+                if *version != BSMS_VERSION {
+                    return Err(format!("unsupported bsms version {}", version).into());
+                }
+                validate_token(token)?;
+                let key = XpubWithOrigin::from_str(key)?;
+                if key.origin.is_none() {
+                    return Err("bsms key record without key origin".into());
+                }
+                Ok(KeyRecord {
+                    token: token.to_string(),
+                    key,
+                    description: description.to_string(),
+                    signature: signature.to_string(),
+                })
+            }
+            _ => Err("bsms key record must have 5 lines".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::bsms::{validate_token, KeyRecord, TOKEN_NO_ENCRYPTION};
+    use crate::common::fixtures::ACCOUNT_XPUB;
+    use crate::common::key_origin::XpubWithOrigin;
+    use crate::PrivateMasterKey;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::Network;
+    use std::str::FromStr;
+
+    // bitcoin signed message of the record by the account key, computed with bitcoin 0.28
+    const SIGNATURE: &str =
+        "INWPjFY00l5/or+ZGZOEP/SkdORnZHhYA1iK/Jb0FUXCFH7+Tq7yvByILmAc9HImFX7XMuXzIUJu7poYp+RGth8=";
+
+    #[test]
+    fn test_key_record() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let master = PrivateMasterKey::new(Network::Testnet, &seed, "bip32-vector-1").unwrap();
+        let key = XpubWithOrigin::from_str(ACCOUNT_XPUB).unwrap();
+        let path = &key.origin.as_ref().unwrap().path;
+        let xprv = master
+            .xprv
+            .derive_priv(&Secp256k1::signing_only(), path)
+            .unwrap();
+
+        let record =
+            KeyRecord::sign(TOKEN_NO_ENCRYPTION, key.clone(), &xprv, "firma signer").unwrap();
+        assert_eq!(record.signature, SIGNATURE);
+        record.verify(TOKEN_NO_ENCRYPTION).unwrap();
+        let text = record.to_string();
+        assert_eq!(text.lines().count(), 5);
+        assert_eq!(KeyRecord::from_str(&text).unwrap(), record);
+
+        let result = record.verify("a54044308ceac9b7");
+        assert!(result.unwrap_err().to_string().contains("does not match"));
+        let mut tampered = record.clone();
+        tampered.description = "another signer".to_string();
+        assert!(tampered.verify(TOKEN_NO_ENCRYPTION).is_err());
+        let result = KeyRecord::sign(TOKEN_NO_ENCRYPTION, key, &master.xprv, "firma signer");
+        assert!(result.is_err());
+
+        assert!(validate_token("00").is_ok());
+        let result = validate_token("a54044308ceac9b7");
+        assert!(result.unwrap_err().to_string().contains("not supported"));
+        let text = text.replace("\n00\n", "\na54044308ceac9b7\n");
+        assert!(KeyRecord::from_str(&text).is_err());
+        assert!(validate_token("0011").is_err());
+        assert!(validate_token("zz4044308ceac9b7").is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::*;
+
+/// Max nesting of arrays, maps and tags accepted while decoding
+const MAX_DEPTH: usize = 32;
+
+/// Subset of CBOR (RFC 7049) values used by UR payloads, without floats and indefinite lengths
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cbor {
+    Unsigned(u64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+    Bool(bool),
+}
+
+impl Cbor {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = vec![];
+        self.encode_to(&mut out);
+        out
+    }
+
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Cbor::Unsigned(value) => write_head(0, *value, out),
+            Cbor::Bytes(bytes) => {
+                write_head(2, bytes.len() as u64, out);
+                out.extend_from_slice(bytes);
+            }
+            Cbor::Text(text) => {
+                write_head(3, text.len() as u64, out);
+                out.extend_from_slice(text.as_bytes());
+            }
+            Cbor::Array(items) => {
+                write_head(4, items.len() as u64, out);
+                items.iter().for_each(|i| i.encode_to(out));
+            }
+            Cbor::Map(entries) => {
+                write_head(5, entries.len() as u64, out);
+                for (key, value) in entries {
+                    key.encode_to(out);
+                    value.encode_to(out);
+                }
+            }
+            Cbor::Tag(tag, value) => {
+                write_head(6, *tag, out);
+                value.encode_to(out);
+            }
+            Cbor::Bool(value) => out.push(if *value { 0xf5 } else { 0xf4 }),
+        }
+    }
+
+    /// Decode a single CBOR value, which must use all the given bytes
+    pub fn decode(bytes: &[u8]) -> Result<Cbor> {
+        let mut pos = 0;
+        let value = decode_item(bytes, &mut pos, 0)?;
+        if pos != bytes.len() {
+            return Err(format!("{} trailing bytes after cbor value", bytes.len() - pos).into());
+        }
+        Ok(value)
+    }
+
+    /// Value of the map entry with the unsigned integer `key`
+    pub fn get(&self, key: u64) -> Option<&Cbor> {
+        match self {
+            Cbor::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| *k == Cbor::Unsigned(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Content of the tag, error if the value is not tagged with `tag`
+    pub fn untag(&self, tag: u64) -> Result<&Cbor> {
+        match self {
+            Cbor::Tag(t, value) if *t == tag => Ok(value),
+            _ => Err(format!("expected cbor tag {}", tag).into()),
+        }
+    }
+
+    pub fn as_u64(&self) -> Result<u64> {
+        match self {
+            Cbor::Unsigned(value) => Ok(*value),
+            _ => Err("expected cbor unsigned integer".into()),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Result<&[u8]> {
+        match self {
+            Cbor::Bytes(bytes) => Ok(bytes),
+            _ => Err("expected cbor byte string".into()),
+        }
+    }
+
+    pub fn as_array(&self) -> Result<&[Cbor]> {
+        match self {
+            Cbor::Array(items) => Ok(items),
+            _ => Err("expected cbor array".into()),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool> {
+        match self {
+            Cbor::Bool(value) => Ok(*value),
+            _ => Err("expected cbor boolean".into()),
+        }
+    }
+}
+
+fn write_head(major: u8, value: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    if value < 24 {
+        out.push(major | value as u8);
+    } else if value <= u64::from(u8::max_value()) {
+        out.push(major | 24);
+        out.push(value as u8);
+    } else if value <= u64::from(u16::max_value()) {
+        out.push(major | 25);
+        out.extend_from_slice(&(value as u16).to_be_bytes());
+    } else if value <= u64::from(u32::max_value()) {
+        out.push(major | 26);
+        out.extend_from_slice(&(value as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+fn take<'a>(bytes: &'a [u8], pos: &mut usize, len: usize) -> Result<&'a [u8]> {
+    let end = pos
+        .checked_add(len)
+        .filter(|end| *end <= bytes.len())
+        .ok_or_else(fn_err("unexpected end of cbor data"))?;
+    let result = &bytes[*pos..end];
+    *pos = end;
+    Ok(result)
+}
+
+fn decode_item(bytes: &[u8], pos: &mut usize, depth: usize) -> Result<Cbor> {
+    if depth > MAX_DEPTH {
+        return Err("cbor value nested too deeply".into());
+    }
+    let initial = take(bytes, pos, 1)?[0];
+    let major = initial >> 5;
+    let info = initial & 0x1f;
+    if major == 7 {
+        return match info {
+            20 => Ok(Cbor::Bool(false)),
+            21 => Ok(Cbor::Bool(true)),
+            _ => Err(format!("unsupported cbor simple value {}", info).into()),
+        };
+    }
+    let value = match info {
+        0..=23 => u64::from(info),
+        24..=27 => take(bytes, pos, 1 << (info - 24))?
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | u64::from(*b)),
+        _ => return Err("unsupported cbor indefinite length".into()),
+    };
+    let len = |value: u64| -> Result<usize> {
+        if value > (bytes.len() - *pos) as u64 {
+            Err("cbor length exceeds the data".into())
+        } else {
+            Ok(value as usize)
+        }
+    };
+    Ok(match major {
+        0 => Cbor::Unsigned(value),
+        2 => Cbor::Bytes(take(bytes, pos, len(value)?)?.to_vec()),
+        3 => Cbor::Text(std::str::from_utf8(take(bytes, pos, len(value)?)?)?.to_string()),
+        4 => {
+            let mut items = Vec::with_capacity(len(value)?);
+            for _ in 0..value {
+                items.push(decode_item(bytes, pos, depth + 1)?);
+            }
+            Cbor::Array(items)
+        }
+        5 => {
+            let mut entries = Vec::with_capacity(len(value)?);
+            for _ in 0..value {
+                let key = decode_item(bytes, pos, depth + 1)?;
+                let value = decode_item(bytes, pos, depth + 1)?;
+                entries.push((key, value));
+            }
+            Cbor::Map(entries)
+        }
+        6 => Cbor::Tag(value, Box::new(decode_item(bytes, pos, depth + 1)?)),
+        _ => return Err(format!("unsupported cbor major type {}", major).into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::cbor::Cbor;
+
+    #[test]
+    fn test_cbor() {
+        // vectors from RFC 7049 appendix A
+        let vectors = vec![
+            (Cbor::Unsigned(0), "00"),
+            (Cbor::Unsigned(23), "17"),
+            (Cbor::Unsigned(24), "1818"),
+            (Cbor::Unsigned(1000), "1903e8"),
+            (Cbor::Unsigned(1_000_000), "1a000f4240"),
+            (Cbor::Unsigned(1_000_000_000_000), "1b000000e8d4a51000"),
+            (Cbor::Bytes(vec![1, 2, 3, 4]), "4401020304"),
+            (Cbor::Text("IETF".to_string()), "6449455446"),
+            (Cbor::Bool(false), "f4"),
+            (Cbor::Bool(true), "f5"),
+            (
+                Cbor::Tag(1, Box::new(Cbor::Unsigned(1_363_896_240))),
+                "c11a514b67b0",
+            ),
+            (
+                Cbor::Array(vec![
+                    Cbor::Unsigned(1),
+                    Cbor::Array(vec![Cbor::Unsigned(2), Cbor::Unsigned(3)]),
+                ]),
+                "8201820203",
+            ),
+            (
+                Cbor::Map(vec![
+                    (Cbor::Unsigned(1), Cbor::Unsigned(2)),
+                    (Cbor::Unsigned(3), Cbor::Unsigned(4)),
+                ]),
+                "a201020304",
+            ),
+        ];
+        for (value, expected) in vectors {
+            assert_eq!(hex::encode(value.encode()), expected);
+            assert_eq!(
+                Cbor::decode(&hex::decode(expected).unwrap()).unwrap(),
+                value
+            );
+        }
+
+        let map = Cbor::decode(&hex::decode("a201020304").unwrap()).unwrap();
+        assert_eq!(map.get(3).unwrap().as_u64().unwrap(), 4);
+        assert!(map.get(2).is_none());
+
+        assert!(Cbor::decode(&hex::decode("4401020304ff").unwrap()).is_err());
+        assert!(Cbor::decode(&hex::decode("5bffffffffffffffff").unwrap()).is_err());
+        assert!(Cbor::decode(&hex::decode("9f").unwrap()).is_err());
+        assert!(Cbor::decode(&[0x81; 100]).is_err());
+    }
+}
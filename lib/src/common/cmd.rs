@@ -1,8 +1,11 @@
+use crate::common::descriptor::{verify_checksum, WalletDescriptor};
+use crate::common::qr::QrEncoding;
 use crate::*;
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
+use std::str::FromStr;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -65,6 +68,44 @@ impl Context {
         Ok(path)
     }
 
+    /// Save the wallet QR codes, without the daemon options which are not needed on other devices,
+    /// with UR encoding only the main descriptor is saved as `ur:crypto-output` and receivers
+    /// derive the change descriptor from it, so this must use `/1/*` where the main uses `/0/*`
+    pub fn save_wallet_qrs(
+        &self,
+        wallet: &WalletJson,
+        qr_version: i16,
+        encoding: QrEncoding,
+    ) -> Result<Vec<PathBuf>> {
+        let wallet_qr_path = self.path_for_wallet_qr()?;
+        match encoding {
+            QrEncoding::StructuredAppend => {
+                let mut wallet_for_qr = wallet.clone();
+                wallet_for_qr.daemon_opts = None;
+                let qr_bytes = serde_json::to_vec(&wallet_for_qr)?;
+                qr::save_qrs_with_document(
+                    qr_bytes,
+                    wallet_qr_path,
+                    qr_version,
+                    &format!("Wallet {}", wallet.name),
+                    &document::wallet_summary(wallet)?,
+                )
+            }
+            QrEncoding::Ur => {
+                let main = verify_checksum(&wallet.descriptor_main)?;
+                if main.replace("/0/*", "/1/*") != verify_checksum(&wallet.descriptor_change)? {
+                    return Err(
+                        "ur encoding requires change descriptor keys ending with /1/* where the main ones end with /0/*"
+                            .into(),
+                    );
+                }
+                let descriptor = WalletDescriptor::from_str(&wallet.descriptor_main)?;
+                let cbor = ur::output_to_cbor(&descriptor)?;
+                qr::save_ur_qrs(ur::CRYPTO_OUTPUT, &cbor, wallet_qr_path, qr_version)
+            }
+        }
+    }
+
     pub fn save_index(&self, indexes: &WalletIndexes) -> Result<()> {
         let path = self.filename_for_wallet("indexes.json")?;
         info!("Saving index data in {:?}", path);
@@ -107,3 +148,36 @@ fn read_indexes(path: &PathBuf) -> Result<WalletIndexes> {
     let indexes = fs::read(path)?;
     Ok(serde_json::from_slice(&indexes)?)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{self, ACCOUNT_1_XPUB, ACCOUNT_XPUB};
+    use crate::common::qr::QrEncoding;
+    use crate::descriptor::add_checksum;
+    use crate::Context;
+    use bitcoin::Network;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_save_wallet_ur_qrs() {
+        let temp_dir = TempDir::new("test_save_wallet_ur_qrs").unwrap();
+        let descriptor = format!(
+            "wsh(sortedmulti(2,{}/0/*,{}/0/*))",
+            ACCOUNT_XPUB, ACCOUNT_1_XPUB
+        );
+        let mut wallet = fixtures::wallet("ur", &descriptor);
+        let context = Context {
+            network: Network::Testnet,
+            wallet_name: wallet.name.clone(),
+            firma_datadir: format!("{}/", temp_dir.path().display()),
+        };
+        let qr_files = context
+            .save_wallet_qrs(&wallet, 14, QrEncoding::Ur)
+            .unwrap();
+        assert!(!qr_files.is_empty());
+
+        wallet.descriptor_change = add_checksum(&descriptor.replace("/0/*", "/2/*")).unwrap();
+        let result = context.save_wallet_qrs(&wallet, 14, QrEncoding::Ur);
+        assert!(result.unwrap_err().to_string().contains("/1/*"));
+    }
+}
@@ -0,0 +1,666 @@
+use crate::common::bsms::BSMS_VERSION;
+use crate::common::descriptor::{
+    add_checksum, normalize_keys, verify_checksum, DescriptorScript, WalletDescriptor,
+};
+use crate::common::key_origin::{slip132_script_type, KeyOrigin, XpubWithOrigin};
+use crate::*;
+use bitcoin::util::bip32::ChildNumber;
+use bitcoin::{Address, Network};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io;
+use std::str::FromStr;
+
+/// Multisig wallet formats of other coordinators and hardware signers
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WalletFormat {
+    /// Coldcard multisig setup text file
+    Coldcard,
+    /// Specter Desktop wallet json with `descriptor`
+    Specter,
+    /// Electrum wallet file, also exported by Sparrow
+    Sparrow,
+    /// BIP129 descriptor record, `BSMS 1.0` followed by descriptor template and first address
+    Bsms,
+}
+
+const BSMS_PATH_RESTRICTIONS: &str = "/0/*,/1/*";
+
+impl WalletFormat {
+    /// Guess the format from the content, json with a `descriptor` (Specter) or with a
+    /// `wallet_type` (Electrum), other json is refused
+    pub fn detect(content: &str) -> Result<WalletFormat> {
+        if content.trim_start().starts_with("BSMS") {
+            return Ok(WalletFormat::Bsms);
+        }
+        match serde_json::from_str::<Value>(content) {
+            Ok(value) if value.get("descriptor").is_some() => Ok(WalletFormat::Specter),
+            Ok(value) if value.get("wallet_type").is_some() => Ok(WalletFormat::Sparrow),
+            Ok(_) => Err("unknown wallet json, without a descriptor or a wallet_type".into()),
+            Err(_) => Ok(WalletFormat::Coldcard),
+        }
+    }
+}
+
+impl ToString for WalletFormat {
+    fn to_string(&self) -> String {
+        match self {
+            WalletFormat::Coldcard => "coldcard",
+            WalletFormat::Specter => "specter",
+            WalletFormat::Sparrow => "sparrow",
+            WalletFormat::Bsms => "bsms",
+        }
+        .to_string()
+    }
+}
+
+impl FromStr for WalletFormat {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "coldcard" => Ok(WalletFormat::Coldcard),
+            "specter" => Ok(WalletFormat::Specter),
+            "sparrow" | "electrum" => Ok(WalletFormat::Sparrow),
+            "bsms" => Ok(WalletFormat::Bsms),
+            _ => Err(io_err(&format!(
+                "({}) valid values are: coldcard, specter, sparrow, bsms",
+                s
+            ))),
+        }
+    }
+}
+
+/// Parse a wallet exported by another coordinator, `name` overrides the name in the file,
+/// `script_type` is required for Electrum files with bare xpubs, which don't imply one
+pub fn import(
+    content: &str,
+    format: WalletFormat,
+    name: Option<&str>,
+    script_type: Option<ScriptType>,
+) -> Result<WalletJson> {
+    let (file_name, descriptor_main, descriptor_change, created_at_height) = match format {
+        WalletFormat::Coldcard => parse_coldcard(content)?,
+        WalletFormat::Specter => parse_specter(content)?,
+        WalletFormat::Sparrow => parse_sparrow(content, script_type)?,
+        WalletFormat::Bsms => parse_bsms(content)?,
+    };
+    let name = name
+        .map(|s| s.to_string())
+        .or(file_name)
+        .ok_or_else(fn_err("wallet name missing in the file, specify one"))?;
+    validate_name(&name)?;
+    descriptor_wallet(
+        name,
+        &descriptor_main,
+        &descriptor_change,
+        created_at_height,
+    )
+}
+
+/// Wallet of a descriptor with keys ending in `/0/*`, the change descriptor uses `/1/*`
+pub fn from_descriptor(descriptor: &str, name: &str) -> Result<WalletJson> {
+    let descriptor = if descriptor.contains('#') {
+        verify_checksum(descriptor)?
+    } else {
+        descriptor
+    };
+    if !descriptor.contains("/0/*") {
+        return Err("descriptor keys must end with /0/*".into());
+    }
+    let main = normalize_keys(descriptor)?;
+    let change = normalize_keys(&descriptor.replace("/0/*", "/1/*"))?;
+    descriptor_wallet(name.to_string(), &main, &change, 0)
+}
+
+/// Wallet of the descriptors, the miniscript of policy wallets is kept as their policy since
+/// the one they were compiled from is not in the descriptor
+fn descriptor_wallet(
+    name: String,
+    descriptor_main: &str,
+    descriptor_change: &str,
+    created_at_height: u64,
+) -> Result<WalletJson> {
+    let descriptor_main = add_checksum(descriptor_main)?;
+    let descriptor_change = add_checksum(descriptor_change)?;
+    let parsed = WalletDescriptor::from_str(&descriptor_main)?;
+    let (required_sig, sorted, policy) = match &parsed.script {
+        DescriptorScript::Wpkh(_) => (Some(1), false, None),
+        DescriptorScript::Multi {
+            required, sorted, ..
+        } => (Some(*required), *sorted, None),
+        DescriptorScript::Miniscript { miniscript, .. } => (None, false, Some(miniscript.clone())),
+    };
+    let wallet = WalletJson {
+        name,
+        fingerprints: parsed.fingerprints(),
+        descriptor_main,
+        descriptor_change,
+        required_sig,
+        policy,
+        sorted,
+        script_type: parsed.script_type,
+        daemon_opts: None,
+        created_at_height,
+    };
+    wallet.validate()?;
+    Ok(wallet)
+}
+
+/// Render the wallet in the given format, to register it on hardware signers and watch-only apps
+pub fn export(wallet: &WalletJson, format: WalletFormat, network: Network) -> Result<String> {
+    wallet.validate()?;
+    match format {
+        WalletFormat::Coldcard => render_coldcard(wallet),
+        WalletFormat::Specter => render_specter(wallet),
+        WalletFormat::Sparrow => render_sparrow(wallet),
+        WalletFormat::Bsms => render_bsms(wallet, network),
+    }
+}
+
+type Descriptors = (Option<String>, String, String, u64);
+
+/// Main and change descriptors of a sortedmulti, as used by Coldcard and Electrum
+fn sorted_multi(
+    required: usize,
+    keys: &[XpubWithOrigin],
+    script_type: ScriptType,
+) -> (String, String) {
+    let descriptor = |i: u32| {
+        let keys: Vec<String> = keys.iter().map(|k| format!("{}/{}/*", k, i)).collect();
+        script_type.wrap(&format!("sortedmulti({},{})", required, keys.join(",")))
+    };
+    (descriptor(0), descriptor(1))
+}
+
+/// Parse "2 of 3", "2of3" or "2/3"
+fn parse_m_of_n(s: &str) -> Result<(usize, usize)> {
+    let s = s.replace(" ", "").replace("/", "of");
+    let parts: Vec<&str> = s.split("of").collect();
+    match parts.as_slice() {
+        [m, n] => match (m.parse(), n.parse()) {
+            (Ok(m), Ok(n)) if m > 0 && m <= n => Ok((m, n)),
+            _ => Err(format!("invalid policy {}", s).into()),
+        },
+        _ => Err(format!("invalid policy {}", s).into()),
+    }
+}
+
+fn key_with_origin(fingerprint: &str, derivation: &str, xpub: &str) -> Result<XpubWithOrigin> {
+    let path = derivation.trim().trim_start_matches('m');
+    let key = format!(
+        "[{}{}]{}",
+        fingerprint.trim().to_lowercase(),
+        path,
+        xpub.trim()
+    );
+    Ok(XpubWithOrigin::from_str(&key)?)
+}
+
+/// Keys of a sortedmulti wallet with their origin, Coldcard and Electrum support only these
+fn sorted_multi_keys(wallet: &WalletJson) -> Result<(usize, Vec<(KeyOrigin, XpubWithOrigin)>)> {
+    let parsed = WalletDescriptor::from_str(&wallet.descriptor_main)?;
+    let (required, keys) = match parsed.script {
+        DescriptorScript::Multi {
+            required,
+            keys,
+            sorted: true,
+        } => (required, keys),
+        _ => return Err("only sortedmulti wallets can be exported in this format".into()),
+    };
+    let mut result = vec![];
+    for key in keys {
+        if key.path != [ChildNumber::from_normal_idx(0)?] || !key.wildcard {
+            return Err(format!("key {} does not end with /0/*", key).into());
+        }
+        let origin = key
+            .xpub
+            .origin
+            .clone()
+            .ok_or_else(|| Error::Generic(format!("key {} without origin", key)))?;
+        result.push((origin, key.xpub));
+    }
+    Ok((required, result))
+}
+
+fn render_coldcard(wallet: &WalletJson) -> Result<String> {
+    if wallet.name.len() > 20 {
+        return Err("coldcard wallet name cannot be longer than 20 characters".into());
+    }
+    let (required, keys) = sorted_multi_keys(wallet)?;
+    let format = match wallet.script_type {
+        ScriptType::Wsh => "P2WSH",
+        ScriptType::ShWsh => "P2SH-P2WSH",
+        ScriptType::Sh => "P2SH",
+        ScriptType::Wpkh | ScriptType::ShWpkh => {
+            return Err("only sortedmulti wallets can be exported in this format".into())
+        }
+    };
+    let mut content = format!(
+        "# Coldcard Multisig setup file (exported by firma)\n#\nName: {}\nPolicy: {} of {}\nFormat: {}\n",
+        wallet.name,
+        required,
+        keys.len(),
+        format
+    );
+    for (origin, key) in keys.iter() {
+        content.push_str(&format!(
+            "\n# derivation: {}\n{}: {}\n",
+            origin.path,
+            origin.fingerprint.to_string().to_uppercase(),
+            key.xpub
+        ));
+    }
+    Ok(content)
+}
+
+fn render_specter(wallet: &WalletJson) -> Result<String> {
+    let parsed = WalletDescriptor::from_str(&wallet.descriptor_main)?;
+    let devices: Vec<Value> = parsed
+        .script
+        .keys()
+        .iter()
+        .map(|k| json!({ "type": "other", "label": k.xpub.fingerprint().to_string() }))
+        .collect();
+    let value = json!({
+        "label": wallet.name,
+        "blockheight": wallet.created_at_height,
+        "descriptor": wallet.descriptor_main,
+        "devices": devices,
+    });
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+fn render_sparrow(wallet: &WalletJson) -> Result<String> {
+    let (required, keys) = sorted_multi_keys(wallet)?;
+    let mut value = json!({
+        "wallet_type": format!("{}of{}", required, keys.len()),
+        "use_encryption": false,
+        "seed_version": 17,
+    });
+    for (i, (origin, key)) in keys.iter().enumerate() {
+        value[format!("x{}/", i + 1).as_str()] = json!({
+            "type": "bip32",
+            "label": "",
+            "derivation": origin.path.to_string(),
+            "root_fingerprint": origin.fingerprint.to_string(),
+            "xpub": key.to_slip132_for(wallet.script_type),
+        });
+    }
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+/// BIP129 descriptor record, the template has `/**` in place of the `/0/*` and `/1/*` paths
+fn render_bsms(wallet: &WalletJson, network: Network) -> Result<String> {
+    let descriptor = verify_checksum(&wallet.descriptor_main)?;
+    if descriptor.replace("/0/*", "/1/*") != verify_checksum(&wallet.descriptor_change)? {
+        return Err("bsms requires change descriptor with /1/* in place of /0/*".into());
+    }
+    let template = add_checksum(&descriptor.replace("/0/*", "/**"))?;
+    let first_address = wallet.derive_address(0, false, network)?;
+    Ok(format!(
+        "{}\n{}\n{}\n{}",
+        BSMS_VERSION, template, BSMS_PATH_RESTRICTIONS, first_address
+    ))
+}
+
+fn parse_coldcard(content: &str) -> Result<Descriptors> {
+    let mut name = None;
+    let mut policy = None;
+    // Coldcard default when the derivation is not specified
+    let mut derivation = "m/45'".to_string();
+    let mut key_derivation = None;
+    let mut script_type = ScriptType::Sh;
+    let mut keys = vec![];
+    for line in content.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        if line.starts_with('#') {
+            let comment = line.trim_start_matches('#').trim();
+            if comment.to_lowercase().starts_with("derivation:") {
+                key_derivation = Some(comment["derivation:".len()..].trim().to_string());
+            }
+            continue;
+        }
+        let mut parts = line.splitn(2, ':');
+        let label = parts.next().unwrap_or("").trim();
+        let value = parts
+            .next()
+            .ok_or_else(|| Error::Generic(format!("invalid line {}", line)))?
+            .trim();
+        match label.to_lowercase().as_str() {
+            "name" => name = Some(value.to_string()),
+            "policy" => policy = Some(parse_m_of_n(value)?),
+            "derivation" => derivation = value.to_string(),
+            "format" => {
+                script_type = match value.to_uppercase().as_str() {
+                    "P2WSH" => ScriptType::Wsh,
+                    "P2SH-P2WSH" | "P2WSH-P2SH" => ScriptType::ShWsh,
+                    "P2SH" => ScriptType::Sh,
+                    _ => return Err(format!("unsupported format {}", value).into()),
+                }
+            }
+            _ => {
+                let derivation = key_derivation.take().unwrap_or_else(|| derivation.clone());
+                keys.push(key_with_origin(label, &derivation, value)?);
+            }
+        }
+    }
+    let (required, total) = policy.ok_or_else(fn_err("missing policy in coldcard file"))?;
+    if total != keys.len() {
+        return Err(format!("policy requires {} keys, found {}", total, keys.len()).into());
+    }
+    let (main, change) = sorted_multi(required, &keys, script_type);
+    Ok((name, main, change, 0))
+}
+
+fn parse_specter(content: &str) -> Result<Descriptors> {
+    let value: Value = serde_json::from_str(content)?;
+    let descriptor = value
+        .get("descriptor")
+        .and_then(|d| d.as_str())
+        .ok_or_else(fn_err("missing descriptor in specter file"))?;
+    let descriptor = if descriptor.contains('#') {
+        verify_checksum(descriptor)?
+    } else {
+        descriptor
+    };
+    let (main, change) = if descriptor.contains("/<0;1>/*") {
+        (
+            descriptor.replace("/<0;1>/*", "/0/*"),
+            descriptor.replace("/<0;1>/*", "/1/*"),
+        )
+    } else if descriptor.contains("/0/*") {
+        (descriptor.to_string(), descriptor.replace("/0/*", "/1/*"))
+    } else {
+        return Err("specter descriptor keys must end with /0/*".into());
+    };
+    let name = value
+        .get("label")
+        .or_else(|| value.get("name"))
+        .and_then(|n| n.as_str())
+        .map(|n| n.to_string());
+    let height = value
+        .get("blockheight")
+        .and_then(|h| h.as_u64())
+        .unwrap_or(0);
+    Ok((
+        name,
+        normalize_keys(&main)?,
+        normalize_keys(&change)?,
+        height,
+    ))
+}
+
+fn parse_sparrow(content: &str, given_script_type: Option<ScriptType>) -> Result<Descriptors> {
+    let value: Value = serde_json::from_str(content)?;
+    let wallet_type = value
+        .get("wallet_type")
+        .and_then(|w| w.as_str())
+        .ok_or_else(fn_err("missing wallet_type in electrum file"))?;
+    let (required, total) = parse_m_of_n(wallet_type)?;
+    let mut keys = vec![];
+    let mut script_type = given_script_type;
+    for i in 1..=total {
+        let keystore = value
+            .get(&format!("x{}/", i))
+            .ok_or_else(|| Error::Generic(format!("missing keystore x{}/", i)))?;
+        let field = |name: &str| {
+            keystore
+                .get(name)
+                .and_then(|f| f.as_str())
+                .ok_or_else(|| Error::Generic(format!("missing {} in keystore x{}/", name, i)))
+        };
+        let xpub = field("xpub")?;
+        if let Some(key_script_type) = slip132_script_type(xpub)? {
+            if *script_type.get_or_insert(key_script_type) != key_script_type {
+                return Err("keystores with different script types than the given one".into());
+            }
+        }
+        keys.push(key_with_origin(
+            field("root_fingerprint")?,
+            field("derivation")?,
+            xpub,
+        )?);
+    }
+    let script_type = script_type.ok_or_else(fn_err(
+        "electrum file with bare xpubs, specify the script type with --script-type",
+    ))?;
+    if script_type.is_single_sig() {
+        return Err("electrum multisig wallets cannot have a single signature script type".into());
+    }
+    let (main, change) = sorted_multi(required, &keys, script_type);
+    Ok((None, main, change, 0))
+}
+
+fn parse_bsms(content: &str) -> Result<Descriptors> {
+    let lines: Vec<&str> = content
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .collect();
+    let (template, first_address) = match lines.as_slice() {
+        [version, template, restrictions, first_address] => {
+            if *version != BSMS_VERSION {
+                return Err(format!("unsupported bsms version {}", version).into());
+            }
+            if *restrictions != BSMS_PATH_RESTRICTIONS {
+                return Err(format!("unsupported bsms path restrictions {}", restrictions).into());
+            }
+            (*template, Address::from_str(first_address)?)
+        }
+        _ => return Err("bsms descriptor record must have 4 lines".into()),
+    };
+    let template = if template.contains('#') {
+        verify_checksum(template)?
+    } else {
+        template
+    };
+    if !template.contains("/**") {
+        return Err("bsms descriptor template keys must end with /**".into());
+    }
+    let main = normalize_keys(&template.replace("/**", "/0/*"))?;
+    let change = normalize_keys(&template.replace("/**", "/1/*"))?;
+
+    let derived = WalletDescriptor::from_str(&add_checksum(&main)?)?
+        .derive_address(0, first_address.network)?;
+    if derived.script_pubkey() != first_address.script_pubkey() {
+        return Err(format!(
+            "bsms first address {} does not match derived address {}",
+            first_address, derived
+        )
+        .into());
+    }
+    Ok((None, main, change, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::coordinator::{export, from_descriptor, import, WalletFormat};
+    use crate::common::descriptor::add_checksum;
+    use crate::common::fixtures::{ACCOUNT_1_XPUB, ACCOUNT_XPUB};
+    use crate::ScriptType;
+    use bitcoin::Network;
+
+    fn expected(script_type: ScriptType, i: u32) -> String {
+        let inner = format!(
+            "sortedmulti(2,{}/{}/*,{}/{}/*)",
+            ACCOUNT_XPUB, i, ACCOUNT_1_XPUB, i
+        );
+        add_checksum(&script_type.wrap(&inner)).unwrap()
+    }
+
+    #[test]
+    fn test_import_coldcard() {
+        let content = include_str!("../../test_data/import/coldcard.txt");
+        assert_eq!(
+            WalletFormat::detect(content).unwrap(),
+            WalletFormat::Coldcard
+        );
+        let wallet = import(content, WalletFormat::Coldcard, None, None).unwrap();
+        assert_eq!(wallet.name, "cc-2of2");
+        assert_eq!(wallet.required_sig, Some(2));
+        assert!(wallet.sorted);
+        assert_eq!(wallet.script_type, ScriptType::Wsh);
+        assert_eq!(wallet.descriptor_main, expected(ScriptType::Wsh, 0));
+        assert_eq!(wallet.descriptor_change, expected(ScriptType::Wsh, 1));
+
+        let wrong_policy = content.replace("Policy: 2 of 2", "Policy: 2 of 3");
+        let result = import(&wrong_policy, WalletFormat::Coldcard, None, None);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "policy requires 3 keys, found 2"
+        );
+        let traversal = content.replace("Name: cc-2of2", "Name: ../../../cc");
+        let result = import(&traversal, WalletFormat::Coldcard, None, None);
+        assert!(result.unwrap_err().to_string().starts_with("invalid name"));
+        let nested = content.replace("Format: P2WSH", "Format: P2SH-P2WSH");
+        let wallet = import(&nested, WalletFormat::Coldcard, Some("renamed"), None).unwrap();
+        assert_eq!(wallet.name, "renamed");
+        assert_eq!(wallet.descriptor_main, expected(ScriptType::ShWsh, 0));
+    }
+
+    #[test]
+    fn test_import_specter() {
+        let content = include_str!("../../test_data/import/specter.json");
+        assert_eq!(
+            WalletFormat::detect(content).unwrap(),
+            WalletFormat::Specter
+        );
+        let wallet = import(content, WalletFormat::Specter, None, None).unwrap();
+        assert_eq!(wallet.name, "specter-2of2");
+        assert_eq!(wallet.created_at_height, 1_834_000);
+        assert_eq!(wallet.descriptor_main, expected(ScriptType::Wsh, 0));
+        assert_eq!(wallet.descriptor_change, expected(ScriptType::Wsh, 1));
+
+        let traversal = content.replace("\"specter-2of2\"", "\"../specter\"");
+        let result = import(&traversal, WalletFormat::Specter, None, None);
+        assert!(result.unwrap_err().to_string().starts_with("invalid name"));
+
+        let tampered = content.replace("sortedmulti(2,", "sortedmulti(1,");
+        let result = import(&tampered, WalletFormat::Specter, None, None);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("descriptor checksum mismatch"));
+
+        let copy = from_descriptor(&wallet.descriptor_main, "specter-2of2").unwrap();
+        assert_eq!(copy.descriptor_change, wallet.descriptor_change);
+        assert_eq!(copy.fingerprints, wallet.fingerprints);
+        let change = from_descriptor(&wallet.descriptor_change, "change");
+        assert!(change.unwrap_err().to_string().contains("/0/*"));
+
+        let single = from_descriptor(&format!("wpkh({}/0/*)", ACCOUNT_XPUB), "single").unwrap();
+        assert_eq!(single.required_sig, Some(1));
+        assert_eq!(single.script_type, ScriptType::Wpkh);
+        assert_eq!(single.policy, None);
+        let miniscript = format!(
+            "or_d(pk({}/0/*),and_v(v:pk({}/0/*),older(144)))",
+            ACCOUNT_XPUB, ACCOUNT_1_XPUB
+        );
+        let policy = from_descriptor(&format!("wsh({})", miniscript), "policy").unwrap();
+        assert_eq!(policy.required_sig, None);
+        assert_eq!(policy.policy, Some(miniscript));
+        assert!(!policy.sorted);
+    }
+
+    #[test]
+    fn test_import_sparrow() {
+        let content = include_str!("../../test_data/import/sparrow.json");
+        assert_eq!(
+            WalletFormat::detect(content).unwrap(),
+            WalletFormat::Sparrow
+        );
+        let result = import(content, WalletFormat::Sparrow, None, None);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("wallet name missing"));
+        let wallet = import(content, WalletFormat::Sparrow, Some("sparrow-2of2"), None).unwrap();
+        assert_eq!(wallet.required_sig, Some(2));
+        assert_eq!(wallet.descriptor_main, expected(ScriptType::Wsh, 0));
+        assert_eq!(wallet.descriptor_change, expected(ScriptType::Wsh, 1));
+
+        let result = import(content, WalletFormat::Sparrow, None, Some(ScriptType::Sh));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("different script types"));
+        assert!(WalletFormat::detect("{\"label\": \"other\"}").is_err());
+    }
+
+    #[test]
+    fn test_export_roundtrip() {
+        let content = include_str!("../../test_data/import/coldcard.txt");
+        for (script_type, coldcard_format) in [
+            (ScriptType::Wsh, "P2WSH"),
+            (ScriptType::ShWsh, "P2SH-P2WSH"),
+            (ScriptType::Sh, "P2SH"),
+        ]
+        .iter()
+        {
+            let content = content.replace("Format: P2WSH", &format!("Format: {}", coldcard_format));
+            let mut wallet = import(&content, WalletFormat::Coldcard, None, None).unwrap();
+            wallet.created_at_height = 1_834_000;
+            for format in [
+                WalletFormat::Coldcard,
+                WalletFormat::Specter,
+                WalletFormat::Sparrow,
+                WalletFormat::Bsms,
+            ]
+            .iter()
+            {
+                let exported = export(&wallet, *format, Network::Testnet).unwrap();
+                assert_eq!(WalletFormat::detect(&exported).unwrap(), *format);
+                let imported =
+                    import(&exported, *format, Some("cc-2of2"), Some(*script_type)).unwrap();
+                assert_eq!(imported.descriptor_main, wallet.descriptor_main);
+                assert_eq!(imported.descriptor_change, wallet.descriptor_change);
+                assert_eq!(imported.script_type, *script_type);
+            }
+            // electrum exports legacy p2sh keys as bare xpubs
+            let exported = export(&wallet, WalletFormat::Sparrow, Network::Testnet).unwrap();
+            let result = import(&exported, WalletFormat::Sparrow, Some("cc-2of2"), None);
+            assert_eq!(result.is_err(), *script_type == ScriptType::Sh);
+        }
+
+        let wallet = import(content, WalletFormat::Coldcard, None, None).unwrap();
+        let exported = export(&wallet, WalletFormat::Specter, Network::Testnet).unwrap();
+        let imported = import(&exported, WalletFormat::Specter, None, None).unwrap();
+        assert_eq!(imported, wallet);
+
+        let exported = export(&wallet, WalletFormat::Sparrow, Network::Testnet).unwrap();
+        assert!(exported.contains("Vpub"));
+
+        let exported = export(&wallet, WalletFormat::Bsms, Network::Testnet).unwrap();
+        let lines: Vec<&str> = exported.lines().collect();
+        assert_eq!(lines[0], "BSMS 1.0");
+        let template = format!(
+            "wsh(sortedmulti(2,{}/**,{}/**))",
+            ACCOUNT_XPUB, ACCOUNT_1_XPUB
+        );
+        assert_eq!(lines[1], add_checksum(&template).unwrap());
+        assert_eq!(lines[2], "/0/*,/1/*");
+        let first = "tb1qne8muyajakvzzpxkrvlzsk4q3k3py5w7s8egfpgyl4n0xtns26hqjw3dj6";
+        assert_eq!(lines[3], first);
+        let wrong_address = exported.replace(first, "2N2tSo4x17YMemWDLijahWEDEwv7q8aER7A");
+        let result = import(&wrong_address, WalletFormat::Bsms, Some("bsms"), None);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("does not match derived address"));
+
+        let unsorted_descriptor = |i: u32| {
+            let inner = format!(
+                "multi(2,{}/{}/*,{}/{}/*)",
+                ACCOUNT_XPUB, i, ACCOUNT_1_XPUB, i
+            );
+            add_checksum(&ScriptType::Wsh.wrap(&inner)).unwrap()
+        };
+        let mut unsorted = wallet.clone();
+        unsorted.descriptor_main = unsorted_descriptor(0);
+        unsorted.descriptor_change = unsorted_descriptor(1);
+        unsorted.sorted = false;
+        assert!(export(&unsorted, WalletFormat::Coldcard, Network::Testnet).is_err());
+        assert!(export(&unsorted, WalletFormat::Specter, Network::Testnet).is_ok());
+    }
+}
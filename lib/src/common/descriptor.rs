@@ -0,0 +1,724 @@
+use crate::common::key_origin::XpubWithOrigin;
+use crate::*;
+use bitcoin::secp256k1::{Secp256k1, Verification};
+use bitcoin::util::bip32::{ChildNumber, Fingerprint};
+use bitcoin::util::key;
+use bitcoin::{Address, Network};
+use miniscript::policy::Concrete;
+use miniscript::{Miniscript, Segwitv0};
+use std::collections::HashSet;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Characters allowed in descriptors, ordered as in BIP380 to compute the checksum
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const GENERATOR: [u64; 5] = [
+    0xf5_dee5_1989,
+    0xa9_fdca_3312,
+    0x1b_ab10_e32d,
+    0x37_06b1_677a,
+    0x64_4d62_6ffd,
+];
+
+/// Node of a descriptor expression, eg. `sortedmulti(2,A,B)`, leafs (keys, numbers) have no args
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    pub name: String,
+    pub args: Vec<Expression>,
+}
+
+/// Key in a descriptor, eg. `[d34db33f/48'/1'/0'/2']tpub.../0/*`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DescriptorKey {
+    pub xpub: XpubWithOrigin,
+    /// derivation steps after the xpub, excluding the final wildcard
+    pub path: Vec<ChildNumber>,
+    pub wildcard: bool,
+}
+
+/// Script inside the descriptor script type wrapper
+#[derive(Debug, Clone, PartialEq)]
+pub enum DescriptorScript {
+    Wpkh(DescriptorKey),
+    Multi {
+        required: usize,
+        keys: Vec<DescriptorKey>,
+        sorted: bool,
+    },
+    Miniscript {
+        miniscript: String,
+        keys: Vec<DescriptorKey>,
+    },
+}
+
+/// Wallet descriptor as created by `create-wallet`, eg. `sh(wsh(sortedmulti(2,A/0/*,B/0/*)))`
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletDescriptor {
+    pub script_type: ScriptType,
+    pub script: DescriptorScript,
+}
+
+fn polymod(symbols: &[u64]) -> u64 {
+    let mut chk = 1u64;
+    for value in symbols {
+        let top = chk >> 35;
+        chk = (chk & 0x7_ffff_ffff) << 5 ^ value;
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+/// Compute the BIP380 checksum of a descriptor without checksum
+pub fn checksum(descriptor: &str) -> Result<String> {
+    let mut symbols = vec![];
+    let mut groups = vec![];
+    for c in descriptor.chars() {
+        let value = INPUT_CHARSET
+            .find(c)
+            .ok_or_else(|| Error::Generic(format!("invalid character {:?} in descriptor", c)))?
+            as u64;
+        symbols.push(value & 31);
+        groups.push(value >> 5);
+        if groups.len() == 3 {
+            symbols.push(groups[0] * 9 + groups[1] * 3 + groups[2]);
+            groups.clear();
+        }
+    }
+    match groups.len() {
+        1 => symbols.push(groups[0]),
+        2 => symbols.push(groups[0] * 3 + groups[1]),
+        _ => (),
+    }
+    symbols.extend_from_slice(&[0; 8]);
+    let chk = polymod(&symbols) ^ 1;
+    Ok((0..8)
+        .map(|i| CHECKSUM_CHARSET[((chk >> (5 * (7 - i))) & 31) as usize] as char)
+        .collect())
+}
+
+/// Append the `#checksum` to a descriptor without checksum
+pub fn add_checksum(descriptor: &str) -> Result<String> {
+    Ok(format!("{}#{}", descriptor, checksum(descriptor)?))
+}
+
+/// Rewrite the keys of a descriptor without checksum in the form used by firma,
+/// eg. hardened steps as `'` instead of `h` and SLIP-132 keys as xpub
+pub fn normalize_keys(descriptor: &str) -> Result<String> {
+    let expression = Expression::from_str(descriptor)?.map_keys(&|k| Ok(k.to_string()))?;
+    Ok(expression.to_string())
+}
+
+/// Verify the mandatory checksum of the descriptor, returning the descriptor without it
+pub fn verify_checksum(descriptor: &str) -> Result<&str> {
+    let mut parts = descriptor.splitn(2, '#');
+    let without = parts.next().unwrap_or("");
+    let found = parts
+        .next()
+        .ok_or_else(|| Error::Generic(format!("descriptor {} without checksum", descriptor)))?;
+    let expected = checksum(without)?;
+    if found != expected {
+        return Err(format!(
+            "descriptor checksum mismatch, found {} expected {}",
+            found, expected
+        )
+        .into());
+    }
+    Ok(without)
+}
+
+impl Expression {
+    fn parse_inner(s: &str) -> Result<(Expression, &str)> {
+        let end = s
+            .find(|c| c == '(' || c == ',' || c == ')')
+            .unwrap_or(s.len());
+        let name = s[..end].to_string();
+        if name.is_empty() {
+            return Err("empty expression in descriptor".into());
+        }
+        let mut rest = &s[end..];
+        let mut args = vec![];
+        if rest.starts_with('(') {
+            loop {
+                let (arg, after) = Expression::parse_inner(&rest[1..])?;
+                args.push(arg);
+                rest = after;
+                match rest.chars().next() {
+                    Some(',') => continue,
+                    Some(')') => {
+                        rest = &rest[1..];
+                        break;
+                    }
+                    _ => return Err("missing closing parenthesis in descriptor".into()),
+                }
+            }
+        }
+        Ok((Expression { name, args }, rest))
+    }
+
+    fn leaf(&self) -> Option<&str> {
+        if self.args.is_empty() {
+            Some(&self.name)
+        } else {
+            None
+        }
+    }
+
+    fn single_arg(&self) -> Result<&Expression> {
+        match self.args.as_slice() {
+            [arg] => Ok(arg),
+            _ => Err(format!("{}() requires exactly one argument", self.name).into()),
+        }
+    }
+
+    /// Replace the keys in the expression with the result of `f`
+    fn map_keys<F: Fn(&DescriptorKey) -> Result<String>>(&self, f: &F) -> Result<Self> {
+        if let Some(leaf) = self.leaf() {
+            if let Ok(key) = DescriptorKey::from_str(leaf) {
+                return Ok(Expression {
+                    name: f(&key)?,
+                    args: vec![],
+                });
+            }
+        }
+        let args = self
+            .args
+            .iter()
+            .map(|arg| arg.map_keys(f))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Expression {
+            name: self.name.clone(),
+            args,
+        })
+    }
+
+    fn collect_keys(&self, keys: &mut Vec<DescriptorKey>) {
+        match self.leaf() {
+            Some(leaf) => {
+                if let Ok(key) = DescriptorKey::from_str(leaf) {
+                    keys.push(key);
+                }
+            }
+            None => self.args.iter().for_each(|arg| arg.collect_keys(keys)),
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (expression, rest) = Expression::parse_inner(s)?;
+        if !rest.is_empty() {
+            return Err(format!("unexpected trailing characters {} in descriptor", rest).into());
+        }
+        Ok(expression)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.args.is_empty() {
+            let args: Vec<String> = self.args.iter().map(|a| a.to_string()).collect();
+            write!(f, "({})", args.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DescriptorKey {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let start = s.find(']').map(|i| i + 1).unwrap_or(0);
+        let mut steps = s[start..].split('/');
+        let xpub = steps.next().unwrap_or("");
+        let xpub = XpubWithOrigin::from_str(&s[..start + xpub.len()])?;
+        let mut path = vec![];
+        let mut wildcard = false;
+        for step in steps {
+            if wildcard {
+                return Err(io_err("wildcard must be the last derivation step"));
+            }
+            if step == "*" {
+                wildcard = true;
+                continue;
+            }
+            let hardened = step.ends_with('\'') || step.ends_with('h');
+            let index = if hardened {
+                &step[..step.len() - 1]
+            } else {
+                step
+            };
+            let index = index
+                .parse::<u32>()
+                .map_err(|_| io_err("invalid derivation step in descriptor key"))?;
+            let child = if hardened {
+                ChildNumber::from_hardened_idx(index)
+            } else {
+                ChildNumber::from_normal_idx(index)
+            };
+            path.push(child.map_err(|e| io_err(&e.to_string()))?);
+        }
+        Ok(DescriptorKey {
+            xpub,
+            path,
+            wildcard,
+        })
+    }
+}
+
+impl DescriptorKey {
+    /// Public key at `index` in place of the final wildcard
+    pub fn derive<C: Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        index: u32,
+    ) -> Result<key::PublicKey> {
+        let mut path = self.path.clone();
+        if self.wildcard {
+            path.push(ChildNumber::from_normal_idx(index)?);
+        }
+        Ok(self.xpub.xpub.derive_pub(secp, &path)?.public_key)
+    }
+}
+
+impl fmt::Display for DescriptorKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.xpub)?;
+        for child in self.path.iter() {
+            write!(f, "/{}", child)?;
+        }
+        if self.wildcard {
+            write!(f, "/*")?;
+        }
+        Ok(())
+    }
+}
+
+impl DescriptorScript {
+    fn parse(expression: &Expression, script_type: ScriptType) -> Result<Self> {
+        match expression.name.as_str() {
+            "wpkh" if script_type.is_single_sig() => {
+                let key = expression.single_arg()?.leaf().unwrap_or("");
+                Ok(DescriptorScript::Wpkh(DescriptorKey::from_str(key)?))
+            }
+            "wpkh" => Err(format!("unsupported descriptor script {}", expression).into()),
+            "multi" | "sortedmulti" => {
+                let mut args = expression.args.iter().map(|a| a.leaf().unwrap_or(""));
+                let required = args
+                    .next()
+                    .unwrap_or("")
+                    .parse::<usize>()
+                    .map_err(|_| Error::Generic("invalid multisig threshold".into()))?;
+                let keys = args
+                    .map(DescriptorKey::from_str)
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                if required == 0 || required > keys.len() {
+                    return Err(
+                        "multisig threshold must be between 1 and the number of keys".into(),
+                    );
+                }
+                Ok(DescriptorScript::Multi {
+                    required,
+                    keys,
+                    sorted: expression.name == "sortedmulti",
+                })
+            }
+            _ if script_type != ScriptType::Sh => {
+                let mut keys = vec![];
+                expression.collect_keys(&mut keys);
+                if keys.is_empty() {
+                    return Err(format!("no keys in descriptor script {}", expression).into());
+                }
+                Ok(DescriptorScript::Miniscript {
+                    miniscript: expression.to_string(),
+                    keys,
+                })
+            }
+            _ => Err(format!("unsupported descriptor script {}", expression).into()),
+        }
+    }
+
+    pub fn keys(&self) -> Vec<&DescriptorKey> {
+        match self {
+            DescriptorScript::Wpkh(key) => vec![key],
+            DescriptorScript::Multi { keys, .. } | DescriptorScript::Miniscript { keys, .. } => {
+                keys.iter().collect()
+            }
+        }
+    }
+}
+
+impl FromStr for WalletDescriptor {
+    type Err = Error;
+
+    /// Parse a descriptor, the checksum is mandatory and verified
+    fn from_str(s: &str) -> Result<Self> {
+        let expression = Expression::from_str(verify_checksum(s)?)?;
+        let (script_type, inner) = match expression.name.as_str() {
+            "wsh" => (ScriptType::Wsh, expression.single_arg()?),
+            "wpkh" => (ScriptType::Wpkh, &expression),
+            "sh" => {
+                let inner = expression.single_arg()?;
+                match inner.name.as_str() {
+                    "wsh" => (ScriptType::ShWsh, inner.single_arg()?),
+                    "wpkh" => (ScriptType::ShWpkh, inner),
+                    _ => (ScriptType::Sh, inner),
+                }
+            }
+            _ => return Err(format!("unsupported descriptor {}", expression.name).into()),
+        };
+        Ok(WalletDescriptor {
+            script_type,
+            script: DescriptorScript::parse(inner, script_type)?,
+        })
+    }
+}
+
+impl WalletDescriptor {
+    /// Derive the address at `index` without asking the bitcoin node
+    pub fn derive_address(&self, index: u32, network: Network) -> Result<Address> {
+        let secp = Secp256k1::verification_only();
+        let script = match &self.script {
+            DescriptorScript::Wpkh(key) => {
+                let key = key.derive(&secp, index)?;
+                return Ok(match self.script_type {
+                    ScriptType::ShWpkh => Address::p2shwpkh(&key, network),
+                    _ => Address::p2wpkh(&key, network),
+                });
+            }
+            DescriptorScript::Multi {
+                required,
+                keys,
+                sorted,
+            } => {
+                let keys = keys
+                    .iter()
+                    .map(|k| k.derive(&secp, index))
+                    .collect::<Result<Vec<_>>>()?;
+                multisig_script(*required, &keys, *sorted)
+            }
+            DescriptorScript::Miniscript { miniscript, .. } => {
+                let derived = Expression::from_str(miniscript)?
+                    .map_keys(&|k| Ok(k.derive(&secp, index)?.to_string()))?;
+                Miniscript::<key::PublicKey, Segwitv0>::from_str(&derived.to_string())
+                    .map_err(|e| Error::Generic(format!("invalid miniscript: {}", e)))?
+                    .encode()
+            }
+        };
+        Ok(match self.script_type {
+            ScriptType::ShWsh => Address::p2shwsh(&script, network),
+            ScriptType::Sh => Address::p2sh(&script, network),
+            _ => Address::p2wsh(&script, network),
+        })
+    }
+
+    /// True if the keys are of the given network, testnet keys are used also in regtest
+    pub fn is_for_network(&self, network: Network) -> bool {
+        self.script.keys().iter().all(|k| {
+            let key_network = k.xpub.xpub.network;
+            key_network == network
+                || (network == Network::Regtest && key_network == Network::Testnet)
+        })
+    }
+
+    /// Fingerprints of the master keys in the descriptor
+    pub fn fingerprints(&self) -> HashSet<Fingerprint> {
+        self.script
+            .keys()
+            .iter()
+            .map(|k| k.xpub.fingerprint())
+            .collect()
+    }
+}
+
+/// Descriptor without checksum and with `/*` in place of the `/<branch>/*` ending every key
+fn strip_branch(descriptor: &str, branch: u32) -> Result<String> {
+    let branch = ChildNumber::from_normal_idx(branch)?;
+    let expression = Expression::from_str(verify_checksum(descriptor)?)?;
+    let stripped = expression.map_keys(&|key| {
+        if !key.wildcard || key.path.last() != Some(&branch) {
+            return Err(format!("key {} does not end with /{}/*", key, branch).into());
+        }
+        let mut key = key.clone();
+        key.path.pop();
+        Ok(key.to_string())
+    })?;
+    Ok(stripped.to_string())
+}
+
+impl WalletJson {
+    /// Check the descriptors checksum, that the change descriptor is the main one with keys
+    /// ending in `/1/*` in place of `/0/*` and that the other fields agree with the descriptors
+    pub fn validate(&self) -> Result<()> {
+        let main = WalletDescriptor::from_str(&self.descriptor_main)?;
+        WalletDescriptor::from_str(&self.descriptor_change)?;
+        if strip_branch(&self.descriptor_main, 0)? != strip_branch(&self.descriptor_change, 1)? {
+            return Err("main and change descriptors do not match".into());
+        }
+        if main.script_type != self.script_type {
+            return Err(format!(
+                "script type {} does not match descriptor",
+                self.script_type.to_string()
+            )
+            .into());
+        }
+        if main.fingerprints() != self.fingerprints {
+            return Err("fingerprints do not match the descriptor keys".into());
+        }
+        let (required, sorted) = match main.script {
+            DescriptorScript::Wpkh(_) => (Some(1), self.sorted),
+            DescriptorScript::Multi {
+                required, sorted, ..
+            } => (Some(required), sorted),
+            DescriptorScript::Miniscript { .. } => (None, self.sorted),
+        };
+        if required != self.required_sig || sorted != self.sorted {
+            return Err("required signatures or key ordering do not match descriptor".into());
+        }
+        if let DescriptorScript::Miniscript { miniscript, .. } = &main.script {
+            // miniscript is parsed only with concrete keys, those derived at the first index
+            main.derive_address(0, Network::Bitcoin)?;
+            if let Some(policy) = &self.policy {
+                // policies compiled by create-wallet have fingerprints in place of keys
+                if policy != miniscript && Concrete::<String>::from_str(policy).is_err() {
+                    return Err(format!("invalid policy {}", policy).into());
+                }
+            }
+        } else if self.policy.is_some() {
+            return Err("policy of a wallet without miniscript".into());
+        }
+        Ok(())
+    }
+
+    /// Main and change descriptors of the wallet parsed, to derive many addresses parsing them once
+    pub fn parse_descriptors(&self) -> Result<(WalletDescriptor, WalletDescriptor)> {
+        Ok((
+            WalletDescriptor::from_str(&self.descriptor_main)?,
+            WalletDescriptor::from_str(&self.descriptor_change)?,
+        ))
+    }
+
+    /// Derive the main (or change) address at `index` from the wallet descriptors
+    pub fn derive_address(&self, index: u32, change: bool, network: Network) -> Result<Address> {
+        let descriptor = if change {
+            &self.descriptor_change
+        } else {
+            &self.descriptor_main
+        };
+        WalletDescriptor::from_str(descriptor)?.derive_address(index, network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::descriptor::*;
+    use crate::common::fixtures::{self, ACCOUNT_1_XPUB, ACCOUNT_XPUB};
+    use crate::ScriptType;
+    use bitcoin::util::bip32::{ChildNumber, Fingerprint};
+    use bitcoin::Network;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_checksum() {
+        // vectors from BIP380 and bitcoin core doc/descriptors.md
+        assert_eq!(checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        assert_eq!(
+            add_checksum("addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)").unwrap(),
+            "addr(mkmZxiEcEd8ZqjQWVZuC6so5dFMKEFpN2j)#02wpgw69"
+        );
+        let core = "wsh(multi(1,xpub661MyMwAqRbcFW31YEwpkMuc5THy2PSt5bDMsktWQcFF8syAmRUapSCGu8ED9W6oDMSgv6Zz8idoc4a6mr8BDzTJY47LJhkJ8UB7WEGuduB/1/0/*,xpub69H7F5d8KSRgmmdJg2KhpAK8SR3DjMwAdkxj3ZuxV27CprR9LgpeyGmXUbC6wb7ERfvrnKZjXoUmmDznezpbZb7ap6r1D3tgFxHmwMkQTPH/0/0/*))#t2zpj2eu";
+        let without = verify_checksum(core).unwrap();
+        assert_eq!(add_checksum(without).unwrap(), core);
+
+        let tampered = core.replace("multi(1", "multi(2");
+        assert_eq!(
+            verify_checksum(&tampered).unwrap_err().to_string(),
+            "descriptor checksum mismatch, found t2zpj2eu expected pk8r7xut"
+        );
+        assert!(verify_checksum(without)
+            .unwrap_err()
+            .to_string()
+            .contains("without checksum"));
+        assert!(checksum("wsh(é)").is_err());
+    }
+
+    #[test]
+    fn test_wallet_descriptor() {
+        let descriptor = format!("wsh(sortedmulti(1,{}/0/*))#70xcv4fq", ACCOUNT_XPUB);
+        let parsed = WalletDescriptor::from_str(&descriptor).unwrap();
+        assert_eq!(parsed.script_type, ScriptType::Wsh);
+        let key = DescriptorKey::from_str(&format!("{}/0/*", ACCOUNT_XPUB)).unwrap();
+        assert_eq!(key.path, vec![ChildNumber::from_normal_idx(0).unwrap()]);
+        assert!(key.wildcard);
+        assert_eq!(key.to_string(), format!("{}/0/*", ACCOUNT_XPUB));
+        assert_eq!(
+            parsed.script,
+            DescriptorScript::Multi {
+                required: 1,
+                keys: vec![key.clone()],
+                sorted: true,
+            }
+        );
+        let fingerprint = Fingerprint::from_str("3442193e").unwrap();
+        assert!(parsed.fingerprints().contains(&fingerprint));
+
+        let descriptor = format!("sh(wpkh({}/1/*))#xr005t0f", ACCOUNT_XPUB);
+        let parsed = WalletDescriptor::from_str(&descriptor).unwrap();
+        assert_eq!(parsed.script_type, ScriptType::ShWpkh);
+        if let DescriptorScript::Wpkh(wpkh) = parsed.script {
+            assert_eq!(wpkh.xpub.fingerprint(), fingerprint);
+        } else {
+            panic!("expected wpkh");
+        }
+
+        let miniscript = format!("wsh(and_v(v:pk({}/0/*),older(144)))", ACCOUNT_XPUB);
+        let parsed = WalletDescriptor::from_str(&add_checksum(&miniscript).unwrap()).unwrap();
+        match parsed.script {
+            DescriptorScript::Miniscript { miniscript, keys } => {
+                assert_eq!(miniscript, format!("and_v(v:pk({}),older(144))", key));
+                assert_eq!(keys, vec![key]);
+            }
+            _ => panic!("expected miniscript"),
+        }
+
+        let unbalanced = add_checksum(&format!("wsh(multi(1,{}/0/*)", ACCOUNT_XPUB)).unwrap();
+        assert!(WalletDescriptor::from_str(&unbalanced).is_err());
+        let threshold = add_checksum(&format!("wsh(multi(2,{}/0/*))", ACCOUNT_XPUB)).unwrap();
+        assert!(WalletDescriptor::from_str(&threshold).is_err());
+        let nested = add_checksum(&format!("wsh(wpkh({}/0/*))", ACCOUNT_XPUB)).unwrap();
+        assert!(WalletDescriptor::from_str(&nested).is_err());
+        let legacy = add_checksum(&format!("sh(and_v(v:pk({}/0/*),older(1)))", ACCOUNT_XPUB));
+        assert!(WalletDescriptor::from_str(&legacy.unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_validate_wallet() {
+        let descriptor = format!("sh(wsh(sortedmulti(1,{}/0/*)))", ACCOUNT_XPUB);
+        let mut wallet = fixtures::wallet("validate", &descriptor);
+        assert_eq!(wallet.script_type, ScriptType::ShWsh);
+        assert_eq!(wallet.required_sig, Some(1));
+        assert!(wallet.sorted);
+        wallet.validate().unwrap();
+
+        wallet.sorted = false;
+        assert!(wallet.validate().is_err());
+        wallet.sorted = true;
+        wallet.script_type = ScriptType::Wsh;
+        assert_eq!(
+            wallet.validate().unwrap_err().to_string(),
+            "script type wsh does not match descriptor"
+        );
+        wallet.script_type = ScriptType::ShWsh;
+        let descriptor_change = wallet.descriptor_change.clone();
+        wallet.descriptor_change = descriptor_change.replace("/1/*", "/2/*");
+        assert!(wallet
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .starts_with("descriptor checksum mismatch"));
+
+        // change keys must end in /1/* and the change descriptor must be the main one
+        wallet.descriptor_change = add_checksum(&descriptor.replace("/0/*", "/2/*")).unwrap();
+        assert!(wallet
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .ends_with("/2/* does not end with /1/*"));
+        wallet.descriptor_change = wallet.descriptor_main.clone();
+        assert!(wallet.validate().is_err());
+        let other = format!("wsh(sortedmulti(1,{}/1/*))", ACCOUNT_XPUB);
+        wallet.descriptor_change = add_checksum(&other).unwrap();
+        assert_eq!(
+            wallet.validate().unwrap_err().to_string(),
+            "main and change descriptors do not match"
+        );
+        let other = descriptor.replace(ACCOUNT_XPUB, ACCOUNT_1_XPUB);
+        wallet.descriptor_change = add_checksum(&other.replace("/0/*", "/1/*")).unwrap();
+        assert_eq!(
+            wallet.validate().unwrap_err().to_string(),
+            "main and change descriptors do not match"
+        );
+        wallet.descriptor_change = descriptor_change;
+        wallet.validate().unwrap();
+        wallet.policy = Some("pk(3442193e)".to_string());
+        assert_eq!(
+            wallet.validate().unwrap_err().to_string(),
+            "policy of a wallet without miniscript"
+        );
+
+        // the policy is the miniscript of the descriptor or a policy with fingerprints as keys
+        let miniscript = format!("wsh(and_v(v:pk({}/0/*),older(144)))", ACCOUNT_XPUB);
+        let mut wallet = fixtures::wallet("miniscript", &miniscript);
+        wallet.validate().unwrap();
+        wallet.policy = Some("and(pk(3442193e),older(144))".to_string());
+        wallet.validate().unwrap();
+        wallet.policy = Some("and(pk(3442193e),older(144)".to_string());
+        assert_eq!(
+            wallet.validate().unwrap_err().to_string(),
+            "invalid policy and(pk(3442193e),older(144)"
+        );
+        wallet.policy = None;
+        let invalid = format!("wsh(and_v(pk({}/0/*),older(144)))", ACCOUNT_XPUB);
+        wallet.descriptor_main = add_checksum(&invalid).unwrap();
+        wallet.descriptor_change = add_checksum(&invalid.replace("/0/*", "/1/*")).unwrap();
+        assert!(wallet
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid miniscript"));
+    }
+
+    #[test]
+    fn test_derive_address() {
+        let (a, b) = (ACCOUNT_XPUB, ACCOUNT_1_XPUB);
+        let vectors = vec![
+            (
+                format!("wpkh({}/0/*)", a),
+                0,
+                "tb1q7fa4zp8vxqqry9df9y630zzj0rrkycmj4d3e98",
+            ),
+            (
+                format!("sh(wpkh({}/1/*))", a),
+                5,
+                "2NDrXXsZn5KMfphX49amwV7gCTdNhKpqBo8",
+            ),
+            (
+                format!("wsh(sortedmulti(1,{}/0/*,{}/0/*))", b, a),
+                3,
+                "tb1q02n47zer7q6elg09j9acauc32juz94v7nash0ysty8hhzwa95kpsk0v3cm",
+            ),
+            (
+                format!("sh(wsh(multi(2,{}/1/*,{}/1/*)))", b, a),
+                0,
+                "2Mvhzs8pdYYA5ZwR6JRvikPMaibGhMHU1be",
+            ),
+            (
+                format!("sh(sortedmulti(2,{}/0/*,{}/0/*))", a, b),
+                7,
+                "2N9rFRvtiLgVG5SfSDZNLU1PAzzqJvamST8",
+            ),
+            (
+                format!("wsh(and_v(v:pk({}/0/*),older(144)))", a),
+                2,
+                "tb1qa8gy5ms8x8vrg6cttegzf0rkdqr4xck26ulknjhfuu75j46ju5jsgqzwv9",
+            ),
+        ];
+        for (descriptor, index, expected) in vectors {
+            let parsed = WalletDescriptor::from_str(&add_checksum(&descriptor).unwrap()).unwrap();
+            let address = parsed.derive_address(index, Network::Testnet).unwrap();
+            assert_eq!(address.to_string(), expected, "{}", descriptor);
+        }
+
+        let hardened = add_checksum(&format!("wpkh({}/0'/*)", a)).unwrap();
+        let parsed = WalletDescriptor::from_str(&hardened).unwrap();
+        assert!(parsed.derive_address(0, Network::Testnet).is_err());
+    }
+}
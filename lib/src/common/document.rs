@@ -0,0 +1,333 @@
+use crate::common::descriptor::WalletDescriptor;
+use crate::common::qr::SplittedQr;
+use crate::*;
+use bitcoin::hashes::{sha256, Hash};
+use log::info;
+use qrcode::types::Color;
+use qrcode::QrCode;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A4 page size in points
+const PAGE_WIDTH: usize = 595;
+const PAGE_HEIGHT: usize = 842;
+const MARGIN: usize = 50;
+
+/// Checksum of the data in the QR codes, the first 8 bytes of its sha256, the same on every page
+/// so that pages of different documents are not mixed up
+pub fn checksum(bytes: &[u8]) -> String {
+    hex::encode(&sha256::Hash::hash(bytes)[..8])
+}
+
+/// Summary lines of a PSBT: txid, fee, number of inputs and outputs and the fingerprints of the
+/// keys signing the inputs
+pub fn psbt_summary(psbt_json: &PsbtJson) -> Result<Vec<String>> {
+    let (_, psbt) = psbt_from_base64(&psbt_json.psbt)?;
+    let tx = &psbt.global.unsigned_tx;
+    let fingerprints: BTreeSet<String> = psbt
+        .inputs
+        .iter()
+        .flat_map(|input| input.hd_keypaths.values())
+        .map(|(fingerprint, _)| fingerprint.to_string())
+        .collect();
+    Ok(vec![
+        format!("txid: {}", tx.txid()),
+        format!("fee: {} BTC", psbt_json.fee),
+        format!("inputs: {} outputs: {}", tx.input.len(), tx.output.len()),
+        format!("fingerprints: {}", join(&fingerprints)),
+    ])
+}
+
+/// Summary lines of a wallet: required signatures, fingerprints of the keys and checksum of the
+/// main descriptor
+pub fn wallet_summary(wallet: &WalletJson) -> Result<Vec<String>> {
+    let descriptor = WalletDescriptor::from_str(&wallet.descriptor_main)?;
+    let fingerprints: BTreeSet<String> =
+        wallet.fingerprints.iter().map(|f| f.to_string()).collect();
+    let descriptor_checksum = wallet.descriptor_main.split('#').nth(1).unwrap_or("none");
+    let required = match (wallet.required_sig, &wallet.policy) {
+        (Some(required_sig), _) => format!(
+            "required signatures: {} of {}",
+            required_sig,
+            descriptor.script.keys().len()
+        ),
+        (None, Some(policy)) => format!("policy: {}", policy),
+        (None, None) => "required signatures: unknown".to_string(),
+    };
+    Ok(vec![
+        required,
+        format!("fingerprints: {}", join(&fingerprints)),
+        format!("descriptor checksum: {}", descriptor_checksum),
+    ])
+}
+
+fn join(items: &BTreeSet<String>) -> String {
+    items.iter().cloned().collect::<Vec<_>>().join(" ")
+}
+
+/// Chars of a summary line fitting in the page width
+pub const LINE_CHARS: usize = 80;
+
+/// Page of a printable document, a title, text lines, a QR code below them and a footer
+pub struct Page {
+    pub title: String,
+    pub lines: Vec<String>,
+    pub qr: QrCode,
+    pub footer: String,
+}
+
+/// Split the text in lines of at most `LINE_CHARS` chars, breaking at spaces when possible
+pub fn wrap(text: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut current = String::new();
+    for word in text.split(' ') {
+        if !current.is_empty() && current.len() + 1 + word.len() > LINE_CHARS {
+            lines.push(current);
+            current = String::new();
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+        while current.len() > LINE_CHARS {
+            let rest = current.split_off(LINE_CHARS);
+            lines.push(current);
+            current = rest;
+        }
+    }
+    lines.push(current);
+    lines
+}
+
+/// Save in `qrs.pdf`, in the directory of `path`, a printable document with a page for every
+/// structured append QR code containing the title, the summary lines, the part index and the
+/// checksum of the data
+pub fn save_qr_document(
+    splitted: &SplittedQr,
+    title: &str,
+    summary: &[String],
+    mut path: PathBuf,
+) -> Result<PathBuf> {
+    let qrs = splitted.split()?;
+    let checksum = checksum(&splitted.bytes);
+    let total = qrs.len();
+    let pages: Vec<Page> = qrs
+        .into_iter()
+        .enumerate()
+        .map(|(i, qr)| Page {
+            title: title.to_string(),
+            lines: summary.to_vec(),
+            qr,
+            footer: format!("part {}/{}   checksum {}", i + 1, total, checksum),
+        })
+        .collect();
+
+    path.set_file_name("qrs.pdf");
+    save_pages(&pages, &path)?;
+    Ok(path)
+}
+
+/// Save the pages as a PDF document in `path`
+pub fn save_pages(pages: &[Page], path: &Path) -> Result<()> {
+    let contents: Vec<Vec<u8>> = pages.iter().map(page_content).collect();
+    info!("Saving document in {:?}", path);
+    fs::write(path, pdf(&contents))?;
+    Ok(())
+}
+
+/// Drawing operators of a page, the QR code is centered below the text in the remaining space,
+/// dark modules are drawn as rectangles merging the consecutive ones of a row
+fn page_content(page: &Page) -> Vec<u8> {
+    let mut content = String::new();
+    content.push_str(&text(16, MARGIN, PAGE_HEIGHT - MARGIN, &[&page.title]));
+    let lines: Vec<&str> = page.lines.iter().map(|s| s.as_str()).collect();
+    content.push_str(&text(10, MARGIN, PAGE_HEIGHT - MARGIN - 24, &lines));
+    content.push_str(&text(12, MARGIN, MARGIN, &[&page.footer]));
+
+    let qr = &page.qr;
+    let width = qr.width();
+    let colors = qr.clone().into_colors();
+    let available_width = (PAGE_WIDTH - 2 * MARGIN) as f64;
+    let space_top = (PAGE_HEIGHT - 2 * MARGIN) as f64 - 14.0 * lines.len() as f64;
+    let available_height = space_top - (MARGIN + 20) as f64;
+    // 4 modules of quiet zone on each side
+    let side = (width + 8) as f64;
+    let module = available_width.min(available_height) / side;
+    let left = MARGIN as f64 + (available_width - module * side) / 2.0 + 4.0 * module;
+    let top = space_top - 4.0 * module;
+    for (y, row) in colors.chunks(width).enumerate() {
+        let mut x = 0;
+        while x < width {
+            if row[x] == Color::Dark {
+                let start = x;
+                while x < width && row[x] == Color::Dark {
+                    x += 1;
+                }
+                content.push_str(&format!(
+                    "{:.2} {:.2} {:.2} {:.2} re\n",
+                    left + start as f64 * module,
+                    top - (y + 1) as f64 * module,
+                    (x - start) as f64 * module,
+                    module
+                ));
+            } else {
+                x += 1;
+            }
+        }
+    }
+    content.push_str("f\n");
+    content.into_bytes()
+}
+
+/// Text lines in the Courier font, starting from the given point
+fn text(size: usize, x: usize, y: usize, lines: &[&str]) -> String {
+    let mut result = format!("BT /F1 {} Tf {} TL {} {} Td\n", size, size + 4, x, y);
+    for line in lines {
+        result.push_str(&format!("({}) Tj T*\n", escape(line)));
+    }
+    result.push_str("ET\n");
+    result
+}
+
+/// Escape a PDF string, non ASCII chars are not in the standard font encoding
+fn escape(line: &str) -> String {
+    line.chars()
+        .map(|c| match c {
+            '\\' | '(' | ')' => format!("\\{}", c),
+            ' '..='~' => c.to_string(),
+            _ => "?".to_string(),
+        })
+        .collect()
+}
+
+/// PDF with the given page contents, it uses the standard Courier font which doesn't need to be
+/// embedded
+fn pdf(pages: &[Vec<u8>]) -> Vec<u8> {
+    // objects: 1 catalog, 2 pages, 3 font, then page and content of every page
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", 4 + i * 2))
+        .collect();
+    let mut objects = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_vec(),
+    ];
+    for (i, content) in pages.iter().enumerate() {
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                PAGE_WIDTH,
+                PAGE_HEIGHT,
+                5 + i * 2
+            )
+            .into_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend_from_slice(content);
+        stream.extend_from_slice(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut result = b"%PDF-1.4\n".to_vec();
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(result.len());
+        result.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        result.extend_from_slice(object);
+        result.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = result.len();
+    result.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        result.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    result.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::coordinator::{import, WalletFormat};
+    use crate::common::document::{
+        checksum, escape, psbt_summary, save_qr_document, wallet_summary,
+    };
+    use crate::common::qr::SplittedQr;
+    use crate::PsbtJson;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_qr_document() {
+        let temp_dir = TempDir::new("test_qr_document").unwrap();
+        let data: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let splitted = SplittedQr::new(data.clone(), 10).unwrap();
+        assert_eq!(splitted.total_qr, 4);
+        let summary = vec!["txid: 1234".to_string(), "fee: 0.0001".to_string()];
+        let path = temp_dir.path().join("filename");
+        let path = save_qr_document(&splitted, "PSBT (test)", &summary, path).unwrap();
+        assert!(path.ends_with("qrs.pdf"));
+
+        let pdf = std::fs::read(&path).unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert_eq!(text.matches("/Type /Page ").count(), 4);
+        assert!(text.contains("(PSBT \\(test\\)) Tj"));
+        assert!(text.contains("(fee: 0.0001) Tj"));
+        let checksum = checksum(&data);
+        assert_eq!(checksum.len(), 16);
+        for i in 1..=4 {
+            let part = format!("(part {}/4   checksum {}) Tj", i, checksum);
+            assert!(text.contains(&part));
+        }
+
+        // xref entries point to the objects
+        let xref_start = text.rfind("startxref\n").unwrap() + 10;
+        let xref: usize = text[xref_start..].lines().next().unwrap().parse().unwrap();
+        let entries: Vec<&str> = text[xref..].lines().skip(3).take(11).collect();
+        for (i, entry) in entries.iter().enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+        }
+
+        assert_eq!(escape("a\\b€"), "a\\\\b?");
+    }
+
+    #[test]
+    fn test_summary() {
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.json");
+        let psbt: PsbtJson = serde_json::from_slice(bytes).unwrap();
+        let summary = psbt_summary(&psbt).unwrap();
+        assert_eq!(
+            summary,
+            vec![
+                "txid: 82efd652d7ab1197f01a5f4d9a30cb4c68bb79ab6fec58dfa1bf112291d1617b",
+                "fee: 0.00000192 BTC",
+                "inputs: 2 outputs: 2",
+                "fingerprints: d90c6a4f",
+            ]
+        );
+
+        let content = include_str!("../../test_data/import/specter.json");
+        let wallet = import(content, WalletFormat::Specter, None, None).unwrap();
+        let summary = wallet_summary(&wallet).unwrap();
+        assert_eq!(summary[0], "required signatures: 2 of 2");
+        assert_eq!(summary[1], "fingerprints: 3442193e");
+        assert!(wallet.descriptor_main.ends_with(&summary[2][21..]));
+    }
+}
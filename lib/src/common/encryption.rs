@@ -0,0 +1,199 @@
+use crate::{Error, PrivateMasterKey, Result};
+use chacha20poly1305::aead::{Aead, NewAead, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use rand::Rng;
+use scrypt::ScryptParams;
+use serde::{Deserialize, Serialize};
+
+const VERSION: u8 = 1;
+const CIPHER: &str = "chacha20poly1305";
+const KDF: &str = "scrypt";
+#[cfg(not(test))]
+const KDF_LOG_N: u8 = 15;
+#[cfg(test)]
+const KDF_LOG_N: u8 = 4; // keep tests fast, scrypt is slow in debug builds
+
+/// Envelope of a private key file encrypted with a passphrase
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EncryptedPrivateKey {
+    pub version: u8,
+    pub kdf: KdfParams,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct KdfParams {
+    pub name: String,
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    pub salt: String,
+}
+
+impl KdfParams {
+    fn new(salt: &[u8]) -> Self {
+        KdfParams {
+            name: KDF.to_string(),
+            log_n: KDF_LOG_N,
+            r: 8,
+            p: 1,
+            salt: hex::encode(salt),
+        }
+    }
+
+    fn derive(&self, passphrase: &str) -> Result<[u8; 32]> {
+        if self.name != KDF {
+            return Err(Error::Encryption(format!("unsupported kdf {}", self.name)));
+        }
+        let params = ScryptParams::new(self.log_n, self.r, self.p)
+            .map_err(|_| Error::Encryption("invalid kdf params".into()))?;
+        let salt = hex::decode(&self.salt)?;
+        let mut key = [0u8; 32];
+        scrypt::scrypt(passphrase.as_bytes(), &salt, &params, &mut key)
+            .map_err(|_| Error::Encryption("invalid kdf output length".into()))?;
+        Ok(key)
+    }
+}
+
+impl EncryptedPrivateKey {
+    pub fn encrypt(key: &PrivateMasterKey, passphrase: &str) -> Result<Self> {
+        if passphrase.is_empty() {
+            return Err(Error::Encryption("passphrase cannot be empty".into()));
+        }
+        let mut rng = rand::thread_rng();
+        let kdf = KdfParams::new(&rng.gen::<[u8; 16]>());
+        let nonce = rng.gen::<[u8; 12]>();
+        let mut envelope = EncryptedPrivateKey {
+            version: VERSION,
+            kdf,
+            cipher: CIPHER.to_string(),
+            nonce: hex::encode(&nonce),
+            ciphertext: String::new(),
+        };
+
+        let plaintext = serde_json::to_vec(key)?;
+        let aad = envelope.aad();
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&envelope.kdf.derive(passphrase)?));
+        let payload = Payload {
+            msg: &plaintext,
+            aad: aad.as_bytes(),
+        };
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| Error::Encryption("encryption failed".into()))?;
+        envelope.ciphertext = base64::encode(&ciphertext);
+
+        Ok(envelope)
+    }
+
+    pub fn decrypt(&self, passphrase: &str) -> Result<PrivateMasterKey> {
+        if self.version != VERSION || self.cipher != CIPHER {
+            return Err(Error::Encryption(format!(
+                "unsupported version {} or cipher {}",
+                self.version, self.cipher
+            )));
+        }
+        let nonce = hex::decode(&self.nonce)?;
+        if nonce.len() != 12 {
+            return Err(Error::Encryption("invalid nonce length".into()));
+        }
+        let ciphertext = base64::decode(&self.ciphertext)?;
+        let aad = self.aad();
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&self.kdf.derive(passphrase)?));
+        let payload = Payload {
+            msg: &ciphertext,
+            aad: aad.as_bytes(),
+        };
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|_| Error::Encryption("wrong passphrase or corrupted file".into()))?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Header fields authenticated together with the ciphertext
+    fn aad(&self) -> String {
+        format!(
+            "firma:{}:{}:{}:{}:{}:{}:{}:{}",
+            self.version,
+            self.kdf.name,
+            self.kdf.log_n,
+            self.kdf.r,
+            self.kdf.p,
+            self.kdf.salt,
+            self.cipher,
+            self.nonce
+        )
+    }
+}
+
+/// Content of a private key file, either in clear or encrypted
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PrivateKeyFile {
+    Encrypted(EncryptedPrivateKey),
+    Plain(PrivateMasterKey),
+}
+
+impl PrivateKeyFile {
+    pub fn is_encrypted(&self) -> bool {
+        match self {
+            PrivateKeyFile::Encrypted(_) => true,
+            PrivateKeyFile::Plain(_) => false,
+        }
+    }
+
+    pub fn into_key(self, passphrase: Option<&str>) -> Result<PrivateMasterKey> {
+        match (self, passphrase) {
+            (PrivateKeyFile::Plain(key), _) => Ok(key),
+            (PrivateKeyFile::Encrypted(encrypted), Some(passphrase)) => {
+                encrypted.decrypt(passphrase)
+            }
+            (PrivateKeyFile::Encrypted(_), None) => Err(Error::Encryption(
+                "key file is encrypted, passphrase required".into(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::encryption::{EncryptedPrivateKey, PrivateKeyFile};
+    use crate::PrivateMasterKey;
+    use bitcoin::Network;
+
+    #[test]
+    fn test_encrypt_decrypt() {
+        let key = PrivateMasterKey::new(Network::Testnet, &[1u8; 32], "name").unwrap();
+        let encrypted = EncryptedPrivateKey::encrypt(&key, "passphrase").unwrap();
+        assert_eq!(encrypted.decrypt("passphrase").unwrap(), key);
+
+        let result = encrypted.decrypt("wrong");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Encryption error: wrong passphrase or corrupted file"
+        );
+
+        let mut tampered = encrypted.clone();
+        tampered.kdf.log_n += 1;
+        assert!(tampered.decrypt("passphrase").is_err());
+
+        let encrypted_2 = EncryptedPrivateKey::encrypt(&key, "passphrase").unwrap();
+        assert_ne!(encrypted, encrypted_2, "salt and nonce must be random");
+
+        assert!(EncryptedPrivateKey::encrypt(&key, "").is_err());
+
+        let json = serde_json::to_string(&encrypted).unwrap();
+        let file: PrivateKeyFile = serde_json::from_str(&json).unwrap();
+        assert!(file.is_encrypted());
+        assert!(file.clone().into_key(None).is_err());
+        assert_eq!(file.into_key(Some("passphrase")).unwrap(), key);
+
+        let json = serde_json::to_string(&key).unwrap();
+        let file: PrivateKeyFile = serde_json::from_str(&json).unwrap();
+        assert!(!file.is_encrypted());
+        assert_eq!(file.into_key(None).unwrap(), key);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::ErrorJson;
+use crate::{ErrorJson, PolicyViolation};
 use serde_json::Value;
 use std::path::PathBuf;
 
@@ -8,7 +8,10 @@ pub enum Error {
 
     // Internal
     InvalidStructuredQr(String),
+    InvalidMnemonic(String),
+    Encryption(String),
     FileExist(PathBuf),
+    SpendingPolicy(Vec<PolicyViolation>),
 
     // External
     BitcoinRpc(bitcoincore_rpc::Error),
@@ -68,7 +71,13 @@ impl ToString for Error {
             Error::Generic(e) => e.to_string(),
 
             Error::InvalidStructuredQr(s) => format!("Invalid structured QR: {}", s),
+            Error::InvalidMnemonic(s) => format!("Invalid mnemonic: {}", s),
+            Error::Encryption(s) => format!("Encryption error: {}", s),
             Error::FileExist(s) => format!("file {} already exist", s.display()),
+            Error::SpendingPolicy(v) => {
+                let reasons: Vec<String> = v.iter().map(|e| e.to_string()).collect();
+                format!("Refused by spending policy: {}", reasons.join(", "))
+            }
 
             Error::BitcoinRpc(e) => e.to_string(),
             Error::Serde(e) => e.to_string(),
@@ -117,6 +126,10 @@ impl Error {
     pub fn to_json(&self) -> Value {
         let value = ErrorJson {
             error: self.to_string(),
+            policy_violations: match self {
+                Error::SpendingPolicy(v) => v.clone(),
+                _ => vec![],
+            },
         };
         serde_json::to_value(&value).unwrap() // safe to unwrap, ErrorJson does not contain map with non string keys
     }
@@ -1,3 +1,5 @@
+use crate::common::encryption::{EncryptedPrivateKey, PrivateKeyFile};
+use crate::common::key_origin::XpubWithOrigin;
 use crate::*;
 use bitcoin::Network;
 use log::info;
@@ -22,7 +24,7 @@ pub enum KindAndName {
     PSBT(String),
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Kind {
     #[serde(rename = "wallets")]
     Wallet,
@@ -78,6 +80,10 @@ impl PathBuilder {
             .name
             .as_ref()
             .ok_or_else(|| Error::Generic("missing name".into()))?;
+        validate_name(content)?;
+        if let Some(subdir) = subdir {
+            validate_name(subdir)?;
+        }
         let kind = self.kind.to_string();
         let network_string = format!("{}", self.network);
         let mut paths: Vec<&str> = vec![&self.datadir, &network_string, &kind, &content];
@@ -99,6 +105,24 @@ impl PathBuilder {
     }
 }
 
+/// Check the name of a wallet, key or PSBT can be a directory of the datadir, names may come from
+/// untrusted files and QR codes and must not point outside it
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with('.')
+        || name.contains("..")
+        || name.contains('/')
+        || name.contains('\\')
+    {
+        return Err(format!(
+            "invalid name {:?}, it cannot be empty, start with a dot or contain path separators",
+            name
+        )
+        .into());
+    }
+    Ok(())
+}
+
 fn path_for(dirs: Vec<&str>) -> Result<PathBuf> {
     let mut path = PathBuf::from(
         dirs.get(0)
@@ -151,25 +175,40 @@ pub fn save_public(public_key: &PublicMasterKey, output: &PathBuf) -> Result<()>
     save(serde_json::to_string_pretty(public_key)?, output)
 }
 
-pub fn save_private(private_key: &PrivateMasterKey, output: &PathBuf) -> Result<()> {
+pub fn save_private(
+    private_key: &PrivateMasterKey,
+    output: &PathBuf,
+    encryption_passphrase: Option<&str>,
+) -> Result<()> {
     if output.exists() {
         return Err(Error::FileExist(output.clone()));
     }
-    save(serde_json::to_string_pretty(private_key)?, output)
+    let value = match encryption_passphrase {
+        Some(passphrase) => {
+            let encrypted = EncryptedPrivateKey::encrypt(private_key, passphrase)?;
+            serde_json::to_string_pretty(&encrypted)?
+        }
+        None => serde_json::to_string_pretty(private_key)?,
+    };
+    save(value, output)
 }
 
 pub fn save_keys(
     datadir: &str,
     network: Network,
     key_name: &str,
-    key: PrivateMasterKey,
+    mut key: PrivateMasterKey,
     qr_version: i16,
+    encryption_passphrase: Option<&str>,
 ) -> Result<MasterKeyOutput> {
+    if key.created_at.is_none() {
+        key.created_at = Some(now()?);
+    }
     let option_name = Some(key_name.to_string());
     let path_builder = PathBuilder::new(datadir, network, Kind::Key, option_name.clone());
     let private_key_file = path_builder.file("PRIVATE.json")?;
     let public_key_file = path_builder.file("public.json")?;
-    save_private(&key, &private_key_file)?;
+    save_private(&key, &private_key_file, encryption_passphrase)?;
     let public_master_key = key.clone().into();
     save_public(&public_master_key, &public_key_file)?;
 
@@ -190,6 +229,24 @@ pub fn save_keys(
     })
 }
 
+pub fn read_key_file(path: &PathBuf) -> Result<PrivateKeyFile> {
+    let is_key = path
+        .file_name()
+        .ok_or_else(|| Error::Generic("no file_name".into()))?
+        .to_str()
+        .ok_or_else(|| Error::Generic("OsStr".into()))?
+        == "PRIVATE.json";
+    if !is_key {
+        return Err(Error::Generic("private name MUST be PRIVATE.json".into()));
+    }
+    let content = fs::read(path)?;
+    Ok(serde_json::from_slice(&content)?)
+}
+
+pub fn read_key(path: &PathBuf, encryption_passphrase: Option<&str>) -> Result<PrivateMasterKey> {
+    read_key_file(path)?.into_key(encryption_passphrase)
+}
+
 pub fn read_psbt_json(path: &Path) -> Result<PsbtJson> {
     let slice = fs::read(path)?;
     Ok(serde_json::from_slice(&slice)?)
@@ -200,9 +257,38 @@ pub fn read_psbt(path: &Path) -> Result<PSBT> {
     Ok(psbt_from_base64(&psbt_json.psbt)?.1)
 }
 
+/// Read the wallet descriptor file, rejecting it if the descriptors checksum does not match
 pub fn read_wallet(path: &PathBuf) -> Result<WalletJson> {
     let wallet = fs::read(path)?;
-    Ok(serde_json::from_slice(&wallet)?)
+    let wallet: WalletJson = serde_json::from_slice(&wallet)?;
+    wallet.validate()?;
+    Ok(wallet)
+}
+
+/// Read extended public keys with their origin from public key files (public.json)
+pub fn read_xpubs_files(paths: &[PathBuf]) -> Result<Vec<XpubWithOrigin>> {
+    let mut xpubs = vec![];
+    for xpub_path in paths.iter() {
+        let content = fs::read(xpub_path)?;
+        let json: PublicMasterKey = serde_json::from_slice(&content)?;
+        xpubs.push(json.into());
+    }
+    Ok(xpubs)
+}
+
+/// Read the descriptor of the wallet with the given name saved in the datadir
+pub fn read_wallet_by_name(datadir: &str, network: Network, name: &str) -> Result<WalletJson> {
+    validate_name(name)?;
+    let mut path = PathBuilder::new(datadir, network, Kind::Wallet, None).type_path()?;
+    path.push(name);
+    path.push("descriptor.json");
+    read_wallet(&path).map_err(|e| {
+        Error::Generic(format!(
+            "{} not found or invalid: {}",
+            path.display(),
+            e.to_string()
+        ))
+    })
 }
 
 /*
@@ -217,3 +303,48 @@ pub fn filename_for_qr(datadir: &str,
     Ok(path)
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use crate::common::file::{validate_name, PathBuilder};
+    use crate::Kind;
+    use bitcoin::Network;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_validate_name() {
+        for name in &["wallet", "2of3-vault", "psbt_1", "a.b"] {
+            assert!(validate_name(name).is_ok(), "{}", name);
+        }
+        for name in &[
+            "",
+            ".",
+            "..",
+            ".hidden",
+            "../../../x",
+            "a/b",
+            "a\\b",
+            "a..b",
+        ] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+
+        let temp_dir = TempDir::new("test_validate_name").unwrap();
+        let datadir = format!("{}/datadir/", temp_dir.path().display());
+        let path_builder = PathBuilder::new(
+            &datadir,
+            Network::Testnet,
+            Kind::PSBT,
+            Some("../../../x".to_string()),
+        );
+        assert!(path_builder.file("psbt.json").is_err());
+        let path_builder = PathBuilder::new(
+            &datadir,
+            Network::Testnet,
+            Kind::PSBT,
+            Some("x".to_string()),
+        );
+        assert!(path_builder.file_with_subdir(Some(".."), "qr").is_err());
+        assert!(!temp_dir.path().join("x").exists());
+    }
+}
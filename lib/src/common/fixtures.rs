@@ -0,0 +1,40 @@
+//! Keys and wallets shared by the unit tests
+
+use crate::common::descriptor::{add_checksum, DescriptorScript, WalletDescriptor};
+use crate::WalletJson;
+use std::str::FromStr;
+
+// accounts 0 and 1 of the bip32 test vector 1 seed 000102030405060708090a0b0c0d0e0f
+pub const ACCOUNT_XPUB: &str = "[3442193e/48'/1'/0'/2']tpubDEC8p4skY4i7mNjxt9yF3u7my5T6KtGLcvEDcH5rKA6XVtJN4JV3SsHWoePTqsHNh47WBkQ79r77KsUYc2PAmaqqasBJMvidbxiupZpJexg";
+pub const ACCOUNT_1_XPUB: &str = "[3442193e/48'/1'/1'/2']tpubDFAkyi7Hw6GQJuttSzbBMEdxXH2nyz1pV2pVJtUikW5XgZgw1JEaCEdcsK5Vq6zvhhJacm4rY42AypLDc8pbsRT76Z5wNEVPYzN27oLPFRi";
+/// `ACCOUNT_XPUB` without key origin, with SLIP-132 multisig p2wsh version bytes
+pub const ACCOUNT_VPUB: &str = "Vpub5m44ojSg6PE5DZLFhustFswD1p6ofcTsZLF9cCWUb1RHGaa7FAh79RALKuLyszFCKLQxwohEadxTzHNa4zrrSA91d49YuytRNHH5sNMjgYn";
+
+/// Valid wallet of the main descriptor without checksum and with keys ending in `/0/*`, the
+/// change descriptor uses `/1/*` and the other fields are taken from the descriptor
+pub fn wallet(name: &str, descriptor_main: &str) -> WalletJson {
+    let descriptor_change = add_checksum(&descriptor_main.replace("/0/*", "/1/*")).unwrap();
+    let descriptor_main = add_checksum(descriptor_main).unwrap();
+    let parsed = WalletDescriptor::from_str(&descriptor_main).unwrap();
+    let (required_sig, sorted, policy) = match &parsed.script {
+        DescriptorScript::Wpkh(_) => (Some(1), false, None),
+        DescriptorScript::Multi {
+            required, sorted, ..
+        } => (Some(*required), *sorted, None),
+        DescriptorScript::Miniscript { miniscript, .. } => (None, false, Some(miniscript.clone())),
+    };
+    let wallet = WalletJson {
+        name: name.to_string(),
+        fingerprints: parsed.fingerprints(),
+        descriptor_main,
+        descriptor_change,
+        required_sig,
+        policy,
+        sorted,
+        script_type: parsed.script_type,
+        daemon_opts: None,
+        created_at_height: 0,
+    };
+    wallet.validate().unwrap();
+    wallet
+}
@@ -0,0 +1,470 @@
+use crate::common::cbor::Cbor;
+use crate::*;
+use bitcoin::hashes::{sha256, Hash};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Fragments shorter than this are not created, unless the message itself is shorter
+const MIN_FRAGMENT_LEN: usize = 10;
+
+/// Parts declaring more fragments are refused, choosing fragments is quadratic in their number
+const MAX_SEQ_LEN: usize = 10_000;
+
+/// CRC-32 (ISO-HDLC, as in zlib) used by UR parts and bytewords
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Xoshiro256** seeded with the sha256 of the seed, as in BCR-2020-005
+pub struct Xoshiro256 {
+    s: [u64; 4],
+}
+
+impl Xoshiro256 {
+    pub fn new(seed: &[u8]) -> Self {
+        let hash = sha256::Hash::hash(seed).into_inner();
+        let mut s = [0u64; 4];
+        for (i, chunk) in hash.chunks(8).enumerate() {
+            s[i] = chunk.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+        }
+        Xoshiro256 { s }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let s = &mut self.s;
+        let result = s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn next_double(&mut self) -> f64 {
+        self.next_u64() as f64 / (u64::max_value() as f64 + 1.0)
+    }
+
+    /// Random integer in the closed range `low..=high`
+    pub fn next_int(&mut self, low: u64, high: u64) -> u64 {
+        (self.next_double() * (high - low + 1) as f64) as u64 + low
+    }
+}
+
+/// Remove random items until none is left, the order of removal is the result
+fn shuffled<T>(mut items: Vec<T>, rng: &mut Xoshiro256) -> Vec<T> {
+    let mut result = Vec::with_capacity(items.len());
+    while !items.is_empty() {
+        let index = rng.next_int(0, items.len() as u64 - 1) as usize;
+        result.push(items.remove(index));
+    }
+    result
+}
+
+/// Walker-Vose alias method, returns indexes with the given (not normalized) probabilities
+struct RandomSampler {
+    probs: Vec<f64>,
+    aliases: Vec<usize>,
+}
+
+impl RandomSampler {
+    fn new(probs: &[f64]) -> Self {
+        let n = probs.len();
+        let total: f64 = probs.iter().sum();
+        let mut scaled: Vec<f64> = probs.iter().map(|p| p * n as f64 / total).collect();
+        let (mut small, mut large) = (vec![], vec![]);
+        for i in (0..n).rev() {
+            if scaled[i] < 1.0 {
+                small.push(i);
+            } else {
+                large.push(i);
+            }
+        }
+        let mut result_probs = vec![0.0; n];
+        let mut aliases = vec![0; n];
+        while !small.is_empty() && !large.is_empty() {
+            let a = small.pop().expect("checked not empty");
+            let g = large.pop().expect("checked not empty");
+            result_probs[a] = scaled[a];
+            aliases[a] = g;
+            scaled[g] += scaled[a] - 1.0;
+            if scaled[g] < 1.0 {
+                small.push(g);
+            } else {
+                large.push(g);
+            }
+        }
+        for i in large.into_iter().chain(small.into_iter()) {
+            result_probs[i] = 1.0;
+        }
+        RandomSampler {
+            probs: result_probs,
+            aliases,
+        }
+    }
+
+    fn next(&self, rng: &mut Xoshiro256) -> usize {
+        let r1 = rng.next_double();
+        let r2 = rng.next_double();
+        let i = (self.probs.len() as f64 * r1) as usize;
+        if r2 < self.probs[i] {
+            i
+        } else {
+            self.aliases[i]
+        }
+    }
+}
+
+/// Indexes of the fragments xored in the part `seq`, first `seq_len` parts are the fragments
+pub fn choose_fragments(seq: u32, seq_len: usize, checksum: u32) -> Vec<usize> {
+    if seq as usize <= seq_len {
+        return vec![seq as usize - 1];
+    }
+    let mut seed = seq.to_be_bytes().to_vec();
+    seed.extend_from_slice(&checksum.to_be_bytes());
+    let mut rng = Xoshiro256::new(&seed);
+    let probs: Vec<f64> = (1..=seq_len).map(|i| 1.0 / i as f64).collect();
+    let degree = RandomSampler::new(&probs).next(&mut rng) + 1;
+    let mut indexes = shuffled((0..seq_len).collect(), &mut rng);
+    indexes.truncate(degree);
+    indexes
+}
+
+/// Part of a fountain coded message, serialized as cbor `[seq, seqLen, messageLen, checksum, data]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Part {
+    pub seq: u32,
+    pub seq_len: usize,
+    pub message_len: usize,
+    pub checksum: u32,
+    pub data: Vec<u8>,
+}
+
+impl Part {
+    pub fn to_cbor(&self) -> Vec<u8> {
+        Cbor::Array(vec![
+            Cbor::Unsigned(u64::from(self.seq)),
+            Cbor::Unsigned(self.seq_len as u64),
+            Cbor::Unsigned(self.message_len as u64),
+            Cbor::Unsigned(u64::from(self.checksum)),
+            Cbor::Bytes(self.data.clone()),
+        ])
+        .encode()
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> Result<Part> {
+        let cbor = Cbor::decode(bytes)?;
+        match cbor.as_array()? {
+            [seq, seq_len, message_len, checksum, data] => {
+                let part = Part {
+                    seq: to_u32(seq)?,
+                    seq_len: to_u32(seq_len)? as usize,
+                    message_len: to_u32(message_len)? as usize,
+                    checksum: to_u32(checksum)?,
+                    data: data.as_bytes()?.to_vec(),
+                };
+                part.validate()?;
+                Ok(part)
+            }
+            _ => Err("ur part must be an array of 5 elements".into()),
+        }
+    }
+
+    /// Check that the fragment length agrees with message length and number of fragments
+    fn validate(&self) -> Result<()> {
+        let fragment_len = self.data.len() as u64;
+        let (seq_len, message_len) = (self.seq_len as u64, self.message_len as u64);
+        if self.seq == 0
+            || self.seq_len == 0
+            || self.seq_len > MAX_SEQ_LEN
+            || fragment_len == 0
+            || message_len > fragment_len * seq_len
+            || message_len <= fragment_len * (seq_len - 1)
+        {
+            return Err("inconsistent ur part header".into());
+        }
+        Ok(())
+    }
+}
+
+fn to_u32(value: &Cbor) -> Result<u32> {
+    let value = value.as_u64()?;
+    if value > u64::from(u32::max_value()) {
+        return Err("ur part value out of range".into());
+    }
+    Ok(value as u32)
+}
+
+fn xor_into(target: &mut [u8], other: &[u8]) {
+    target.iter_mut().zip(other).for_each(|(t, o)| *t ^= o);
+}
+
+/// Split the message in equal fragments and emit an unlimited sequence of parts,
+/// after the first `seq_len` parts each one is the xor of some random fragments
+pub struct FountainEncoder {
+    fragments: Vec<Vec<u8>>,
+    message_len: usize,
+    checksum: u32,
+    seq: u32,
+}
+
+impl FountainEncoder {
+    pub fn new(message: &[u8], max_fragment_len: usize) -> Result<Self> {
+        if message.is_empty() {
+            return Err("cannot encode an empty message".into());
+        }
+        if max_fragment_len < MIN_FRAGMENT_LEN {
+            return Err(format!(
+                "max fragment length {} is less than {}",
+                max_fragment_len, MIN_FRAGMENT_LEN
+            )
+            .into());
+        }
+        let fragment_len = fragment_len(message.len(), max_fragment_len);
+        if (message.len() + fragment_len - 1) / fragment_len > MAX_SEQ_LEN {
+            return Err("message too big for ur encoding".into());
+        }
+        let fragments = message
+            .chunks(fragment_len)
+            .map(|chunk| {
+                let mut fragment = chunk.to_vec();
+                fragment.resize(fragment_len, 0);
+                fragment
+            })
+            .collect();
+        Ok(FountainEncoder {
+            fragments,
+            message_len: message.len(),
+            checksum: crc32(message),
+            seq: 0,
+        })
+    }
+
+    pub fn seq_len(&self) -> usize {
+        self.fragments.len()
+    }
+
+    pub fn is_single_part(&self) -> bool {
+        self.fragments.len() == 1
+    }
+
+    pub fn next_part(&mut self) -> Part {
+        self.seq += 1;
+        let fragment_len = self.fragments[0].len();
+        let mut data = vec![0u8; fragment_len];
+        for i in choose_fragments(self.seq, self.seq_len(), self.checksum) {
+            xor_into(&mut data, &self.fragments[i]);
+        }
+        Part {
+            seq: self.seq,
+            seq_len: self.seq_len(),
+            message_len: self.message_len,
+            checksum: self.checksum,
+            data,
+        }
+    }
+}
+
+/// Smallest number of fragments, of at least `MIN_FRAGMENT_LEN` bytes, not exceeding `max` bytes
+fn fragment_len(message_len: usize, max: usize) -> usize {
+    let max_count = (message_len / MIN_FRAGMENT_LEN).max(1);
+    let mut len = message_len;
+    for count in 1..=max_count {
+        len = (message_len + count - 1) / count;
+        if len <= max {
+            break;
+        }
+    }
+    len
+}
+
+/// Receive parts in any order, with duplicates and losses, until the message is rebuilt
+#[derive(Default)]
+pub struct FountainDecoder {
+    /// `(seq_len, message_len, checksum, fragment_len)` of the first part received
+    expected: Option<(usize, usize, u32, usize)>,
+    simple: BTreeMap<usize, Vec<u8>>,
+    mixed: Vec<(BTreeSet<usize>, Vec<u8>)>,
+    message: Option<Vec<u8>>,
+}
+
+impl FountainDecoder {
+    pub fn receive(&mut self, part: &Part) -> Result<()> {
+        part.validate()?;
+        let header = (
+            part.seq_len,
+            part.message_len,
+            part.checksum,
+            part.data.len(),
+        );
+        match self.expected {
+            None => self.expected = Some(header),
+            Some(expected) if expected != header => {
+                return Err("ur part does not belong to the same message".into())
+            }
+            Some(_) => (),
+        }
+        if self.is_complete() {
+            return Ok(());
+        }
+
+        let indexes = choose_fragments(part.seq, part.seq_len, part.checksum);
+        let mut queue = vec![(indexes.into_iter().collect(), part.data.clone())];
+        while let Some((indexes, data)) = queue.pop() {
+            self.process(indexes, data, &mut queue);
+        }
+
+        if self.simple.len() == part.seq_len {
+            let mut message: Vec<u8> = self.simple.values().flatten().cloned().collect();
+            message.truncate(part.message_len);
+            if crc32(&message) != part.checksum {
+                return Err("invalid checksum of the ur message".into());
+            }
+            self.message = Some(message);
+        }
+        Ok(())
+    }
+
+    /// Reduce the part with the known fragments, store it and reduce the stored parts with it
+    fn process(
+        &mut self,
+        mut indexes: BTreeSet<usize>,
+        mut data: Vec<u8>,
+        queue: &mut Vec<(BTreeSet<usize>, Vec<u8>)>,
+    ) {
+        for i in indexes.clone() {
+            if let Some(fragment) = self.simple.get(&i) {
+                xor_into(&mut data, fragment);
+                indexes.remove(&i);
+            }
+        }
+        for (mixed_indexes, mixed_data) in self.mixed.iter() {
+            if mixed_indexes.len() < indexes.len() && mixed_indexes.is_subset(&indexes) {
+                xor_into(&mut data, mixed_data);
+                indexes = indexes.difference(mixed_indexes).cloned().collect();
+            }
+        }
+        if indexes.is_empty() || self.mixed.iter().any(|(m, _)| *m == indexes) {
+            return;
+        }
+
+        let (reducible, kept): (Vec<_>, Vec<_>) = self
+            .mixed
+            .drain(..)
+            .partition(|(m, _)| indexes.is_subset(m));
+        self.mixed = kept;
+        for (mixed_indexes, mut mixed_data) in reducible {
+            xor_into(&mut mixed_data, &data);
+            queue.push((
+                mixed_indexes.difference(&indexes).cloned().collect(),
+                mixed_data,
+            ));
+        }
+
+        if indexes.len() == 1 {
+            let index = *indexes.iter().next().expect("len is 1");
+            self.simple.insert(index, data);
+        } else {
+            self.mixed.push((indexes, data));
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.message.is_some()
+    }
+
+    /// The rebuilt message, if all the fragments has been received
+    pub fn message(&self) -> Option<&[u8]> {
+        self.message.as_ref().map(|m| m.as_slice())
+    }
+
+    /// Number of fragments known and total number of fragments
+    pub fn progress(&self) -> (usize, usize) {
+        let total = self.expected.map(|e| e.0).unwrap_or(0);
+        (self.simple.len(), total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fountain::*;
+
+    #[test]
+    fn test_random() {
+        // vectors of the BC-UR reference implementation
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let numbers: Vec<u64> = (0..12).map(|_| rng.next_u64() % 100).collect();
+        assert_eq!(numbers, vec![42, 81, 85, 8, 82, 84, 76, 73, 70, 88, 2, 74]);
+
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let items: Vec<u32> = (1..=10).collect();
+        assert_eq!(
+            shuffled(items.clone(), &mut rng),
+            vec![6, 4, 9, 3, 10, 5, 7, 8, 1, 2]
+        );
+        assert_eq!(
+            shuffled(items, &mut rng),
+            vec![10, 8, 6, 5, 1, 2, 3, 9, 7, 4]
+        );
+
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let sampler = RandomSampler::new(&[1.0, 2.0, 4.0, 8.0]);
+        let samples: Vec<usize> = (0..12).map(|_| sampler.next(&mut rng)).collect();
+        assert_eq!(samples, vec![3, 3, 3, 3, 3, 3, 3, 0, 2, 3, 3, 3]);
+
+        assert_eq!(crc32(b"Hello, world!"), 0xebe6_c6e6);
+        assert_eq!(crc32(b"Wolf"), 0x598c_84dc);
+    }
+
+    #[test]
+    fn test_fountain() {
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let message: Vec<u8> = (0..1000).map(|_| rng.next_int(0, 255) as u8).collect();
+
+        assert_eq!(fragment_len(12345, 1955), 1764);
+        assert_eq!(fragment_len(12345, 30000), 12345);
+        assert_eq!(fragment_len(5, 100), 5);
+
+        let mut encoder = FountainEncoder::new(&message, 100).unwrap();
+        assert_eq!(encoder.seq_len(), 10);
+        let parts: Vec<Part> = (0..40).map(|_| encoder.next_part()).collect();
+        for part in parts.iter() {
+            assert_eq!(&Part::from_cbor(&part.to_cbor()).unwrap(), part);
+        }
+
+        // skip the simple parts, the message is rebuilt from the mixed ones
+        let mut decoder = FountainDecoder::default();
+        for part in parts.iter().skip(10) {
+            decoder.receive(part).unwrap();
+            if decoder.is_complete() {
+                break;
+            }
+        }
+        assert_eq!(decoder.message().unwrap(), message.as_slice());
+
+        let mut decoder = FountainDecoder::default();
+        for part in parts.iter().rev().step_by(2) {
+            decoder.receive(part).unwrap();
+        }
+        assert_eq!(decoder.message().unwrap(), message.as_slice());
+
+        let mut other = FountainEncoder::new(&message[1..], 100).unwrap();
+        let mut decoder = FountainDecoder::default();
+        decoder.receive(&parts[0]).unwrap();
+        assert_eq!(decoder.progress(), (1, 10));
+        assert!(decoder.receive(&other.next_part()).is_err());
+
+        let mut tampered = parts[0].clone();
+        tampered.message_len = 2000;
+        assert!(Part::from_cbor(&tampered.to_cbor()).is_err());
+    }
+}
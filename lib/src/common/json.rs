@@ -1,3 +1,6 @@
+use crate::common::coordinator::WalletFormat;
+use crate::common::file::Kind;
+use crate::common::key_origin::{KeyOrigin, XpubWithOrigin};
 use crate::DaemonOpts;
 use bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey, Fingerprint};
 use bitcoin::{bech32, Address, Network, OutPoint, Txid};
@@ -6,7 +9,10 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashSet;
 use std::convert::TryInto;
+use std::fmt;
+use std::io;
 use std::path::PathBuf;
+use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PrivateMasterKey {
@@ -16,8 +22,13 @@ pub struct PrivateMasterKey {
     pub seed: Option<Seed>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dice: Option<Dice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<Mnemonic>,
     pub name: String,
     pub fingerprint: Fingerprint,
+    /// Seconds since epoch of the key file creation, missing in keys created by older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -27,6 +38,12 @@ pub struct Dice {
     pub value: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Mnemonic {
+    pub words: String,
+    pub passphrase_protected: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Seed {
     pub hex: String,
@@ -43,9 +60,51 @@ pub struct MasterKeyOutput {
     pub public_qr_files: Vec<PathBuf>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EncryptKeyOutput {
+    pub private_file: PathBuf,
+    pub fingerprint: Fingerprint,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SplitKeyOutput {
+    pub fingerprint: Fingerprint,
+    pub group_threshold: u8,
+    pub groups: Vec<ShareGroupOutput>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct ShareGroup {
+    pub threshold: u8,
+    pub count: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ShareGroupOutput {
+    pub group: ShareGroup,
+    /// Words of every share, they are not saved in the data directory
+    pub shares: Vec<String>,
+    /// QR codes of the shares, only when a directory for them is given
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub qr_files: Vec<PathBuf>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PublicMasterKey {
     pub xpub: ExtendedPubKey,
+    /// Present when the xpub is not the master one, eg. a BIP48 account xpub
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_origin: Option<KeyOrigin>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExportXpubOutput {
+    pub xpub: XpubWithOrigin,
+    pub slip132: String,
+    pub public_file: PathBuf,
+    pub public_qr_files: Vec<PathBuf>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -56,6 +115,34 @@ pub struct PsbtJson {
     pub changepos: i32,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BackupSheetOutput {
+    pub fingerprint: Fingerprint,
+    /// Nature of the secret on the sheet, as in the `restore` command
+    pub nature: String,
+    pub created: String,
+    pub wallets: Vec<String>,
+    pub file: PathBuf,
+}
+
+/// Transaction extracted from the finalized PSBTs, saved in hex with its QR codes
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FinalizeOutput {
+    pub txid: String,
+    pub hex: String,
+    pub file: PathBuf,
+    pub qr_files: Vec<PathBuf>,
+}
+
+/// Item imported from QR codes, saved in the datadir as if created on this device
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ImportQrOutput {
+    pub kind: Kind,
+    pub name: String,
+    pub file: PathBuf,
+    pub qr_files: Vec<PathBuf>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct PsbtJsonOutput {
     pub psbt: PsbtJson,
@@ -69,12 +156,118 @@ pub struct WalletJson {
     pub descriptor_main: String,
     pub descriptor_change: String,
     pub fingerprints: HashSet<Fingerprint>,
-    pub required_sig: usize,
+    /// Signatures required to spend, absent for policy wallets where it depends on the spending
+    /// path
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_sig: Option<usize>,
+    /// Spending policy the descriptors are compiled from, absent for multisig wallets
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Keys in the multisig are sorted as in BIP67 (sortedmulti), false for wallets using multi
+    #[serde(default)]
+    pub sorted: bool,
+    #[serde(default)]
+    pub script_type: ScriptType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub daemon_opts: Option<DaemonOpts>,
     pub created_at_height: u64,
 }
 
+/// How the wallet script is wrapped in the output, native segwit, nested segwit or legacy,
+/// `wpkh` and `sh-wpkh` are the single signature types
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ScriptType {
+    #[serde(rename = "wsh")]
+    Wsh,
+    #[serde(rename = "sh-wsh")]
+    ShWsh,
+    #[serde(rename = "sh")]
+    Sh,
+    #[serde(rename = "wpkh")]
+    Wpkh,
+    #[serde(rename = "sh-wpkh")]
+    ShWpkh,
+}
+
+impl Default for ScriptType {
+    fn default() -> Self {
+        ScriptType::Wsh
+    }
+}
+
+impl ScriptType {
+    /// Wrap the inner script descriptor, eg. `multi(...)`, according to the script type,
+    /// for single signature types `inner` is the key
+    pub fn wrap(&self, inner: &str) -> String {
+        match self {
+            ScriptType::Wsh => format!("wsh({})", inner),
+            ScriptType::ShWsh => format!("sh(wsh({}))", inner),
+            ScriptType::Sh => format!("sh({})", inner),
+            ScriptType::Wpkh => format!("wpkh({})", inner),
+            ScriptType::ShWpkh => format!("sh(wpkh({}))", inner),
+        }
+    }
+
+    /// True for the single signature types `wpkh` and `sh-wpkh`
+    pub fn is_single_sig(&self) -> bool {
+        match self {
+            ScriptType::Wpkh | ScriptType::ShWpkh => true,
+            ScriptType::Wsh | ScriptType::ShWsh | ScriptType::Sh => false,
+        }
+    }
+}
+
+impl ToString for ScriptType {
+    fn to_string(&self) -> String {
+        match self {
+            ScriptType::Wsh => "wsh",
+            ScriptType::ShWsh => "sh-wsh",
+            ScriptType::Sh => "sh",
+            ScriptType::Wpkh => "wpkh",
+            ScriptType::ShWpkh => "sh-wpkh",
+        }
+        .to_string()
+    }
+}
+
+impl FromStr for ScriptType {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "wsh" => Ok(ScriptType::Wsh),
+            "sh-wsh" => Ok(ScriptType::ShWsh),
+            "sh" => Ok(ScriptType::Sh),
+            "wpkh" => Ok(ScriptType::Wpkh),
+            "sh-wpkh" => Ok(ScriptType::ShWpkh),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("({}) valid values are: wsh, sh-wsh, sh, wpkh, sh-wpkh", s),
+            )),
+        }
+    }
+}
+
+impl FromStr for ShareGroup {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parts: Vec<_> = s.split("of").collect();
+        if parts.len() != 2 {
+            return Err(crate::io_err("group is not in the format MofN (eg. 2of3)"));
+        }
+        let threshold = parts[0]
+            .parse::<u8>()
+            .map_err(|_| crate::io_err("group threshold is not a number"))?;
+        let count = parts[1]
+            .parse::<u8>()
+            .map_err(|_| crate::io_err("group count is not a number"))?;
+        Ok(ShareGroup { threshold, count })
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct WalletIndexes {
     pub main: u32,
@@ -93,6 +286,73 @@ pub struct GetAddressOutput {
     pub indexes: WalletIndexes,
 }
 
+/// Address derived offline from the wallet descriptor
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DerivedAddress {
+    pub address: Address,
+    pub index: u32,
+    pub change: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct VerifyAddressOutput {
+    pub wallet: String,
+    pub derived: DerivedAddress,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ShowAddressesOutput {
+    pub wallet: String,
+    pub addresses: Vec<DerivedAddress>,
+}
+
+/// Coordinator state of a BIP129 session, waiting for the key records of the signers
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BsmsSession {
+    pub token: String,
+    pub required_sig: usize,
+    pub signers: usize,
+    pub script_type: ScriptType,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BsmsSessionOutput {
+    pub session: BsmsSession,
+    pub session_file: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BsmsKeyRecordOutput {
+    pub key_record: String,
+    pub key_record_file: PathBuf,
+    pub qr_files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BsmsDescriptorOutput {
+    pub descriptor_record: String,
+    pub descriptor_record_file: PathBuf,
+    pub qr_files: Vec<PathBuf>,
+    pub wallet: WalletJson,
+    pub wallet_file: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BsmsVerifyOutput {
+    pub first_address: Address,
+    pub wallet: WalletJson,
+    pub wallet_file: PathBuf,
+    pub qr_files: Vec<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ExportWalletOutput {
+    pub format: WalletFormat,
+    pub content: String,
+    pub export_file: PathBuf,
+    pub qr_files: Vec<PathBuf>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct SendTxOutput {
     pub hex: String,
@@ -118,6 +378,8 @@ pub struct CreateWalletOutput {
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 pub struct ListOutput {
     pub keys: Vec<MasterKeyOutput>,
+    #[serde(default)]
+    pub encrypted_keys: Vec<PathBuf>,
     pub wallets: Vec<CreateWalletOutput>,
     pub psbts: Vec<PsbtJsonOutput>,
 }
@@ -133,9 +395,87 @@ pub struct Coin {
     pub amount: u64,
 }
 
+/// Rule of the spending policy not satisfied by a transaction
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "rule")]
+pub enum PolicyViolation {
+    MaxAmountPerTx {
+        amount: u64,
+        max: u64,
+    },
+    DailyLimit {
+        amount: u64,
+        spent_today: u64,
+        limit: u64,
+    },
+    DestinationNotAllowed {
+        output: usize,
+        address: String,
+    },
+    NoChangeToWallet,
+    ChangeNotToWallet {
+        output: usize,
+    },
+    MaxFeeRate {
+        rate: f64,
+        max: f64,
+    },
+    NonAllSighash {
+        input: usize,
+        sighash_type: String,
+    },
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyViolation::MaxAmountPerTx { amount, max } => write!(
+                f,
+                "amount {} sat is over the max of {} sat per transaction",
+                amount, max
+            ),
+            PolicyViolation::DailyLimit {
+                amount,
+                spent_today,
+                limit,
+            } => write!(
+                f,
+                "amount {} sat plus {} sat spent today is over the daily limit of {} sat",
+                amount, spent_today, limit
+            ),
+            PolicyViolation::DestinationNotAllowed { output, address } => write!(
+                f,
+                "output {} sends to {} which is not an allowed destination",
+                output, address
+            ),
+            PolicyViolation::NoChangeToWallet => write!(f, "no output pays back to the wallet"),
+            PolicyViolation::ChangeNotToWallet { output } => write!(
+                f,
+                "change output {} does not pay back to the wallet",
+                output
+            ),
+            PolicyViolation::MaxFeeRate { rate, max } => write!(
+                f,
+                "fee rate {:.2} sat/vbyte is over the max of {:.2} sat/vbyte",
+                rate, max
+            ),
+            PolicyViolation::NonAllSighash {
+                input,
+                sighash_type,
+            } => write!(
+                f,
+                "input {} requests sighash type {}, only ALL is signed with a spending policy",
+                input, sighash_type
+            ),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ErrorJson {
     pub error: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policy_violations: Vec<PolicyViolation>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -147,6 +487,15 @@ pub struct TxInOut {
     pub value: String,
     pub path: String,
     pub wallet: String,
+    /// Fingerprints of the keys with a valid signature of the input
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signed_by: Vec<String>,
+    /// Why the other signatures of the input are not valid
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invalid_signatures: Vec<String>,
+    /// Signatures still required by the wallet of the input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub missing_signatures: Option<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
@@ -203,6 +552,13 @@ impl Seed {
     }
 }
 
+impl WalletJson {
+    /// Single signature wallets have wpkh() or sh(wpkh()) descriptors
+    pub fn is_single_sig(&self) -> bool {
+        self.script_type.is_single_sig()
+    }
+}
+
 impl MasterKeyOutput {
     pub fn public_file_str(&self) -> Option<String> {
         self.public_file
@@ -228,11 +584,53 @@ impl PrivateMasterKey {
             xpub,
             seed,
             dice: None,
+            mnemonic: None,
             name: name.to_string(),
             fingerprint: xpub.fingerprint(),
+            created_at: None,
         })
     }
 
+    pub fn from_mnemonic(
+        network: Network,
+        words: &str,
+        passphrase: Option<&str>,
+        name: &str,
+    ) -> crate::Result<PrivateMasterKey> {
+        let mnemonic = bip39::Mnemonic::from_phrase(words, bip39::Language::English)
+            .map_err(|e| crate::Error::InvalidMnemonic(e.to_string()))?;
+        let seed = bip39::Seed::new(&mnemonic, passphrase.unwrap_or(""));
+        let secp = bitcoin::secp256k1::Secp256k1::signing_only();
+
+        let xprv = ExtendedPrivKey::new_master(network, seed.as_bytes())?;
+        let xpub = ExtendedPubKey::from_private(&secp, &xprv);
+
+        Ok(PrivateMasterKey {
+            xprv,
+            xpub,
+            seed: None,
+            dice: None,
+            mnemonic: Some(Mnemonic {
+                words: mnemonic.into_phrase(),
+                passphrase_protected: passphrase.map(|p| !p.is_empty()).unwrap_or(false),
+            }),
+            name: name.to_string(),
+            fingerprint: xpub.fingerprint(),
+            created_at: None,
+        })
+    }
+
+    pub fn from_entropy_mnemonic(
+        network: Network,
+        entropy: &[u8],
+        passphrase: Option<&str>,
+        name: &str,
+    ) -> crate::Result<PrivateMasterKey> {
+        let mnemonic = bip39::Mnemonic::from_entropy(entropy, bip39::Language::English)
+            .map_err(|e| crate::Error::InvalidMnemonic(e.to_string()))?;
+        PrivateMasterKey::from_mnemonic(network, mnemonic.phrase(), passphrase, name)
+    }
+
     pub fn from_xprv(xprv: ExtendedPrivKey, name: &str) -> Self {
         let secp = bitcoin::secp256k1::Secp256k1::signing_only();
         let xpub = ExtendedPubKey::from_private(&secp, &xprv);
@@ -241,8 +639,10 @@ impl PrivateMasterKey {
             xpub,
             seed: None,
             dice: None,
+            mnemonic: None,
             name: name.to_string(),
             fingerprint: xpub.fingerprint(),
+            created_at: None,
         }
     }
 }
@@ -274,6 +674,9 @@ macro_rules! impl_try_into {
     };
 }
 impl_try_into!(MasterKeyOutput);
+impl_try_into!(SplitKeyOutput);
+impl_try_into!(EncryptKeyOutput);
+impl_try_into!(ExportXpubOutput);
 impl_try_into!(PsbtPrettyPrint);
 impl_try_into!(CreateWalletOutput);
 impl_try_into!(CreateTxOutput);
@@ -281,4 +684,14 @@ impl_try_into!(SendTxOutput);
 impl_try_into!(BalanceOutput);
 impl_try_into!(ListCoinsOutput);
 impl_try_into!(GetAddressOutput);
+impl_try_into!(VerifyAddressOutput);
+impl_try_into!(ShowAddressesOutput);
+impl_try_into!(ExportWalletOutput);
+impl_try_into!(BsmsSessionOutput);
+impl_try_into!(BsmsKeyRecordOutput);
+impl_try_into!(BsmsDescriptorOutput);
+impl_try_into!(BsmsVerifyOutput);
 impl_try_into!(ListOutput);
+impl_try_into!(ImportQrOutput);
+impl_try_into!(BackupSheetOutput);
+impl_try_into!(FinalizeOutput);
@@ -0,0 +1,299 @@
+use crate::*;
+use bitcoin::util::base58;
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, ExtendedPubKey, Fingerprint};
+use bitcoin::Network;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// SLIP-132 version bytes of multisig p2wsh extended public keys (Zpub, Vpub)
+const ZPUB: [u8; 4] = [0x02, 0xaa, 0x7e, 0xd3];
+const VPUB: [u8; 4] = [0x02, 0x57, 0x54, 0x83];
+/// SLIP-132 version bytes of multisig p2sh-p2wsh extended public keys (Ypub, Upub)
+const YPUB: [u8; 4] = [0x02, 0x95, 0xb4, 0x3f];
+const UPUB: [u8; 4] = [0x02, 0x42, 0x89, 0xef];
+const XPUB: [u8; 4] = [0x04, 0x88, 0xb2, 0x1e];
+const TPUB: [u8; 4] = [0x04, 0x35, 0x87, 0xcf];
+
+/// Origin of an extended key as in output descriptors, eg. `[d34db33f/48'/1'/0'/2']`
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyOrigin {
+    pub fingerprint: Fingerprint,
+    pub path: DerivationPath,
+}
+
+/// Extended public key with optional key origin, eg. `[d34db33f/48'/1'/0'/2']tpub...`
+#[derive(Debug, Clone, PartialEq)]
+pub struct XpubWithOrigin {
+    pub origin: Option<KeyOrigin>,
+    pub xpub: ExtendedPubKey,
+}
+
+/// Derivation path of the BIP48 account key `m/48'/coin'/account'/script'`, where the script
+/// index is `1'` for p2sh-p2wsh and `2'` for p2wsh multisig
+pub fn bip48_path(
+    network: Network,
+    account: u32,
+    script_type: ScriptType,
+) -> Result<DerivationPath> {
+    let coin = match network {
+        Network::Bitcoin => 0,
+        Network::Testnet | Network::Regtest => 1,
+    };
+    let script = match script_type {
+        ScriptType::ShWsh => 1,
+        ScriptType::Wsh => 2,
+        ScriptType::Sh | ScriptType::Wpkh | ScriptType::ShWpkh => {
+            return Err(format!(
+                "BIP48 has no account keys for script type {}, only wsh and sh-wsh",
+                script_type.to_string()
+            )
+            .into())
+        }
+    };
+    let path: Vec<ChildNumber> = vec![
+        ChildNumber::from_hardened_idx(48)?,
+        ChildNumber::from_hardened_idx(coin)?,
+        ChildNumber::from_hardened_idx(account)?,
+        ChildNumber::from_hardened_idx(script)?,
+    ];
+    Ok(path.into())
+}
+
+impl XpubWithOrigin {
+    /// Fingerprint of the master key, which is the one used in PSBT key paths
+    pub fn fingerprint(&self) -> Fingerprint {
+        match &self.origin {
+            Some(origin) => origin.fingerprint,
+            None => self.xpub.fingerprint(),
+        }
+    }
+
+    /// Encode the xpub with SLIP-132 version bytes for multisig p2wsh (Zpub or Vpub)
+    pub fn to_slip132(&self) -> String {
+        self.to_slip132_for(ScriptType::Wsh)
+    }
+
+    /// Encode the xpub with SLIP-132 version bytes of the multisig script type,
+    /// legacy p2sh and the single signature types keep the xpub/tpub version bytes
+    pub fn to_slip132_for(&self, script_type: ScriptType) -> String {
+        let mut data = base58::from_check(&self.xpub.to_string()).expect("valid xpub");
+        let mainnet = self.xpub.network == Network::Bitcoin;
+        let version = match (script_type, mainnet) {
+            (ScriptType::Wsh, true) => ZPUB,
+            (ScriptType::Wsh, false) => VPUB,
+            (ScriptType::ShWsh, true) => YPUB,
+            (ScriptType::ShWsh, false) => UPUB,
+            (ScriptType::Sh, _) | (ScriptType::Wpkh, _) | (ScriptType::ShWpkh, _) => {
+                return self.xpub.to_string()
+            }
+        };
+        data[0..4].copy_from_slice(&version);
+        base58::check_encode_slice(&data)
+    }
+}
+
+/// Parse an extended public key, accepting also SLIP-132 multisig version bytes
+pub fn xpub_from_slip132(s: &str) -> Result<ExtendedPubKey> {
+    let mut data = base58::from_check(s)?;
+    if data.len() != 78 {
+        return Err("invalid extended public key length".into());
+    }
+    let version = match &data[0..4] {
+        v if v == ZPUB || v == YPUB => XPUB,
+        v if v == VPUB || v == UPUB => TPUB,
+        _ => return Ok(ExtendedPubKey::from_str(s)?),
+    };
+    data[0..4].copy_from_slice(&version);
+    Ok(ExtendedPubKey::from_str(&base58::check_encode_slice(
+        &data,
+    ))?)
+}
+
+/// Script type implied by SLIP-132 version bytes (Zpub/Vpub or Ypub/Upub), None for xpub/tpub
+pub fn slip132_script_type(s: &str) -> Result<Option<ScriptType>> {
+    let data = base58::from_check(s)?;
+    Ok(match data.get(0..4) {
+        Some(v) if v == ZPUB || v == VPUB => Some(ScriptType::Wsh),
+        Some(v) if v == YPUB || v == UPUB => Some(ScriptType::ShWsh),
+        _ => None,
+    })
+}
+
+impl fmt::Display for KeyOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = self.path.to_string(); // starts with "m"
+        write!(f, "[{}{}]", self.fingerprint, &path[1..])
+    }
+}
+
+impl FromStr for KeyOrigin {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if !s.starts_with('[') || !s.ends_with(']') {
+            return Err(io_err("key origin must be enclosed in square brackets"));
+        }
+        let inner = &s[1..s.len() - 1];
+        let mut parts = inner.splitn(2, '/');
+        let fingerprint = parts.next().unwrap_or("");
+        let fingerprint = Fingerprint::from_str(fingerprint)
+            .map_err(|_| io_err("invalid fingerprint in key origin"))?;
+        let path = match parts.next() {
+            Some(path) => format!("m/{}", path.replace("h", "'")),
+            None => "m".to_string(),
+        };
+        let path = DerivationPath::from_str(&path)
+            .map_err(|_| io_err("invalid derivation path in key origin"))?;
+        Ok(KeyOrigin { fingerprint, path })
+    }
+}
+
+impl fmt::Display for XpubWithOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.origin {
+            Some(origin) => write!(f, "{}{}", origin, self.xpub),
+            None => write!(f, "{}", self.xpub),
+        }
+    }
+}
+
+impl FromStr for XpubWithOrigin {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (origin, xpub) = match s.find(']') {
+            Some(end) => (Some(KeyOrigin::from_str(&s[..=end])?), &s[end + 1..]),
+            None => (None, s),
+        };
+        let xpub = xpub_from_slip132(xpub).map_err(|e| io_err(&e.to_string()))?;
+        if let Some(origin) = &origin {
+            if origin.path.as_ref().len() != xpub.depth as usize {
+                return Err(io_err("key origin path length does not match xpub depth"));
+            }
+        }
+        Ok(XpubWithOrigin { origin, xpub })
+    }
+}
+
+impl From<ExtendedPubKey> for XpubWithOrigin {
+    fn from(xpub: ExtendedPubKey) -> Self {
+        XpubWithOrigin { origin: None, xpub }
+    }
+}
+
+impl From<PublicMasterKey> for XpubWithOrigin {
+    fn from(key: PublicMasterKey) -> Self {
+        XpubWithOrigin {
+            origin: key.key_origin,
+            xpub: key.xpub,
+        }
+    }
+}
+
+impl Serialize for KeyOrigin {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyOrigin {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        KeyOrigin::from_str(&s).map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for XpubWithOrigin {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for XpubWithOrigin {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        XpubWithOrigin::from_str(&s).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{ACCOUNT_VPUB, ACCOUNT_XPUB};
+    use crate::common::key_origin::{
+        bip48_path, slip132_script_type, xpub_from_slip132, KeyOrigin, XpubWithOrigin,
+    };
+    use crate::ScriptType;
+    use bitcoin::util::bip32::DerivationPath;
+    use bitcoin::Network;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_xpub_with_origin() {
+        let key = XpubWithOrigin::from_str(ACCOUNT_XPUB).unwrap();
+        let origin = key.origin.as_ref().unwrap();
+        assert_eq!(origin.fingerprint.to_string(), "3442193e");
+        assert_eq!(
+            origin.path,
+            bip48_path(Network::Testnet, 0, ScriptType::Wsh).unwrap()
+        );
+        assert_eq!(key.fingerprint(), origin.fingerprint);
+        assert_eq!(key.to_string(), ACCOUNT_XPUB);
+
+        let alternative = ACCOUNT_XPUB.replace("'", "h");
+        assert_eq!(XpubWithOrigin::from_str(&alternative).unwrap(), key);
+
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(json, format!("\"{}\"", ACCOUNT_XPUB));
+        let back: XpubWithOrigin = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, key);
+
+        assert_eq!(key.to_slip132(), ACCOUNT_VPUB);
+        let upub = key.to_slip132_for(ScriptType::ShWsh);
+        assert!(upub.starts_with("Upub"));
+        assert_eq!(slip132_script_type(&upub).unwrap(), Some(ScriptType::ShWsh));
+        assert_eq!(xpub_from_slip132(&upub).unwrap(), key.xpub);
+        assert_eq!(key.to_slip132_for(ScriptType::Sh), key.xpub.to_string());
+        assert_eq!(
+            slip132_script_type(ACCOUNT_VPUB).unwrap(),
+            Some(ScriptType::Wsh)
+        );
+        let from_slip132 =
+            XpubWithOrigin::from_str(&format!("{}{}", origin, ACCOUNT_VPUB)).unwrap();
+        assert_eq!(from_slip132, key);
+
+        let bare = &ACCOUNT_XPUB[23..];
+        let key = XpubWithOrigin::from_str(bare).unwrap();
+        assert!(key.origin.is_none());
+        assert_eq!(slip132_script_type(bare).unwrap(), None);
+        assert_eq!(key.to_string(), bare);
+
+        let wrong_depth = format!("[3442193e/48'/1'/0']{}", bare);
+        assert!(XpubWithOrigin::from_str(&wrong_depth).is_err());
+        let wrong_fingerprint = format!("[3442193/48'/1'/0'/2']{}", bare);
+        assert!(XpubWithOrigin::from_str(&wrong_fingerprint).is_err());
+    }
+
+    #[test]
+    fn test_key_origin() {
+        let origin = KeyOrigin::from_str("[d34db33f]").unwrap();
+        assert_eq!(origin.path, DerivationPath::from_str("m").unwrap());
+        assert_eq!(origin.to_string(), "[d34db33f]");
+
+        assert_eq!(
+            bip48_path(Network::Bitcoin, 3, ScriptType::Wsh)
+                .unwrap()
+                .to_string(),
+            "m/48'/0'/3'/2'"
+        );
+        assert_eq!(
+            bip48_path(Network::Bitcoin, 3, ScriptType::ShWsh)
+                .unwrap()
+                .to_string(),
+            "m/48'/0'/3'/1'"
+        );
+        assert!(bip48_path(Network::Bitcoin, 3, ScriptType::Sh).is_err());
+    }
+}
@@ -1,4 +1,3 @@
-use crate::offline::sign::read_key;
 use crate::*;
 use bitcoin::Network;
 use log::{debug, warn};
@@ -11,6 +10,26 @@ pub struct ListOptions {
     /// list wallets, keys or psbts
     #[structopt(short, long)]
     pub kind: Kind,
+
+    /// Decrypt encrypted key files with a passphrase asked at the prompt
+    #[structopt(long)]
+    #[serde(default)]
+    pub decrypt: bool,
+
+    /// Passphrase to decrypt encrypted key files, the command line asks it with --decrypt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+}
+
+impl ListOptions {
+    pub fn new(kind: Kind) -> Self {
+        ListOptions {
+            kind,
+            decrypt: false,
+            encryption_passphrase: None,
+        }
+    }
 }
 
 pub fn list(datadir: &str, network: Network, opt: &ListOptions) -> Result<ListOutput> {
@@ -62,7 +81,8 @@ pub fn list(datadir: &str, network: Network, opt: &ListOptions) -> Result<ListOu
                 Kind::Key => {
                     path.push("PRIVATE.json");
                     debug!("try to read key {:?}", path);
-                    match read_key(&path) {
+                    let passphrase = opt.encryption_passphrase.as_ref().map(|s| s.as_str());
+                    match read_key_file(&path).and_then(|f| f.into_key(passphrase)) {
                         Ok(key) => {
                             let public_qr_files = read_qrs(&path)?;
                             let key = MasterKeyOutput {
@@ -73,6 +93,10 @@ pub fn list(datadir: &str, network: Network, opt: &ListOptions) -> Result<ListOu
                             };
                             list.keys.push(key);
                         }
+                        Err(Error::Encryption(e)) => {
+                            warn!("Can't decrypt key {:?}", e);
+                            list.encrypted_keys.push(path.clone());
+                        }
                         Err(e) => {
                             warn!("Can't read key {:?}", e);
                         }
@@ -85,7 +109,7 @@ pub fn list(datadir: &str, network: Network, opt: &ListOptions) -> Result<ListOu
     Ok(list)
 }
 
-fn read_qrs(path: &PathBuf) -> Result<Vec<PathBuf>> {
+pub(crate) fn read_qrs(path: &PathBuf) -> Result<Vec<PathBuf>> {
     let mut path = path.parent().expect("root has no parent").to_path_buf();
     path.push("qr");
     let mut vec = vec![];
@@ -116,8 +140,7 @@ mod tests {
         let _key = crate::offline::random::create_key(&temp_dir_str, Network::Testnet, &rand_opts)
             .unwrap();
 
-        let kind = Kind::Key;
-        let opt = ListOptions { kind };
+        let opt = ListOptions::new(Kind::Key);
         let result = list(&temp_dir_str, Network::Testnet, &opt);
         assert!(result.is_ok());
         let list = result.unwrap();
@@ -125,5 +148,20 @@ mod tests {
             .keys
             .iter()
             .any(|key| key.key.name == rand_opts.key_name));
+
+        let mut rand_opts = RandomOptions::new("list_encrypted".to_string());
+        rand_opts.encryption_passphrase = Some("passphrase".to_string());
+        let key = crate::offline::random::create_key(&temp_dir_str, Network::Testnet, &rand_opts)
+            .unwrap();
+
+        let list_result = crate::common::list::list(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert_eq!(list_result.keys.len(), 1);
+        assert_eq!(list_result.encrypted_keys, vec![key.private_file.clone()]);
+
+        let mut opt = ListOptions::new(Kind::Key);
+        opt.encryption_passphrase = Some("passphrase".to_string());
+        let list_result = crate::common::list::list(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert_eq!(list_result.keys.len(), 2);
+        assert!(list_result.encrypted_keys.is_empty());
     }
 }
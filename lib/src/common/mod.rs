@@ -1,20 +1,39 @@
 use crate::*;
 use bitcoin::blockdata::opcodes;
+use bitcoin::blockdata::script::Builder;
 use bitcoin::blockdata::script::Instruction::PushBytes;
 use bitcoin::consensus::deserialize;
+use bitcoin::hashes::{hash160, Hash};
 use bitcoin::util::key;
 use bitcoin::Script;
 use log::{LevelFilter, Metadata, Record};
+use miniscript::{Miniscript, Segwitv0};
 use std::fs::OpenOptions;
 use std::io::BufWriter;
 use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+pub mod bsms;
+pub mod cbor;
 pub mod cmd;
+pub mod coordinator;
+pub mod descriptor;
+pub mod document;
+pub mod encryption;
 pub mod error;
 pub mod file;
+#[cfg(test)]
+pub mod fixtures;
+pub mod fountain;
 pub mod json;
+pub mod key_origin;
 pub mod list;
+pub mod policy;
 pub mod qr;
+pub mod qr_reader;
+pub mod signatures;
+pub mod transfer;
+pub mod ur;
 
 static LOGGER: SimpleLogger = SimpleLogger;
 
@@ -50,47 +69,90 @@ pub fn init_logger() {
 
 impl From<PrivateMasterKey> for PublicMasterKey {
     fn from(private: PrivateMasterKey) -> Self {
-        PublicMasterKey { xpub: private.xpub }
+        PublicMasterKey {
+            xpub: private.xpub,
+            key_origin: None,
+        }
     }
 }
 
+/// Seconds since epoch
+pub fn now() -> Result<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| Error::Generic(e.to_string()))?
+        .as_secs())
+}
+
 pub fn psbt_from_base64(s: &str) -> Result<(Vec<u8>, PSBT)> {
     let bytes = base64::decode(s)?;
     let psbt = deserialize(&bytes)?;
     Ok((bytes, psbt))
 }
 
+/// Signature and public key pushed in the witness of p2wpkh inputs
+const P2WPKH_WITNESS_SIZE: usize = 72 + 34;
+
 pub fn estimate_weight(psbt: &PSBT) -> Result<usize> {
     let unsigned_weight = psbt.global.unsigned_tx.get_weight();
     let mut spending_weight = 0usize;
 
     for input in psbt.inputs.iter() {
+        let is_p2wpkh = input
+            .witness_utxo
+            .as_ref()
+            .map(|o| o.script_pubkey.is_v0_p2wpkh())
+            .unwrap_or(false);
         let (script, factor) = match (&input.redeem_script, &input.witness_script) {
+            (Some(redeem_script), None) if redeem_script.is_v0_p2wpkh() => {
+                spending_weight += redeem_script.len() * 4 + P2WPKH_WITNESS_SIZE;
+                continue;
+            }
+            (None, None) if is_p2wpkh => {
+                spending_weight += P2WPKH_WITNESS_SIZE;
+                continue;
+            }
             (Some(redeem_script), None) => (redeem_script, 4),
             (_, Some(witness_script)) => (witness_script, 1), // factor=1 for segwit discount
             _ => return Err("both redeem and witness script are None".into()),
         };
-        //TODO signature are less in NofM where N<M
-        let current = script.len() + expected_signatures(script)? * 72; // using 72 as average signature size
+        let current = script.len() + satisfaction_size(script)?;
         spending_weight += current * factor;
+        if let (Some(redeem_script), Some(_)) = (&input.redeem_script, &input.witness_script) {
+            spending_weight += redeem_script.len() * 4; // nested segwit, redeem script in script_sig
+        }
     }
 
     Ok(unsigned_weight + spending_weight)
 }
 
-fn expected_signatures(script: &Script) -> Result<usize> {
+/// Size of the data satisfying the script, max satisfaction size if the script is a miniscript
+fn satisfaction_size(script: &Script) -> Result<usize> {
+    if !is_multisig(script) {
+        if let Ok(ms) = Miniscript::<key::PublicKey, Segwitv0>::parse(script) {
+            return Ok(ms.max_satisfaction_size(2));
+        }
+    }
+    //TODO signature are less in NofM where N<M
+    Ok(expected_signatures(script)? * 72) // using 72 as average signature size
+}
+
+fn is_multisig(script: &Script) -> bool {
+    script.as_bytes().last() == Some(&opcodes::all::OP_CHECKMULTISIG.into_u8())
+}
+
+pub(crate) fn expected_signatures(script: &Script) -> Result<usize> {
     let bytes = script.as_bytes();
-    Ok(
-        if bytes.last().ok_or_else(fn_err("script empty"))?
-            == &opcodes::all::OP_CHECKMULTISIG.into_u8()
-        {
-            read_pushnum(bytes[0])
-                .map(|el| el as usize)
-                .unwrap_or(0usize)
-        } else {
-            extract_pub_keys(script)?.len()
-        },
-    )
+    if bytes.is_empty() {
+        return Err("script empty".into());
+    }
+    Ok(if is_multisig(script) {
+        read_pushnum(bytes[0])
+            .map(|el| el as usize)
+            .unwrap_or(0usize)
+    } else {
+        extract_pub_keys(script)?.len()
+    })
 }
 
 fn read_pushnum(value: u8) -> Option<u8> {
@@ -103,6 +165,23 @@ fn read_pushnum(value: u8) -> Option<u8> {
     }
 }
 
+/// Multisig script requiring `required` signatures, keys are sorted as in BIP67 if `sorted`
+pub fn multisig_script(required: usize, keys: &[key::PublicKey], sorted: bool) -> Script {
+    let mut keys = keys.to_vec();
+    if sorted {
+        keys.sort_by_key(|k| k.to_bytes());
+    }
+    let mut builder = Builder::new().push_int(required as i64);
+    for key in keys.iter() {
+        builder = builder.push_key(key);
+    }
+    builder
+        .push_int(keys.len() as i64)
+        .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+        .into_script()
+}
+
+/// Public keys in the order they appear in the script, for `sortedmulti` this is BIP67 order
 pub fn extract_pub_keys(script: &Script) -> Result<Vec<key::PublicKey>> {
     let mut result = vec![];
     for instruct in script.iter(false) {
@@ -115,6 +194,19 @@ pub fn extract_pub_keys(script: &Script) -> Result<Vec<key::PublicKey>> {
     Ok(result)
 }
 
+/// Public key hashes pushed in the script, as used by miniscript `pk_h` fragments
+pub fn extract_pub_key_hashes(script: &Script) -> Vec<hash160::Hash> {
+    let mut result = vec![];
+    for instruct in script.iter(false) {
+        if let PushBytes(a) = instruct {
+            if a.len() == 20 {
+                result.push(hash160::Hash::from_slice(&a).expect("20 bytes"));
+            }
+        }
+    }
+    result
+}
+
 pub fn unwrap_as_json(result: Result<serde_json::Value>) -> serde_json::Value {
     result.unwrap_or_else(|e| e.to_json())
 }
@@ -0,0 +1,137 @@
+use crate::common::key_origin::XpubWithOrigin;
+use crate::*;
+use miniscript::policy::Concrete;
+use miniscript::Segwitv0;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+/// Descriptors (main and change) generated by a spending policy compiled to miniscript
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyDescriptors {
+    pub descriptor_main: String,
+    pub descriptor_change: String,
+}
+
+/// Compile a policy like `or(thresh(2,pk(A),pk(B),pk(C)),and(pk(D),older(26000)))` where keys
+/// are referenced by the fingerprint of their master key, eg. `pk(d34db33f)`
+pub fn compile(policy: &str, xpubs: &[XpubWithOrigin]) -> Result<PolicyDescriptors> {
+    let concrete = Concrete::<String>::from_str(policy)
+        .map_err(|e| Error::Generic(format!("invalid policy: {}", e)))?;
+
+    let mut fingerprints = HashMap::new();
+    for xpub in xpubs {
+        let fingerprint = xpub.fingerprint().to_string();
+        if fingerprints.insert(fingerprint.clone(), xpub).is_some() {
+            return Err(format!("fingerprint {} used by more than one xpub", fingerprint).into());
+        }
+    }
+    let mut policy_keys = HashSet::new();
+    collect_keys(&concrete, &mut policy_keys);
+    for key in policy_keys.iter() {
+        if !fingerprints.contains_key(key) {
+            return Err(format!("policy key {} does not match any xpub fingerprint", key).into());
+        }
+    }
+    for fingerprint in fingerprints.keys() {
+        if !policy_keys.contains(fingerprint) {
+            return Err(format!("xpub with fingerprint {} not used in policy", fingerprint).into());
+        }
+    }
+
+    let mut descriptors = vec![];
+    for i in 0..=1 {
+        // keys are translated in the policy, so that only keys and never numbers like
+        // `older(N)` are replaced, miniscript compiled from string keys uses them also in `pkh`
+        let translated = concrete.translate_pk(|fingerprint: &String| -> Result<String> {
+            let xpub = fingerprints
+                .get(fingerprint)
+                .ok_or_else(fn_err("policy key without xpub"))?;
+            Ok(format!("{}/{}/*", xpub, i))
+        })?;
+        let miniscript = translated
+            .compile::<Segwitv0>()
+            .map_err(|e| Error::Generic(format!("cannot compile policy: {}", e)))?;
+        descriptors.push(format!("wsh({})", miniscript));
+    }
+
+    Ok(PolicyDescriptors {
+        descriptor_main: descriptors[0].clone(),
+        descriptor_change: descriptors[1].clone(),
+    })
+}
+
+fn collect_keys(policy: &Concrete<String>, keys: &mut HashSet<String>) {
+    match policy {
+        Concrete::Key(key) => {
+            keys.insert(key.clone());
+        }
+        Concrete::And(subs) | Concrete::Threshold(_, subs) => {
+            subs.iter().for_each(|p| collect_keys(p, keys))
+        }
+        Concrete::Or(subs) => subs.iter().for_each(|(_, p)| collect_keys(p, keys)),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::key_origin::XpubWithOrigin;
+    use crate::common::policy::compile;
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey};
+    use bitcoin::Network;
+
+    fn xpubs(n: u8) -> Vec<XpubWithOrigin> {
+        let secp = Secp256k1::signing_only();
+        (0..n)
+            .map(|i| {
+                let xprv = ExtendedPrivKey::new_master(Network::Testnet, &[i; 32]).unwrap();
+                ExtendedPubKey::from_private(&secp, &xprv).into()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compile_policy() {
+        let xpubs = xpubs(4);
+        let f: Vec<String> = xpubs.iter().map(|x| x.fingerprint().to_string()).collect();
+        let policy = format!(
+            "or(99@thresh(2,pk({}),pk({}),pk({})),and(pk({}),older(26000)))",
+            f[0], f[1], f[2], f[3]
+        );
+        let result = compile(&policy, &xpubs).unwrap();
+        assert!(result.descriptor_main.starts_with("wsh("));
+        assert_eq!(
+            result.descriptor_main.replace("/0/*", "/1/*"),
+            result.descriptor_change
+        );
+        assert!(result.descriptor_main.contains("older(26000)"));
+        for xpub in xpubs.iter() {
+            assert!(result.descriptor_main.contains(&format!("{}/0/*", xpub)));
+            assert!(result.descriptor_change.contains(&format!("{}/1/*", xpub)));
+            assert!(!result
+                .descriptor_main
+                .contains(&format!("({})", xpub.fingerprint())));
+        }
+
+        let policy = format!("thresh(2,pk({}),pk({}),pk({}))", f[0], f[1], f[2]);
+        let result = compile(&policy, &xpubs);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("not used in policy"));
+
+        let policy = format!("and(pk({}),pk(d34db33f))", f[0]);
+        let result = compile(&policy, &xpubs[..1]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "policy key d34db33f does not match any xpub fingerprint"
+        );
+
+        let result = compile("thresh(2,pk(", &xpubs);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid policy"));
+    }
+}
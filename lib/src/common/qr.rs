@@ -1,14 +1,64 @@
-use crate::{CreateQrOptions, Result};
+use crate::common::document;
+use crate::common::transfer;
+use crate::common::ur::{self, UrEncoder};
+use crate::{io_err, psbt_from_base64, CreateQrOptions, PsbtJson, Result};
 use image::Luma;
 use log::info;
 use qrcode::bits::{Bits, ExtendedMode};
 use qrcode::types::Color::{Dark, Light};
 use qrcode::{bits, EcLevel, QrCode, Version};
+use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::fs;
 use std::fs::File;
+use std::io;
 use std::io::Write;
 use std::path::PathBuf;
+use std::str::FromStr;
+
+/// How data bigger than a single QR code is split in more QR codes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QrEncoding {
+    /// QR structured append, max 16 codes and all of them must be scanned
+    StructuredAppend,
+    /// BC-UR fountain coded parts to be shown as an animated QR, scanned in any order
+    Ur,
+}
+
+impl Default for QrEncoding {
+    fn default() -> Self {
+        QrEncoding::StructuredAppend
+    }
+}
+
+impl ToString for QrEncoding {
+    fn to_string(&self) -> String {
+        match self {
+            QrEncoding::StructuredAppend => "structured-append",
+            QrEncoding::Ur => "ur",
+        }
+        .to_string()
+    }
+}
+
+impl FromStr for QrEncoding {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "structured-append" => Ok(QrEncoding::StructuredAppend),
+            "ur" => Ok(QrEncoding::Ur),
+            _ => Err(io_err(&format!(
+                "({}) valid values are: structured-append, ur",
+                s
+            ))),
+        }
+    }
+}
+
+/// Bytes of a UR part besides the fragment: part cbor header and bytewords checksum
+const UR_PART_OVERHEAD: usize = 26;
 
 pub fn print_qr(qr_code: &QrCode, inverted: bool) -> Result<String> {
     let mut result = String::new();
@@ -58,32 +108,150 @@ pub fn create_qrs(opt: &CreateQrOptions) -> Result<Vec<PathBuf>> {
     save_qrs(bytes, path, opt.qr_version)
 }
 
-pub fn save_qrs(bytes: Vec<u8>, mut path: PathBuf, version: i16) -> Result<Vec<PathBuf>> {
+pub fn save_qrs(bytes: Vec<u8>, path: PathBuf, version: i16) -> Result<Vec<PathBuf>> {
     match version {
         0 => return Ok(vec![]),
         5..=20 => info!("save_qrs data len:{} version:{}", bytes.len(), version),
         _ => return Err(format!("invalid qr version {}", version).into()),
     }
 
-    let mut wallet_qr_files = vec![];
-
     let qrs = SplittedQr::new(bytes, version)?.split()?;
     info!("splitted qr in {} pieces", qrs.len());
+    save_qr_codes(&qrs, path)
+}
+
+/// Save the QR codes as `save_qrs` and a printable document of them with the title and summary
+pub fn save_qrs_with_document(
+    bytes: Vec<u8>,
+    path: PathBuf,
+    version: i16,
+    title: &str,
+    summary: &[String],
+) -> Result<Vec<PathBuf>> {
+    let qr_files = save_qrs(bytes.clone(), path.clone(), version)?;
+    if !qr_files.is_empty() {
+        let splitted = SplittedQr::new(bytes, version)?;
+        document::save_qr_document(&splitted, title, summary, path)?;
+    }
+    Ok(qr_files)
+}
+
+/// Save the cbor payload as UR QR codes, when more than one they are the frames of an animated QR,
+/// twice the number of fragments are saved so that a reader can miss some of them
+pub fn save_ur_qrs(
+    ur_type: &str,
+    cbor: &[u8],
+    path: PathBuf,
+    version: i16,
+) -> Result<Vec<PathBuf>> {
+    match version {
+        0 => return Ok(vec![]),
+        5..=20 => info!("save_ur_qrs data len:{} version:{}", cbor.len(), version),
+        _ => return Err(format!("invalid qr version {}", version).into()),
+    }
+
+    let (parts, qrs) = ur_qr_codes(ur_type, cbor, version)?;
+    let qr_files = save_qr_codes(&qrs, path.clone())?;
+
+    let mut ur_path = path;
+    ur_path.set_file_name("ur.txt");
+    info!("Saving ur parts in {:?}", &ur_path);
+    fs::write(&ur_path, parts.join("\n"))?;
+    Ok(qr_files)
+}
 
+/// UR parts, uppercase to use the QR alphanumeric mode (5.5 bits per char instead of 8),
+/// and their QR codes not bigger than `version`
+fn ur_qr_codes(ur_type: &str, cbor: &[u8], version: i16) -> Result<(Vec<String>, Vec<QrCode>)> {
+    let max_chars = MAX_BYTES[version as usize] * 16 / 11;
+    let prefix_len = format!("ur:{}/{}-{}/", ur_type, u16::max_value(), u16::max_value()).len();
+    let max_fragment_len = (max_chars - prefix_len) / 2 - UR_PART_OVERHEAD;
+    let mut encoder = UrEncoder::new(ur_type, cbor, max_fragment_len)?;
+    let frames = if encoder.is_single_part() {
+        1
+    } else {
+        encoder.seq_len() * 2
+    };
+    info!("ur in {} fragments, {} frames", encoder.seq_len(), frames);
+
+    let parts: Vec<String> = (0..frames)
+        .map(|_| encoder.next_part().to_uppercase())
+        .collect();
+    let qrs = parts
+        .iter()
+        .map(|part| Ok(QrCode::with_error_correction_level(part.as_bytes(), LEVEL)?))
+        .collect::<Result<Vec<_>>>()?;
+    Ok((parts, qrs))
+}
+
+/// Save the PSBT QR codes, the compact transfer encoding as structured append or the raw PSBT
+/// as `ur:crypto-psbt`, `strip` removes redundant `non_witness_utxo` from the compact encoding
+pub fn save_psbt_qrs(
+    psbt: &PsbtJson,
+    path: PathBuf,
+    version: i16,
+    encoding: QrEncoding,
+    strip: bool,
+) -> Result<Vec<PathBuf>> {
+    match encoding {
+        QrEncoding::StructuredAppend => save_qrs_with_document(
+            transfer::encode_psbt(psbt, strip)?,
+            path,
+            version,
+            &format!("PSBT {}", psbt.name),
+            &document::psbt_summary(psbt)?,
+        ),
+        QrEncoding::Ur => {
+            let (psbt_bytes, _) = psbt_from_base64(&psbt.psbt)?;
+            save_ur_qrs(
+                ur::CRYPTO_PSBT,
+                &ur::psbt_to_cbor(&psbt_bytes),
+                path,
+                version,
+            )
+        }
+    }
+}
+
+/// QR code as SVG, a square for every dark module and a quiet zone of 4 modules
+pub fn qr_svg(qr: &QrCode) -> String {
+    let width = qr.width();
+    let size = width + 8;
+    let mut path = String::new();
+    for (i, color) in qr.clone().into_colors().iter().enumerate() {
+        if *color == Dark {
+            path.push_str(&format!("M{},{}h1v1h-1z", i % width + 4, i / width + 4));
+        }
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {size} {size}\" width=\"{pixels}\" height=\"{pixels}\" shape-rendering=\"crispEdges\">\n<rect width=\"{size}\" height=\"{size}\" fill=\"#fff\"/>\n<path d=\"{path}\" fill=\"#000\"/>\n</svg>\n",
+        size = size,
+        pixels = size * 8,
+        path = path
+    )
+}
+
+/// Save the QR codes as png and svg in the directory of `path` and all of them as text in
+/// `qrs.txt`, the png files are returned
+fn save_qr_codes(qrs: &[QrCode], mut path: PathBuf) -> Result<Vec<PathBuf>> {
+    let mut qr_files = vec![];
     let mut text_qr = vec![String::new(); 2];
     let single = qrs.len() == 1;
 
     for (i, qr) in qrs.iter().enumerate() {
-        if single {
-            path.set_file_name("qr.png");
+        let name = if single {
+            "qr".to_string()
         } else {
-            path.set_file_name(&format!("qr-{}.png", i));
-        }
+            format!("qr-{}", i)
+        };
+        path.set_file_name(&format!("{}.svg", name));
+        fs::write(&path, qr_svg(qr))?;
+        path.set_file_name(&format!("{}.png", name));
         info!("rendering qr");
         let image = qr.render::<Luma<u8>>().build();
         info!("Saving qr in {:?}", &path);
         image.save(&path)?;
-        wallet_qr_files.push(path.clone());
+        qr_files.push(path.clone());
 
         for b in &[true, false] {
             let qr_txt = print_qr(&qr, *b)?;
@@ -95,10 +263,10 @@ pub fn save_qrs(bytes: Vec<u8>, mut path: PathBuf, version: i16) -> Result<Vec<P
     let mut qr_txt_file = File::create(&path)?;
     qr_txt_file.write_all(text_qr[0].as_bytes())?;
     qr_txt_file.write_all(text_qr[1].as_bytes())?;
-    Ok(wallet_qr_files)
+    Ok(qr_files)
 }
 
-// at the moment used only in test
+/// Merge the raw data of structured append QR codes, as read by `qr_reader` or by a camera
 pub fn merge_qrs(mut bytes: Vec<Vec<u8>>) -> Result<Vec<u8>> {
     use std::collections::HashSet;
     use std::convert::TryInto;
@@ -295,9 +463,11 @@ const MAX_BYTES: [usize; 33] = [
 
 #[cfg(test)]
 mod tests {
-    use crate::common::qr::{merge_qrs, SplittedQr, StructuredQr, LEVEL};
+    use crate::common::qr::{merge_qrs, qr_svg, ur_qr_codes, SplittedQr, StructuredQr, LEVEL};
+    use crate::common::ur::{UrDecoder, CRYPTO_PSBT};
     use qrcode::bits::{Bits, ExtendedMode};
-    use qrcode::Version;
+    use qrcode::types::Color::Dark;
+    use qrcode::{QrCode, Version};
     use rand::Rng;
     use std::convert::TryInto;
 
@@ -427,4 +597,38 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_ur_qr_codes() {
+        let data: Vec<u8> = (0..2000).map(|_| rand::random::<u8>()).collect();
+        for ver in &[5, 14, 20] {
+            let ver = *ver;
+            let (parts, qrs) = ur_qr_codes(CRYPTO_PSBT, &data, ver).unwrap();
+            assert!(parts.len() > 1);
+            for qr in qrs.iter() {
+                match qr.version() {
+                    Version::Normal(v) => assert!(v <= ver),
+                    Version::Micro(_) => panic!("unexpected micro qr"),
+                }
+            }
+            let mut decoder = UrDecoder::default();
+            for part in parts.iter().rev() {
+                decoder.receive(part).unwrap();
+            }
+            assert_eq!(decoder.result().unwrap(), (CRYPTO_PSBT, data.as_slice()));
+        }
+        // more than 16 structured append qr codes are needed
+        assert!(SplittedQr::new(data.clone(), 5).is_err());
+        let (parts, _) = ur_qr_codes(CRYPTO_PSBT, &data[..100], 20).unwrap();
+        assert_eq!(parts.len(), 1);
+    }
+
+    #[test]
+    fn test_qr_svg() {
+        let qr = QrCode::new(b"firma").unwrap();
+        let svg = qr_svg(&qr);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 29 29\""));
+        let dark = qr.into_colors().iter().filter(|c| **c == Dark).count();
+        assert_eq!(svg.matches('M').count(), dark);
+    }
 }
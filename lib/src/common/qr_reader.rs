@@ -0,0 +1,636 @@
+use crate::*;
+use std::path::Path;
+
+/// Error correction codewords of every block, indexed by error correction level (L, M, Q, H)
+/// and by version
+const EC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [
+        0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28,
+        30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28,
+        28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+    ],
+    [
+        0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30,
+        30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+    [
+        0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24,
+        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
+    ],
+];
+
+/// Number of error correction blocks, indexed by error correction level (L, M, Q, H) and by version
+const EC_BLOCKS: [[u8; 41]; 4] = [
+    [
+        0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13,
+        14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25,
+    ],
+    [
+        0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21,
+        23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+    ],
+    [
+        0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29,
+        34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68,
+    ],
+    [
+        0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32,
+        35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81,
+    ],
+];
+
+/// Error correction level (index of the tables above) of the two bits in the format information
+const FORMAT_LEVELS: [usize; 4] = [1, 0, 3, 2];
+
+const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// Data codewords of a QR code, error corrected and without the error correction codewords
+#[derive(Debug, Clone, PartialEq)]
+pub struct QrData {
+    pub version: usize,
+    pub codewords: Vec<u8>,
+}
+
+/// Read the QR code in the image file, the code must be aligned to the image axes without
+/// other dark elements around, as the ones saved by firma
+pub fn read_qr(path: &Path) -> Result<QrData> {
+    let image = image::open(path)?.to_luma();
+    let (width, height) = image.dimensions();
+    read_qr_luma(width as usize, height as usize, &image.into_raw())
+}
+
+/// Read the QR code in the grayscale pixels, in rows of `width` pixels
+pub fn read_qr_luma(width: usize, height: usize, pixels: &[u8]) -> Result<QrData> {
+    let modules = sample_modules(width, height, pixels)?;
+    decode_modules(&modules)
+}
+
+impl QrData {
+    /// Data codewords read by another decoder (eg. a camera app), the version is the one having
+    /// the same number of data codewords at level L, as the QR codes saved by firma
+    pub fn from_codewords(codewords: Vec<u8>) -> Result<QrData> {
+        let version = (1..=40)
+            .find(|version| data_codewords(*version, 0) == codewords.len())
+            .ok_or_else(fn_err("data codewords don't match any qr version"))?;
+        Ok(QrData { version, codewords })
+    }
+
+    /// True for a part of a structured append sequence, to be merged with `qr::merge_qrs`
+    pub fn is_structured_append(&self) -> bool {
+        self.codewords.first().map(|b| b >> 4) == Some(3)
+    }
+
+    /// Content of the QR code, the concatenation of its numeric, alphanumeric, byte and kanji
+    /// segments
+    pub fn content(&self) -> Result<Vec<u8>> {
+        let class = match self.version {
+            1..=9 => 0,
+            10..=26 => 1,
+            _ => 2,
+        };
+        let mut reader = BitReader {
+            data: &self.codewords,
+            pos: 0,
+        };
+        let mut content = vec![];
+        // less than 4 bits remaining is a truncated terminator
+        while let Ok(mode) = reader.read(4) {
+            match mode {
+                0 => break,
+                1 => {
+                    let mut count = reader.read([10, 12, 14][class])?;
+                    while count > 0 {
+                        let digits = count.min(3);
+                        let value = reader.read([0, 4, 7, 10][digits])?;
+                        if value >= 10usize.pow(digits as u32) {
+                            return Err("invalid numeric qr segment".into());
+                        }
+                        content.extend(format!("{:0width$}", value, width = digits).bytes());
+                        count -= digits;
+                    }
+                }
+                2 => {
+                    let mut count = reader.read([9, 11, 13][class])?;
+                    while count > 0 {
+                        let chars = count.min(2);
+                        let value = reader.read([0, 6, 11][chars])?;
+                        let (first, second) = (value / 45, value % 45);
+                        if chars == 2 {
+                            content.push(*ALPHANUMERIC.get(first).ok_or_else(invalid_alpha)?);
+                        }
+                        content.push(*ALPHANUMERIC.get(second).ok_or_else(invalid_alpha)?);
+                        count -= chars;
+                    }
+                }
+                4 => {
+                    let count = reader.read([8, 16, 16][class])?;
+                    for _ in 0..count {
+                        content.push(reader.read(8)? as u8);
+                    }
+                }
+                8 => {
+                    // kanji are encoded as 13 bits values of their two bytes of Shift JIS
+                    let count = reader.read([8, 10, 12][class])?;
+                    for _ in 0..count {
+                        let value = reader.read(13)?;
+                        let compacted = ((value / 0xc0) << 8) | (value % 0xc0);
+                        let sjis = if compacted + 0x8140 <= 0x9ffc {
+                            compacted + 0x8140
+                        } else {
+                            compacted + 0xc140
+                        };
+                        content.extend_from_slice(&[(sjis >> 8) as u8, sjis as u8]);
+                    }
+                }
+                7 => {
+                    // ECI designator, content is returned as is
+                    let first = reader.read(8)?;
+                    if first & 0x80 != 0 {
+                        reader.read(if first & 0x40 == 0 { 8 } else { 16 })?;
+                    }
+                }
+                3 => return Err("structured append qr code, merge it with the other parts".into()),
+                _ => return Err(format!("unsupported qr segment mode {}", mode).into()),
+            }
+        }
+        Ok(content)
+    }
+}
+
+fn invalid_alpha() -> Error {
+    "invalid alphanumeric qr segment".into()
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn read(&mut self, bits: usize) -> Result<usize> {
+        if self.pos + bits > self.data.len() * 8 {
+            return Err("truncated qr data".into());
+        }
+        let mut value = 0usize;
+        for _ in 0..bits {
+            let bit = (self.data[self.pos >> 3] >> (7 - (self.pos & 7))) & 1;
+            value = (value << 1) | bit as usize;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+}
+
+/// Square matrix of modules, true when dark
+struct Modules {
+    size: usize,
+    dark: Vec<bool>,
+}
+
+impl Modules {
+    fn new(size: usize) -> Self {
+        Modules {
+            size,
+            dark: vec![false; size * size],
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.dark[y * self.size + x]
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        for j in y..y + height {
+            for i in x..x + width {
+                self.dark[j * self.size + i] = true;
+            }
+        }
+    }
+}
+
+/// Find the code bounds as the bounding box of the dark pixels and the module size from the top
+/// row of the top left finder pattern, which is 7 modules wide, then sample the modules centers
+fn sample_modules(width: usize, height: usize, pixels: &[u8]) -> Result<Modules> {
+    if width == 0 || height == 0 || pixels.len() != width * height {
+        return Err("invalid image size".into());
+    }
+    let min = u16::from(*pixels.iter().min().expect("not empty"));
+    let max = u16::from(*pixels.iter().max().expect("not empty"));
+    if max - min < 64 {
+        return Err("no qr code found in the image".into());
+    }
+    let threshold = (min + max) / 2;
+    let dark = |x: usize, y: usize| u16::from(pixels[y * width + x]) < threshold;
+
+    let top = (0..height)
+        .find(|y| (0..width).any(|x| dark(x, *y)))
+        .expect("there are dark pixels");
+    let bottom = (0..height)
+        .rev()
+        .find(|y| (0..width).any(|x| dark(x, *y)))
+        .expect("there are dark pixels");
+    let left = (0..width)
+        .find(|x| (0..height).any(|y| dark(*x, y)))
+        .expect("there are dark pixels");
+    let right = (0..width)
+        .rev()
+        .find(|x| (0..height).any(|y| dark(*x, y)))
+        .expect("there are dark pixels");
+
+    let finder_width = (left..=right).take_while(|x| dark(*x, top)).count();
+    let (code_width, code_height) = ((right - left + 1) as f64, (bottom - top + 1) as f64);
+    let version = ((code_width * 7.0 / finder_width as f64 - 17.0) / 4.0).round();
+    if !(1.0..=40.0).contains(&version) || (code_width - code_height).abs() > code_width / 10.0 {
+        return Err("no qr code found in the image".into());
+    }
+    let size = version as usize * 4 + 17;
+
+    let mut modules = Modules::new(size);
+    for y in 0..size {
+        for x in 0..size {
+            let pixel_x = left + ((x as f64 + 0.5) * code_width / size as f64) as usize;
+            let pixel_y = top + ((y as f64 + 0.5) * code_height / size as f64) as usize;
+            modules.dark[y * size + x] = dark(pixel_x, pixel_y);
+        }
+    }
+    Ok(modules)
+}
+
+fn decode_modules(modules: &Modules) -> Result<QrData> {
+    let version = (modules.size - 17) / 4;
+    let (level, mask) = read_format(modules)?;
+    let function = function_modules(version);
+    let raw = read_codewords(modules, &function, mask);
+    let codewords = correct_blocks(&raw, version, level)?;
+    Ok(QrData { version, codewords })
+}
+
+/// Error correction level and mask from the closest valid format information of the two copies
+fn read_format(modules: &Modules) -> Result<(usize, u8)> {
+    let size = modules.size;
+    let (mut first, mut second) = (0u32, 0u32);
+    for i in 0..15 {
+        let (x1, y1) = match i {
+            0..=5 => (8, i),
+            6 => (8, 7),
+            7 => (8, 8),
+            8 => (7, 8),
+            _ => (14 - i, 8),
+        };
+        let (x2, y2) = if i < 8 {
+            (size - 1 - i, 8)
+        } else {
+            (8, size - 15 + i)
+        };
+        first |= (modules.get(x1, y1) as u32) << i;
+        second |= (modules.get(x2, y2) as u32) << i;
+    }
+
+    (0..32u32)
+        .map(|data| {
+            let bits = format_bits(data);
+            let distance = (bits ^ first)
+                .count_ones()
+                .min((bits ^ second).count_ones());
+            (distance, data)
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .filter(|(distance, _)| *distance <= 3)
+        .map(|(_, data)| (FORMAT_LEVELS[(data >> 3) as usize], (data & 7) as u8))
+        .ok_or_else(fn_err("cannot read the qr format information"))
+}
+
+/// BCH coded and masked format information of the 5 bits of level and mask
+fn format_bits(data: u32) -> u32 {
+    let mut rem = data;
+    for _ in 0..10 {
+        rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+    }
+    ((data << 10) | rem) ^ 0x5412
+}
+
+/// Modules of finder, timing and alignment patterns, format and version information
+fn function_modules(version: usize) -> Modules {
+    let size = version * 4 + 17;
+    let mut function = Modules::new(size);
+    function.fill(0, 0, 9, 9);
+    function.fill(size - 8, 0, 8, 9);
+    function.fill(0, size - 8, 9, 8);
+    function.fill(6, 0, 1, size);
+    function.fill(0, 6, size, 1);
+
+    let positions = alignment_positions(version);
+    let last = positions.len().saturating_sub(1);
+    for (i, x) in positions.iter().enumerate() {
+        for (j, y) in positions.iter().enumerate() {
+            let on_finder = (i == 0 && (j == 0 || j == last)) || (i == last && j == 0);
+            if !on_finder {
+                function.fill(x - 2, y - 2, 5, 5);
+            }
+        }
+    }
+
+    if version >= 7 {
+        function.fill(size - 11, 0, 3, 6);
+        function.fill(0, size - 11, 6, 3);
+    }
+    function
+}
+
+/// Coordinates of the alignment patterns centers, on both axes
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return vec![];
+    }
+    let count = version / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let size = version * 4 + 17;
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+fn mask_bit(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y) % 2 == 0,
+        1 => y % 2 == 0,
+        2 => x % 3 == 0,
+        3 => (x + y) % 3 == 0,
+        4 => (x / 3 + y / 2) % 2 == 0,
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3) % 2 == 0,
+        _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+    }
+}
+
+/// Unmasked codewords read in the zigzag order of two columns wide strips, from the right
+fn read_codewords(modules: &Modules, function: &Modules, mask: u8) -> Vec<u8> {
+    let size = modules.size;
+    let count = function.dark.iter().filter(|f| !**f).count() / 8;
+    let mut codewords = vec![0u8; count];
+    let mut i = 0usize;
+    let mut right = size - 1;
+    loop {
+        if right == 6 {
+            right = 5; // skip the vertical timing pattern
+        }
+        let upward = (right + 1) & 2 == 0;
+        for vert in 0..size {
+            let y = if upward { size - 1 - vert } else { vert };
+            for x in &[right, right - 1] {
+                if !function.get(*x, y) && i < count * 8 {
+                    if modules.get(*x, y) ^ mask_bit(mask, *x, y) {
+                        codewords[i >> 3] |= 0x80 >> (i & 7);
+                    }
+                    i += 1;
+                }
+            }
+        }
+        if right <= 1 {
+            break;
+        }
+        right -= 2;
+    }
+    codewords
+}
+
+/// Number of data codewords of the version at the error correction level
+fn data_codewords(version: usize, level: usize) -> usize {
+    let function = function_modules(version);
+    let size = function.size;
+    let data_modules = (0..size)
+        .flat_map(|y| (0..size).map(move |x| (x, y)))
+        .filter(|(x, y)| !function.get(*x, *y))
+        .count();
+    data_modules / 8
+        - EC_BLOCKS[level][version] as usize * EC_CODEWORDS_PER_BLOCK[level][version] as usize
+}
+
+/// De-interleave the blocks, correct them and concatenate their data codewords
+fn correct_blocks(raw: &[u8], version: usize, level: usize) -> Result<Vec<u8>> {
+    let blocks = EC_BLOCKS[level][version] as usize;
+    let ec_len = EC_CODEWORDS_PER_BLOCK[level][version] as usize;
+    let short_blocks = blocks - raw.len() % blocks;
+    let short_data_len = raw.len() / blocks - ec_len;
+    let data_len = |block: usize| short_data_len + (block >= short_blocks) as usize;
+
+    let mut block_codewords = vec![vec![]; blocks];
+    let mut iter = raw.iter();
+    for i in 0..=short_data_len {
+        for (block, codewords) in block_codewords.iter_mut().enumerate() {
+            if i < data_len(block) {
+                codewords.push(*iter.next().expect("sizes from tables"));
+            }
+        }
+    }
+    for _ in 0..ec_len {
+        for codewords in block_codewords.iter_mut() {
+            codewords.push(*iter.next().expect("sizes from tables"));
+        }
+    }
+
+    let gf = Galois::new();
+    let mut result = vec![];
+    for (block, codewords) in block_codewords.iter_mut().enumerate() {
+        gf.correct(codewords, ec_len)?;
+        result.extend_from_slice(&codewords[..data_len(block)]);
+    }
+    Ok(result)
+}
+
+/// GF(256) with the QR primitive polynomial x^8 + x^4 + x^3 + x^2 + 1
+struct Galois {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+impl Galois {
+    fn new() -> Self {
+        let mut exp = [0u8; 512];
+        let mut log = [0u8; 256];
+        let mut x = 1u16;
+        for i in 0..255 {
+            exp[i] = x as u8;
+            exp[i + 255] = x as u8;
+            log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= 0x11d;
+            }
+        }
+        Galois { exp, log }
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            0
+        } else {
+            self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+        }
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        if a == 0 {
+            0
+        } else {
+            self.exp[self.log[a as usize] as usize + 255 - self.log[b as usize] as usize]
+        }
+    }
+
+    /// Value at `x` of the polynomial with coefficients from the lowest degree
+    fn eval(&self, poly: &[u8], x: u8) -> u8 {
+        poly.iter().rev().fold(0, |acc, c| self.mul(acc, x) ^ c)
+    }
+
+    /// Reed-Solomon correction of the codewords, the last `ec_len` are the error correction ones,
+    /// errors are located with Berlekamp-Massey and Chien search and valued with Forney
+    fn correct(&self, codewords: &mut [u8], ec_len: usize) -> Result<()> {
+        let n = codewords.len();
+        // the first codeword is the coefficient of the highest degree
+        let syndromes: Vec<u8> = (0..ec_len)
+            .map(|i| {
+                codewords
+                    .iter()
+                    .fold(0, |acc, c| self.mul(acc, self.exp[i]) ^ c)
+            })
+            .collect();
+        if syndromes.iter().all(|s| *s == 0) {
+            return Ok(());
+        }
+
+        let mut locator = vec![1u8];
+        let mut previous = vec![1u8];
+        let (mut errors, mut shift, mut previous_discrepancy) = (0usize, 1usize, 1u8);
+        for i in 0..ec_len {
+            let discrepancy = (1..=errors).fold(syndromes[i], |acc, j| {
+                acc ^ self.mul(*locator.get(j).unwrap_or(&0), syndromes[i - j])
+            });
+            if discrepancy == 0 {
+                shift += 1;
+                continue;
+            }
+            let factor = self.div(discrepancy, previous_discrepancy);
+            let mut updated = locator.clone();
+            updated.resize(updated.len().max(previous.len() + shift), 0);
+            for (j, c) in previous.iter().enumerate() {
+                updated[j + shift] ^= self.mul(factor, *c);
+            }
+            if 2 * errors <= i {
+                previous = locator;
+                errors = i + 1 - errors;
+                previous_discrepancy = discrepancy;
+                shift = 1;
+            } else {
+                shift += 1;
+            }
+            locator = updated;
+        }
+        if 2 * errors > ec_len {
+            return Err("too many errors in the qr code".into());
+        }
+
+        let mut evaluator: Vec<u8> = vec![0u8; ec_len];
+        for (i, s) in syndromes.iter().enumerate() {
+            for (j, l) in locator.iter().enumerate().take(ec_len - i) {
+                evaluator[i + j] ^= self.mul(*s, *l);
+            }
+        }
+        let derivative: Vec<u8> = locator
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, c)| if i % 2 == 1 { *c } else { 0 })
+            .collect();
+
+        let mut found = 0;
+        for (k, codeword) in codewords.iter_mut().enumerate() {
+            let power = (n - 1 - k) % 255;
+            let x = self.exp[power];
+            let x_inv = self.exp[(255 - power) % 255];
+            if self.eval(&locator, x_inv) == 0 {
+                let denominator = self.eval(&derivative, x_inv);
+                if denominator == 0 {
+                    return Err("cannot correct the qr code".into());
+                }
+                let magnitude = self.mul(x, self.div(self.eval(&evaluator, x_inv), denominator));
+                *codeword ^= magnitude;
+                found += 1;
+            }
+        }
+        if found != errors {
+            return Err("too many errors in the qr code".into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::qr::{merge_qrs, SplittedQr};
+    use crate::common::qr_reader::{read_qr_luma, QrData};
+    use image::Luma;
+    use qrcode::{EcLevel, QrCode, Version};
+
+    fn read(qr: &QrCode) -> QrData {
+        let image = qr.render::<Luma<u8>>().build();
+        let (width, height) = image.dimensions();
+        read_qr_luma(width as usize, height as usize, &image.into_raw()).unwrap()
+    }
+
+    #[test]
+    fn test_read_qr() {
+        for content in &[
+            "UR:CRYPTO-PSBT/HDCXJTOLTHLNSHTANAOSNEFHF",
+            "1234567",
+            "{\"a\":1}",
+        ] {
+            let qr = QrCode::new(content.as_bytes()).unwrap();
+            assert_eq!(read(&qr).content().unwrap(), content.as_bytes());
+        }
+        let data: Vec<u8> = (0..500).map(|_| rand::random::<u8>()).collect();
+        for level in &[EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H] {
+            let qr = QrCode::with_version(&data[..100], Version::Normal(20), *level).unwrap();
+            let read = read(&qr);
+            assert_eq!(read.version, 20);
+            assert_eq!(read.content().unwrap(), &data[..100]);
+        }
+
+        for version in &[1, 7, 14, 40] {
+            let qr = QrCode::with_version(&data[..10], Version::Normal(*version), EcLevel::L);
+            let read = read(&qr.unwrap());
+            let from_codewords = QrData::from_codewords(read.codewords.clone()).unwrap();
+            assert_eq!(from_codewords, read);
+        }
+        assert!(QrData::from_codewords(vec![0; 20]).is_err());
+
+        let qrs = SplittedQr::new(data.clone(), 10).unwrap().split().unwrap();
+        assert!(qrs.len() > 1);
+        let parts: Vec<Vec<u8>> = qrs.iter().map(|qr| read(qr).codewords).collect();
+        assert!(parts.iter().all(|p| p[0] >> 4 == 3));
+        assert_eq!(merge_qrs(parts).unwrap(), data);
+
+        let image = QrCode::new(b"firma").unwrap().render::<Luma<u8>>().build();
+        let (width, height) = image.dimensions();
+        let mut pixels = image.into_raw();
+        // a wrong module in the data area is corrected
+        let module = width as usize / 29;
+        let index = (width as usize * module * 14) + module * 15;
+        for i in 0..module {
+            for j in 0..module {
+                let p = index + i * width as usize + j;
+                pixels[p] = 255 - pixels[p];
+            }
+        }
+        let read = read_qr_luma(width as usize, height as usize, &pixels).unwrap();
+        assert_eq!(read.content().unwrap(), b"firma");
+        assert!(read_qr_luma(2, 2, &[255; 4]).is_err());
+    }
+}
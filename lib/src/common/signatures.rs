@@ -0,0 +1,427 @@
+use crate::*;
+use bitcoin::blockdata::opcodes;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::consensus::Encodable;
+use bitcoin::hashes::{hash160, Hash};
+use bitcoin::secp256k1::{self, Message, Secp256k1};
+use bitcoin::{PublicKey, Script, SigHash, SigHashType, Transaction};
+
+/// Flag of the sighash types signing only the input, other inputs could be added
+const ANYONECANPAY: u32 = 0x80;
+
+/// Script code signed by the input: the redeem script of p2sh, the witness script of p2wsh and
+/// the p2pkh script of p2wpkh inputs, checking they match the script of the spent output
+pub fn script_code(psbt: &PSBT, input_index: usize) -> Result<Script> {
+    let input = psbt
+        .inputs
+        .get(input_index)
+        .ok_or_else(fn_err("input not found"))?;
+    match (&input.witness_utxo, &input.non_witness_utxo) {
+        (None, Some(non_witness_utxo)) => {
+            let prevout = psbt.global.unsigned_tx.input[input_index].previous_output;
+            if non_witness_utxo.txid() != prevout.txid {
+                return Err("prevout doesn't match non_witness_utxo".into());
+            }
+            let script_pubkey = &non_witness_utxo
+                .output
+                .get(prevout.vout as usize)
+                .ok_or_else(fn_err("can't find txout"))?
+                .script_pubkey;
+            match &input.redeem_script {
+                Some(redeem_script) => {
+                    if *script_pubkey != redeem_script.to_p2sh() {
+                        return Err(
+                            "script_pubkey does not match the redeem script converted to p2sh"
+                                .into(),
+                        );
+                    }
+                    Ok(redeem_script.clone())
+                }
+                None => Ok(script_pubkey.clone()),
+            }
+        }
+        (None, None) => Err("both witness_utxo and non_witness_utxo are none".into()),
+        (Some(witness_utxo), _) => {
+            check_witness_utxo(psbt, input_index)?;
+            let script = match &input.redeem_script {
+                Some(script) => {
+                    if witness_utxo.script_pubkey != script.to_p2sh() {
+                        return Err("witness_utxo script_pubkey doesn't match the redeem script converted to p2sh".into());
+                    }
+                    script
+                }
+                None => &witness_utxo.script_pubkey,
+            };
+            if script.is_v0_p2wpkh() {
+                let script = to_p2pkh(&script.as_bytes()[2..]);
+                if !script.is_p2pkh() {
+                    return Err("it is not a p2pkh script".into());
+                }
+                Ok(script)
+            } else {
+                let wit_script = input
+                    .witness_script
+                    .as_ref()
+                    .ok_or_else(fn_err("witness_script is none"))?;
+                if *script != wit_script.to_v0_p2wsh() {
+                    return Err("script and witness script to v0 p2wsh doesn't match".into());
+                }
+                Ok(wit_script.clone())
+            }
+        }
+    }
+}
+
+/// Check that `witness_utxo` is the output spent in `non_witness_utxo` when the input has both,
+/// since amounts are read from `witness_utxo`
+pub(crate) fn check_witness_utxo(psbt: &PSBT, input_index: usize) -> Result<()> {
+    let input = psbt
+        .inputs
+        .get(input_index)
+        .ok_or_else(fn_err("input not found"))?;
+    if let (Some(witness_utxo), Some(non_witness_utxo)) =
+        (&input.witness_utxo, &input.non_witness_utxo)
+    {
+        let prevout = psbt.global.unsigned_tx.input[input_index].previous_output;
+        if non_witness_utxo.txid() != prevout.txid
+            || non_witness_utxo.output.get(prevout.vout as usize) != Some(witness_utxo)
+        {
+            return Err(format!(
+                "witness_utxo of input {} doesn't match the spent output of non_witness_utxo",
+                input_index
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Hash signed by the input with `sighash_type`, as in BIP143 for segwit inputs. `SINGLE` types
+/// of inputs without an output at the same index are refused, since they don't commit to outputs
+pub fn signature_hash(
+    psbt: &PSBT,
+    input_index: usize,
+    script_code: &Script,
+    sighash_type: SigHashType,
+) -> Result<SigHash> {
+    let input = psbt
+        .inputs
+        .get(input_index)
+        .ok_or_else(fn_err("input not found"))?;
+    let tx = &psbt.global.unsigned_tx;
+    let base_type = sighash_type.as_u32() & !ANYONECANPAY;
+    if base_type == SigHashType::Single.as_u32() && input_index >= tx.output.len() {
+        return Err(format!(
+            "sighash type {:?} of input {} without an output at the same index",
+            sighash_type, input_index
+        )
+        .into());
+    }
+    match &input.witness_utxo {
+        Some(witness_utxo) => segwit_signature_hash(
+            tx,
+            input_index,
+            script_code,
+            witness_utxo.value,
+            sighash_type,
+        ),
+        None => Ok(tx.signature_hash(input_index, script_code, sighash_type.as_u32())),
+    }
+}
+
+/// BIP143 hash signed by segwit v0 inputs, `NONE` and `SINGLE` types don't commit to all the
+/// outputs, `ANYONECANPAY` types to the other inputs
+fn segwit_signature_hash(
+    tx: &Transaction,
+    input_index: usize,
+    script_code: &Script,
+    value: u64,
+    sighash_type: SigHashType,
+) -> Result<SigHash> {
+    let anyone_can_pay = sighash_type.as_u32() & ANYONECANPAY != 0;
+    let base_type = sighash_type.as_u32() & !ANYONECANPAY;
+    let single = base_type == SigHashType::Single.as_u32();
+    let none = base_type == SigHashType::None.as_u32();
+    let zero = [0u8; 32];
+
+    let hash_prevouts = if anyone_can_pay {
+        zero
+    } else {
+        let mut engine = SigHash::engine();
+        for txin in tx.input.iter() {
+            txin.previous_output.consensus_encode(&mut engine)?;
+        }
+        SigHash::from_engine(engine).into_inner()
+    };
+    let hash_sequence = if anyone_can_pay || single || none {
+        zero
+    } else {
+        let mut engine = SigHash::engine();
+        for txin in tx.input.iter() {
+            txin.sequence.consensus_encode(&mut engine)?;
+        }
+        SigHash::from_engine(engine).into_inner()
+    };
+    let hash_outputs = if single {
+        let mut engine = SigHash::engine();
+        tx.output[input_index].consensus_encode(&mut engine)?;
+        SigHash::from_engine(engine).into_inner()
+    } else if none {
+        zero
+    } else {
+        let mut engine = SigHash::engine();
+        for txout in tx.output.iter() {
+            txout.consensus_encode(&mut engine)?;
+        }
+        SigHash::from_engine(engine).into_inner()
+    };
+
+    let txin = &tx.input[input_index];
+    let mut engine = SigHash::engine();
+    tx.version.consensus_encode(&mut engine)?;
+    hash_prevouts.consensus_encode(&mut engine)?;
+    hash_sequence.consensus_encode(&mut engine)?;
+    txin.previous_output.consensus_encode(&mut engine)?;
+    script_code.consensus_encode(&mut engine)?;
+    value.consensus_encode(&mut engine)?;
+    txin.sequence.consensus_encode(&mut engine)?;
+    hash_outputs.consensus_encode(&mut engine)?;
+    tx.lock_time.consensus_encode(&mut engine)?;
+    sighash_type.as_u32().consensus_encode(&mut engine)?;
+    Ok(SigHash::from_engine(engine))
+}
+
+/// Verify the partial signatures of the input against the hash signed and the public key,
+/// returns who signed: the fingerprint of the key or the key itself if not in the key paths.
+/// Signatures of keys not used in the script code, directly or by hash, are refused
+pub fn verify_partial_sigs(psbt: &PSBT, input_index: usize) -> Result<Vec<String>> {
+    let (signed_by, mut invalid) = check_partial_sigs(psbt, input_index)?;
+    if !invalid.is_empty() {
+        return Err(Error::Generic(invalid.remove(0)));
+    }
+    Ok(signed_by)
+}
+
+/// Like `verify_partial_sigs` but the signatures refused don't stop the verification of the
+/// others, returns who signed and why the other signatures are refused
+pub fn check_partial_sigs(psbt: &PSBT, input_index: usize) -> Result<(Vec<String>, Vec<String>)> {
+    let input = psbt
+        .inputs
+        .get(input_index)
+        .ok_or_else(fn_err("input not found"))?;
+    if input.partial_sigs.is_empty() {
+        return Ok((vec![], vec![]));
+    }
+    let secp = Secp256k1::verification_only();
+    let script_code = script_code(psbt, input_index)?;
+    let script_keys = extract_pub_keys(&script_code)?;
+    let script_hashes = extract_pub_key_hashes(&script_code);
+    let verify = |pubkey: &PublicKey, signature: &[u8]| -> Result<()> {
+        if !script_keys.contains(pubkey)
+            && !script_hashes.contains(&hash160::Hash::hash(&pubkey.to_bytes()))
+        {
+            return Err(format!(
+                "signature of key {} in input {} but the key is not in the script",
+                pubkey, input_index
+            )
+            .into());
+        }
+        let invalid = || {
+            Error::Generic(format!(
+                "invalid signature of key {} in input {}",
+                pubkey, input_index
+            ))
+        };
+        let (sighash_byte, der) = signature.split_last().ok_or_else(invalid)?;
+        let sighash_type = SigHashType::from_u32(*sighash_byte as u32);
+        // from_u32 maps unknown values to a standard type, the hash must commit to the byte
+        if sighash_type.as_u32() != *sighash_byte as u32 {
+            return Err(format!(
+                "signature of key {} in input {} has non standard sighash type {}",
+                pubkey, input_index, sighash_byte
+            )
+            .into());
+        }
+        let hash = signature_hash(psbt, input_index, &script_code, sighash_type)?;
+        let msg = Message::from_slice(&hash.into_inner()[..])?;
+        let signature = secp256k1::Signature::from_der(der).map_err(|_| invalid())?;
+        secp.verify(&msg, &signature, &pubkey.key)
+            .map_err(|_| invalid())
+    };
+    let mut signed_by = vec![];
+    let mut invalid = vec![];
+    for (pubkey, signature) in input.partial_sigs.iter() {
+        match verify(pubkey, signature) {
+            Ok(()) => signed_by.push(match input.hd_keypaths.get(pubkey) {
+                Some((fingerprint, _)) => fingerprint.to_string(),
+                None => pubkey.to_string(),
+            }),
+            Err(e) => invalid.push(e.to_string()),
+        }
+    }
+    signed_by.sort();
+    Ok((signed_by, invalid))
+}
+
+fn to_p2pkh(pubkey_hash: &[u8]) -> Script {
+    Builder::new()
+        .push_opcode(opcodes::all::OP_DUP)
+        .push_opcode(opcodes::all::OP_HASH160)
+        .push_slice(pubkey_hash)
+        .push_opcode(opcodes::all::OP_EQUALVERIFY)
+        .push_opcode(opcodes::all::OP_CHECKSIG)
+        .into_script()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::signatures::{script_code, signature_hash, verify_partial_sigs};
+    use crate::{psbt_from_base64, PsbtJson, PSBT};
+    use bitcoin::consensus::deserialize;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+    use bitcoin::util::key;
+    use bitcoin::{Script, SigHashType, Transaction, TxOut};
+
+    #[test]
+    fn test_signature_hash() {
+        // P2SH-P2WSH 6-of-6 multisig example of BIP143, signed with every sighash type
+        let tx: Transaction = deserialize(&hex::decode("010000000136641869ca081e70f394c6948e8af409e18b619df2ed74aa106c1ca29787b96e0100000000ffffffff0200e9a435000000001976a914389ffce9cd9ae88dcc0631e88a821ffdbe9bfe2688acc0832f05000000001976a9147480a33f950689af511e6e84c138dbbd3c3ee41588ac00000000").unwrap()).unwrap();
+        let witness_script = Script::from(hex::decode("56210307b8ae49ac90a048e9b53357a2354b3334e9c8bee813ecb98e99a7e07e8c3ba32103b28f0c28bfab54554ae8c658ac5c3e0ce6e79ad336331f78c428dd43eea8449b21034b8113d703413d57761b8b9781957b8c0ac1dfe69f492580ca4195f50376ba4a21033400f6afecb833092a9a21cfdf1ed1376e58c5d1f47de74683123987e967a8f42103a6d48b1131e94ba04d9737d61acdaa1322008af9602b3b14862c07a1789aac162102d8b661b0b3302ee2f162b09e07a55ad5dfbe673a9f01d9f0c19617681024306b56ae").unwrap());
+        let mut psbt = PSBT::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: 987654321,
+            script_pubkey: witness_script.to_v0_p2wsh().to_p2sh(),
+        });
+        let vectors = vec![
+            (
+                SigHashType::All,
+                "185c0be5263dce5b4bb50a047973c1b6272bfbd0103a89444597dc40b248ee7c",
+            ),
+            (
+                SigHashType::None,
+                "e9733bc60ea13c95c6527066bb975a2ff29a925e80aa14c213f686cbae5d2f36",
+            ),
+            (
+                SigHashType::Single,
+                "1e1f1c303dc025bd664acb72e583e933fae4cff9148bf78c157d1e8f78530aea",
+            ),
+            (
+                SigHashType::AllPlusAnyoneCanPay,
+                "2a67f03e63a6a422125878b40b82da593be8d4efaafe88ee528af6e5a9955c6e",
+            ),
+            (
+                SigHashType::NonePlusAnyoneCanPay,
+                "781ba15f3779d5542ce8ecb5c18716733a5ee42a6f51488ec96154934e2c890a",
+            ),
+            (
+                SigHashType::SinglePlusAnyoneCanPay,
+                "511e8e52ed574121fc1b654970395502128263f62662e076dc6baf05c2e6a99b",
+            ),
+        ];
+        for (sighash_type, expected) in vectors {
+            let hash = signature_hash(&psbt, 0, &witness_script, sighash_type).unwrap();
+            assert_eq!(hex::encode(hash.into_inner()), expected);
+        }
+
+        // SINGLE without an output at the same index
+        let mut tx = psbt.global.unsigned_tx.clone();
+        tx.output.clear();
+        let mut without_outputs = PSBT::from_unsigned_tx(tx).unwrap();
+        without_outputs.inputs[0].witness_utxo = psbt.inputs[0].witness_utxo.clone();
+        for sighash_type in &[SigHashType::Single, SigHashType::SinglePlusAnyoneCanPay] {
+            assert!(signature_hash(&without_outputs, 0, &witness_script, *sighash_type).is_err());
+        }
+        assert!(signature_hash(&without_outputs, 0, &witness_script, SigHashType::None).is_ok());
+    }
+
+    #[test]
+    fn test_verify_partial_sigs() {
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.signed.json");
+        let psbt_json: PsbtJson = serde_json::from_slice(bytes).unwrap();
+        let (_, mut psbt) = psbt_from_base64(&psbt_json.psbt).unwrap();
+        let signed_by = vec!["d90c6a4f".to_string(), "d90c6a4f".to_string()];
+        assert_eq!(verify_partial_sigs(&psbt, 0).unwrap(), signed_by);
+        assert_eq!(verify_partial_sigs(&psbt, 1).unwrap(), signed_by);
+        assert!(verify_partial_sigs(&psbt, 2).is_err());
+
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.json");
+        let psbt_json: PsbtJson = serde_json::from_slice(bytes).unwrap();
+        let (_, unsigned) = psbt_from_base64(&psbt_json.psbt).unwrap();
+        assert!(verify_partial_sigs(&unsigned, 0).unwrap().is_empty());
+
+        // signature of a key without key path
+        let key = *psbt.inputs[1].partial_sigs.keys().next().unwrap();
+        psbt.inputs[1].hd_keypaths.remove(&key);
+        let signed_by = verify_partial_sigs(&psbt, 1).unwrap();
+        assert!(signed_by.contains(&key.to_string()));
+
+        // valid signature of a key not in the script
+        let mut unrelated = psbt.clone();
+        let secp = Secp256k1::signing_only();
+        let secret_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let other_key = key::PublicKey {
+            compressed: true,
+            key: bitcoin::secp256k1::PublicKey::from_secret_key(&secp, &secret_key),
+        };
+        let script = script_code(&unrelated, 0).unwrap();
+        let hash = signature_hash(&unrelated, 0, &script, SigHashType::All).unwrap();
+        let msg = Message::from_slice(&hash.into_inner()[..]).unwrap();
+        let mut other_signature = secp.sign(&msg, &secret_key).serialize_der().to_vec();
+        other_signature.push(SigHashType::All.as_u32() as u8);
+        unrelated.inputs[0]
+            .partial_sigs
+            .insert(other_key, other_signature);
+        assert_eq!(
+            verify_partial_sigs(&unrelated, 0).unwrap_err().to_string(),
+            format!(
+                "signature of key {} in input 0 but the key is not in the script",
+                other_key
+            )
+        );
+
+        // signature of the first input moved to the second
+        let (key, signature) = psbt.inputs[0].partial_sigs.iter().next().unwrap();
+        let (key, signature) = (*key, signature.clone());
+        let mut moved = psbt.clone();
+        moved.inputs[1].partial_sigs.insert(key, signature.clone());
+        assert_eq!(
+            verify_partial_sigs(&moved, 1).unwrap_err().to_string(),
+            format!(
+                "signature of key {} in input 1 but the key is not in the script",
+                key
+            )
+        );
+
+        // signature of a key of the script given to the other key
+        let mut swapped = psbt.clone();
+        let keys: Vec<_> = swapped.inputs[0].partial_sigs.keys().cloned().collect();
+        let other_signature = swapped.inputs[0].partial_sigs[&keys[1]].clone();
+        swapped.inputs[0]
+            .partial_sigs
+            .insert(keys[0], other_signature);
+        assert_eq!(
+            verify_partial_sigs(&swapped, 0).unwrap_err().to_string(),
+            format!("invalid signature of key {} in input 0", keys[0])
+        );
+
+        // non standard sighash type, read as ALL by SigHashType::from_u32
+        let mut non_standard = signature.clone();
+        *non_standard.last_mut().unwrap() = 0x05;
+        psbt.inputs[0].partial_sigs.insert(key, non_standard);
+        assert_eq!(
+            verify_partial_sigs(&psbt, 0).unwrap_err().to_string(),
+            format!(
+                "signature of key {} in input 0 has non standard sighash type 5",
+                key
+            )
+        );
+
+        // tampered signature
+        let mut tampered = signature;
+        let len = tampered.len();
+        tampered[len - 2] ^= 1;
+        psbt.inputs[0].partial_sigs.insert(key, tampered);
+        assert!(verify_partial_sigs(&psbt, 0).is_err());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::*;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::{Amount, OutPoint};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+/// First byte of the compact PSBT transfer encoding, it can't be the first byte of json,
+/// UTF-8 text or a raw PSBT
+pub const MAGIC: u8 = 0xfb;
+
+/// Version of the compact PSBT transfer encoding
+pub const VERSION: u8 = 1;
+
+/// Flag set when the body is compressed with deflate
+const FLAG_DEFLATE: u8 = 0x01;
+
+/// Max size of the inflated body, much more than a PSBT fitting in 16 QR codes
+const MAX_BODY_LEN: u64 = 1_000_000;
+
+/// Length of name length, fee and change position in the body
+const BODY_HEADER_LEN: usize = 1 + 8 + 4;
+
+/// Compact encoding of the PSBT json for QR codes: `MAGIC`, `VERSION`, flags and the body,
+/// deflated if smaller, made of name length (1 byte), name, fee in satoshi (u64 LE),
+/// change position (i32 LE) and the raw PSBT. Redundant `non_witness_utxo` are removed only if
+/// `strip` is true, since they are the only way for the signer to verify input amounts
+pub fn encode_psbt(psbt_json: &PsbtJson, strip: bool) -> Result<Vec<u8>> {
+    let (mut psbt_bytes, mut psbt) = psbt_from_base64(&psbt_json.psbt)?;
+    if strip && strip_non_witness_utxo(&mut psbt) > 0 {
+        psbt_bytes = serialize(&psbt);
+    }
+    let name = psbt_json.name.as_bytes();
+    if name.len() > u8::max_value() as usize {
+        return Err("psbt name longer than 255 bytes".into());
+    }
+    let fee = Amount::from_btc(psbt_json.fee).map_err(|e| Error::Generic(e.to_string()))?;
+
+    let mut body = vec![name.len() as u8];
+    body.extend_from_slice(name);
+    body.extend_from_slice(&fee.as_sat().to_le_bytes());
+    body.extend_from_slice(&psbt_json.changepos.to_le_bytes());
+    body.extend(psbt_bytes);
+
+    let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+    encoder.write_all(&body)?;
+    let deflated = encoder.finish()?;
+    let (flags, body) = if deflated.len() < body.len() {
+        (FLAG_DEFLATE, deflated)
+    } else {
+        (0, body)
+    };
+
+    let mut result = vec![MAGIC, VERSION, flags];
+    result.extend(body);
+    Ok(result)
+}
+
+/// True if the bytes are in the compact PSBT transfer encoding, of any version
+pub fn is_compact_psbt(bytes: &[u8]) -> bool {
+    bytes.first() == Some(&MAGIC)
+}
+
+/// Decode the compact PSBT transfer encoding
+pub fn decode_psbt(bytes: &[u8]) -> Result<PsbtJson> {
+    if bytes.len() < 3 || bytes[0] != MAGIC {
+        return Err("not a compact psbt".into());
+    }
+    if bytes[1] != VERSION {
+        return Err(format!("unsupported compact psbt version {}", bytes[1]).into());
+    }
+    let flags = bytes[2];
+    if flags & !FLAG_DEFLATE != 0 {
+        return Err(format!("unknown compact psbt flags {}", flags).into());
+    }
+    let body = if flags & FLAG_DEFLATE != 0 {
+        let mut inflated = vec![];
+        DeflateDecoder::new(&bytes[3..])
+            .take(MAX_BODY_LEN)
+            .read_to_end(&mut inflated)?;
+        if inflated.len() as u64 == MAX_BODY_LEN {
+            return Err("compact psbt too big".into());
+        }
+        inflated
+    } else {
+        bytes[3..].to_vec()
+    };
+
+    let name_len = *body.first().ok_or_else(fn_err("empty compact psbt"))? as usize;
+    if body.len() < BODY_HEADER_LEN + name_len {
+        return Err("compact psbt too short".into());
+    }
+    let name = std::str::from_utf8(&body[1..1 + name_len])?.to_string();
+    let rest = &body[1 + name_len..];
+    let fee = u64::from_le_bytes(rest[..8].try_into().expect("8 bytes"));
+    let changepos = i32::from_le_bytes(rest[8..12].try_into().expect("4 bytes"));
+    let psbt_bytes = &rest[12..];
+    let _: PSBT = deserialize(psbt_bytes)?;
+
+    Ok(PsbtJson {
+        name,
+        psbt: base64::encode(psbt_bytes),
+        fee: Amount::from_sat(fee).as_btc(),
+        changepos,
+    })
+}
+
+/// Remove `non_witness_utxo` of segwit inputs when it contains the same output of `witness_utxo`,
+/// segwit signatures commit to the amount but a signer with a spending policy can't verify
+/// `witness_utxo` alone, returns the number of inputs changed
+pub fn strip_non_witness_utxo(psbt: &mut PSBT) -> usize {
+    let prevouts: Vec<OutPoint> = psbt
+        .global
+        .unsigned_tx
+        .input
+        .iter()
+        .map(|i| i.previous_output)
+        .collect();
+    let mut stripped = 0;
+    for (input, prevout) in psbt.inputs.iter_mut().zip(prevouts) {
+        let redundant = match (&input.witness_utxo, &input.non_witness_utxo) {
+            (Some(utxo), Some(prev_tx)) => {
+                let script = match &input.redeem_script {
+                    Some(redeem_script) if utxo.script_pubkey == redeem_script.to_p2sh() => {
+                        redeem_script
+                    }
+                    Some(_) => continue,
+                    None => &utxo.script_pubkey,
+                };
+                (script.is_v0_p2wpkh() || script.is_v0_p2wsh())
+                    && prev_tx.txid() == prevout.txid
+                    && prev_tx.output.get(prevout.vout as usize) == Some(utxo)
+            }
+            _ => false,
+        };
+        if redundant {
+            input.non_witness_utxo = None;
+            stripped += 1;
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::qr::SplittedQr;
+    use crate::common::transfer::{decode_psbt, encode_psbt, strip_non_witness_utxo, MAGIC};
+    use crate::{psbt_from_base64, PsbtJson};
+    use bitcoin::consensus::serialize;
+
+    // BIP174 vector with one P2PKH input and one P2SH-P2WPKH input spending the same transaction
+    const PSBT_P2PKH_P2SH_P2WPKH: &str = "cHNidP8BAKACAAAAAqsJSaCMWvfEm4IS9Bfi8Vqz9cM9zxU4IagTn4d6W3vkAAAAAAD+////qwlJoIxa98SbghL0F+LxWrP1wz3PFTghqBOfh3pbe+QBAAAAAP7///8CYDvqCwAAAAAZdqkUdopAu9dAy+gdmI5x3ipNXHE5ax2IrI4kAAAAAAAAGXapFG9GILVT+glechue4O/p+gOcykWXiKwAAAAAAAEA3wIAAAABJoFxNx7f8oXpN63upLN7eAAMBWbLs61kZBcTykIXG/YAAAAAakcwRAIgcLIkUSPmv0dNYMW1DAQ9TGkaXSQ18Jo0p2YqncJReQoCIAEynKnazygL3zB0DsA5BCJCLIHLRYOUV663b8Eu3ZWzASECZX0RjTNXuOD0ws1G23s59tnDjZpwq8ubLeXcjb/kzjH+////AtPf9QUAAAAAGXapFNDFmQPFusKGh2DpD9UhpGZap2UgiKwA4fUFAAAAABepFDVF5uM7gyxHBQ8k0+65PJwDlIvHh7MuEwAAAQEgAOH1BQAAAAAXqRQ1RebjO4MsRwUPJNPuuTycA5SLx4cBBBYAFIXRNTfy4mVAWjTbr6nj3aAfuCMIAAAA";
+
+    fn qr_count(bytes: &[u8], version: i16) -> usize {
+        SplittedQr::new(bytes.to_vec(), version).unwrap().total_qr
+    }
+
+    #[test]
+    fn test_compact_psbt() {
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.signed.json");
+        let psbt_json: PsbtJson = serde_json::from_slice(bytes).unwrap();
+        let json = serde_json::to_vec(&psbt_json).unwrap();
+        let compact = encode_psbt(&psbt_json, false).unwrap();
+        assert_eq!(compact[..3], [MAGIC, 1, 1]);
+        assert_eq!(decode_psbt(&compact).unwrap(), psbt_json);
+        assert_eq!(json.len(), 1829);
+        assert!(compact.len() < 1100);
+        assert_eq!(qr_count(&json, 14), 5);
+        assert_eq!(qr_count(&compact, 14), 3);
+        assert_eq!(qr_count(&json, 20), 3);
+        assert_eq!(qr_count(&compact, 20), 2);
+
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.json");
+        let psbt_json: PsbtJson = serde_json::from_slice(bytes).unwrap();
+        let json = serde_json::to_vec(&psbt_json).unwrap();
+        let compact = encode_psbt(&psbt_json, false).unwrap();
+        assert_eq!(decode_psbt(&compact).unwrap(), psbt_json);
+        assert_eq!(json.len(), 1257);
+        assert_eq!(qr_count(&json, 14), 3);
+        assert_eq!(qr_count(&compact, 14), 2);
+
+        let mut wrong = compact.clone();
+        wrong[1] = 2;
+        assert_eq!(
+            decode_psbt(&wrong).unwrap_err().to_string(),
+            "unsupported compact psbt version 2"
+        );
+        assert!(decode_psbt(&compact[..compact.len() - 10]).is_err());
+        assert!(decode_psbt(&json).is_err());
+    }
+
+    #[test]
+    fn test_strip_non_witness_utxo() {
+        let (_, original) = psbt_from_base64(PSBT_P2PKH_P2SH_P2WPKH).unwrap();
+        let mut psbt = original.clone();
+        assert_eq!(strip_non_witness_utxo(&mut psbt), 0);
+
+        // the previous transaction of the P2PKH input is also the one of the P2SH-P2WPKH input
+        psbt.inputs[1].non_witness_utxo = psbt.inputs[0].non_witness_utxo.clone();
+        let psbt_json = PsbtJson {
+            name: "strip".to_string(),
+            psbt: base64::encode(serialize(&psbt)),
+            fee: 0.0001,
+            changepos: -1,
+        };
+        let kept = encode_psbt(&psbt_json, false).unwrap();
+        assert_eq!(decode_psbt(&kept).unwrap(), psbt_json);
+
+        let stripped = encode_psbt(&psbt_json, true).unwrap();
+        assert!(stripped.len() < kept.len());
+        assert!(qr_count(&stripped, 14) <= qr_count(&kept, 14));
+        let decoded = decode_psbt(&stripped).unwrap();
+        assert_eq!(decoded.psbt, PSBT_P2PKH_P2SH_P2WPKH);
+        assert_eq!(decoded.fee, 0.0001);
+        assert_eq!(decoded.changepos, -1);
+
+        assert_eq!(strip_non_witness_utxo(&mut psbt), 1);
+        assert!(psbt.inputs[0].non_witness_utxo.is_some());
+        assert_eq!(psbt, original);
+    }
+}
@@ -0,0 +1,552 @@
+use crate::common::cbor::Cbor;
+use crate::common::descriptor::{DescriptorKey, DescriptorScript, WalletDescriptor};
+use crate::common::fountain::{crc32, FountainDecoder, FountainEncoder, Part};
+use crate::common::key_origin::{KeyOrigin, XpubWithOrigin};
+use crate::*;
+use bitcoin::util::bip32::{ChainCode, ChildNumber, ExtendedPubKey, Fingerprint};
+use bitcoin::util::key;
+use bitcoin::Network;
+
+/// UR type of a PSBT (BCR-2020-006)
+pub const CRYPTO_PSBT: &str = "crypto-psbt";
+/// UR type of an output descriptor (BCR-2020-010)
+pub const CRYPTO_OUTPUT: &str = "crypto-output";
+
+const TAG_HDKEY: u64 = 303;
+const TAG_KEYPATH: u64 = 304;
+const TAG_COININFO: u64 = 305;
+const TAG_SH: u64 = 400;
+const TAG_WSH: u64 = 401;
+const TAG_WPKH: u64 = 404;
+const TAG_MULTI: u64 = 406;
+const TAG_SORTEDMULTI: u64 = 407;
+
+/// Bytewords (BCR-2020-012), in minimal encoding a byte is the first and last letter of its word
+const BYTEWORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+    "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+    "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+    "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+    "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+    "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+    "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+    "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+    "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+    "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+    "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+    "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+    "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+    "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+    "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+/// Minimal bytewords of `data` followed by its crc32
+pub fn bytewords_encode(data: &[u8]) -> String {
+    let mut with_checksum = data.to_vec();
+    with_checksum.extend_from_slice(&crc32(data).to_be_bytes());
+    with_checksum
+        .iter()
+        .map(|b| {
+            let word = BYTEWORDS[*b as usize].as_bytes();
+            format!("{}{}", word[0] as char, word[3] as char)
+        })
+        .collect()
+}
+
+/// Decode minimal bytewords, verifying the final crc32
+pub fn bytewords_decode(s: &str) -> Result<Vec<u8>> {
+    let s = s.as_bytes();
+    if s.len() % 2 != 0 || s.len() < 10 {
+        return Err("invalid bytewords length".into());
+    }
+    let mut bytes = s
+        .chunks(2)
+        .map(|pair| {
+            BYTEWORDS
+                .iter()
+                .position(|w| w.as_bytes()[0] == pair[0] && w.as_bytes()[3] == pair[1])
+                .map(|i| i as u8)
+                .ok_or_else(fn_err("invalid byteword"))
+        })
+        .collect::<Result<Vec<u8>>>()?;
+    let checksum = bytes.split_off(bytes.len() - 4);
+    if checksum != crc32(&bytes).to_be_bytes() {
+        return Err("invalid bytewords checksum".into());
+    }
+    Ok(bytes)
+}
+
+/// Encode a cbor payload as `ur:type/bytewords`, or as fountain coded `ur:type/seq-len/bytewords`
+pub struct UrEncoder {
+    ur_type: String,
+    message: Vec<u8>,
+    fountain: FountainEncoder,
+}
+
+impl UrEncoder {
+    pub fn new(ur_type: &str, cbor: &[u8], max_fragment_len: usize) -> Result<Self> {
+        validate_type(ur_type)?;
+        Ok(UrEncoder {
+            ur_type: ur_type.to_string(),
+            message: cbor.to_vec(),
+            fountain: FountainEncoder::new(cbor, max_fragment_len)?,
+        })
+    }
+
+    pub fn seq_len(&self) -> usize {
+        self.fountain.seq_len()
+    }
+
+    pub fn is_single_part(&self) -> bool {
+        self.fountain.is_single_part()
+    }
+
+    /// Next part to show, when single part the whole message is always returned
+    pub fn next_part(&mut self) -> String {
+        if self.is_single_part() {
+            return format!("ur:{}/{}", self.ur_type, bytewords_encode(&self.message));
+        }
+        let part = self.fountain.next_part();
+        format!(
+            "ur:{}/{}-{}/{}",
+            self.ur_type,
+            part.seq,
+            part.seq_len,
+            bytewords_encode(&part.to_cbor())
+        )
+    }
+}
+
+fn validate_type(ur_type: &str) -> Result<()> {
+    let valid = !ur_type.is_empty()
+        && ur_type
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if !valid {
+        return Err(format!("invalid ur type {}", ur_type).into());
+    }
+    Ok(())
+}
+
+/// Collect UR parts, as scanned in any order, until the cbor payload is complete
+#[derive(Default)]
+pub struct UrDecoder {
+    ur_type: Option<String>,
+    fountain: FountainDecoder,
+    single: Option<Vec<u8>>,
+}
+
+impl UrDecoder {
+    /// Receive a part, case insensitive since QR codes contain it uppercase
+    pub fn receive(&mut self, part: &str) -> Result<()> {
+        let part = part.trim().to_lowercase();
+        if !part.starts_with("ur:") {
+            return Err("ur must start with ur:".into());
+        }
+        let components: Vec<&str> = part[3..].split('/').collect();
+        let (ur_type, payload) = match components.as_slice() {
+            [ur_type, payload] => (*ur_type, bytewords_decode(payload)?),
+            [ur_type, _seq, payload] => (*ur_type, bytewords_decode(payload)?),
+            _ => return Err("invalid ur path components".into()),
+        };
+        validate_type(ur_type)?;
+        match &self.ur_type {
+            Some(expected) if expected != ur_type => {
+                return Err(format!("ur of type {} while receiving {}", ur_type, expected).into())
+            }
+            _ => self.ur_type = Some(ur_type.to_string()),
+        }
+        if components.len() == 2 {
+            self.single = Some(payload);
+        } else {
+            let fountain_part = Part::from_cbor(&payload)?;
+            let expected_seq = format!("{}-{}", fountain_part.seq, fountain_part.seq_len);
+            if components[1] != expected_seq {
+                return Err("ur sequence does not match its part".into());
+            }
+            self.fountain.receive(&fountain_part)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.single.is_some() || self.fountain.is_complete()
+    }
+
+    /// UR type and cbor payload once complete
+    pub fn result(&self) -> Option<(&str, &[u8])> {
+        let payload = match &self.single {
+            Some(single) => single.as_slice(),
+            None => self.fountain.message()?,
+        };
+        Some((self.ur_type.as_ref()?.as_str(), payload))
+    }
+
+    /// Number of fragments known and total number of fragments
+    pub fn progress(&self) -> (usize, usize) {
+        match self.single {
+            Some(_) => (1, 1),
+            None => self.fountain.progress(),
+        }
+    }
+}
+
+/// `crypto-psbt` payload, the PSBT as a cbor byte string
+pub fn psbt_to_cbor(psbt_bytes: &[u8]) -> Vec<u8> {
+    Cbor::Bytes(psbt_bytes.to_vec()).encode()
+}
+
+pub fn psbt_from_cbor(cbor: &[u8]) -> Result<Vec<u8>> {
+    Ok(Cbor::decode(cbor)?.as_bytes()?.to_vec())
+}
+
+/// `crypto-output` payload of a wallet descriptor, miniscript descriptors are not supported
+pub fn output_to_cbor(descriptor: &WalletDescriptor) -> Result<Vec<u8>> {
+    let script = match &descriptor.script {
+        DescriptorScript::Wpkh(key) => {
+            let wpkh = tagged(TAG_WPKH, hdkey_to_cbor(key)?);
+            match descriptor.script_type {
+                ScriptType::ShWpkh => tagged(TAG_SH, wpkh),
+                _ => wpkh,
+            }
+        }
+        DescriptorScript::Multi {
+            required,
+            keys,
+            sorted,
+        } => {
+            let keys = keys.iter().map(hdkey_to_cbor).collect::<Result<Vec<_>>>()?;
+            let map = Cbor::Map(vec![
+                (Cbor::Unsigned(1), Cbor::Unsigned(*required as u64)),
+                (Cbor::Unsigned(2), Cbor::Array(keys)),
+            ]);
+            let tag = if *sorted { TAG_SORTEDMULTI } else { TAG_MULTI };
+            let multi = tagged(tag, map);
+            match descriptor.script_type {
+                ScriptType::Wsh => tagged(TAG_WSH, multi),
+                ScriptType::ShWsh => tagged(TAG_SH, tagged(TAG_WSH, multi)),
+                ScriptType::Sh => tagged(TAG_SH, multi),
+                ScriptType::Wpkh | ScriptType::ShWpkh => {
+                    return Err("multisig descriptor with a single signature script type".into())
+                }
+            }
+        }
+        DescriptorScript::Miniscript { .. } => {
+            return Err("miniscript descriptors cannot be encoded as crypto-output".into())
+        }
+    };
+    Ok(script.encode())
+}
+
+/// Descriptor, without checksum, of a `crypto-output` payload
+pub fn output_from_cbor(cbor: &[u8]) -> Result<String> {
+    let cbor = Cbor::decode(cbor)?;
+    let mut inner = &cbor;
+    let mut wrappers = vec![];
+    loop {
+        let (wrapper, value) = match inner {
+            Cbor::Tag(TAG_SH, value) if wrappers.is_empty() => ("sh", value),
+            Cbor::Tag(TAG_WSH, value) if !wrappers.contains(&"wsh") => ("wsh", value),
+            _ => break,
+        };
+        wrappers.push(wrapper);
+        inner = value.as_ref();
+    }
+    let script = match inner {
+        Cbor::Tag(TAG_WPKH, key) => format!("wpkh({})", hdkey_from_cbor(key)?),
+        Cbor::Tag(tag, map) if *tag == TAG_MULTI || *tag == TAG_SORTEDMULTI => {
+            let required = map
+                .get(1)
+                .ok_or_else(fn_err("multisig without threshold"))?;
+            let keys = map
+                .get(2)
+                .ok_or_else(fn_err("multisig without keys"))?
+                .as_array()?
+                .iter()
+                .map(|k| hdkey_from_cbor(k).map(|k| k.to_string()))
+                .collect::<Result<Vec<_>>>()?;
+            let name = if *tag == TAG_SORTEDMULTI {
+                "sortedmulti"
+            } else {
+                "multi"
+            };
+            format!("{}({},{})", name, required.as_u64()?, keys.join(","))
+        }
+        _ => return Err("unsupported crypto-output script".into()),
+    };
+    Ok(wrappers
+        .iter()
+        .rev()
+        .fold(script, |s, wrapper| format!("{}({})", wrapper, s)))
+}
+
+fn tagged(tag: u64, value: Cbor) -> Cbor {
+    Cbor::Tag(tag, Box::new(value))
+}
+
+/// Fingerprints are unsigned integers of their big endian bytes
+fn fingerprint_to_cbor(fingerprint: Fingerprint) -> Cbor {
+    let value = fingerprint[..]
+        .iter()
+        .fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+    Cbor::Unsigned(value)
+}
+
+fn fingerprint_from_cbor(cbor: &Cbor) -> Result<Fingerprint> {
+    let value = cbor.as_u64()?;
+    if value > u64::from(u32::max_value()) {
+        return Err("invalid fingerprint".into());
+    }
+    Ok(Fingerprint::from(&(value as u32).to_be_bytes()[..]))
+}
+
+fn keypath_to_cbor(path: &[ChildNumber], wildcard: bool, fingerprint: Option<Fingerprint>) -> Cbor {
+    let mut components = vec![];
+    for child in path {
+        let (index, hardened) = match child {
+            ChildNumber::Normal { index } => (*index, false),
+            ChildNumber::Hardened { index } => (*index, true),
+        };
+        components.push(Cbor::Unsigned(u64::from(index)));
+        components.push(Cbor::Bool(hardened));
+    }
+    if wildcard {
+        components.push(Cbor::Array(vec![]));
+        components.push(Cbor::Bool(false));
+    }
+    let mut map = vec![(Cbor::Unsigned(1), Cbor::Array(components))];
+    if let Some(fingerprint) = fingerprint {
+        map.push((Cbor::Unsigned(2), fingerprint_to_cbor(fingerprint)));
+    }
+    tagged(TAG_KEYPATH, Cbor::Map(map))
+}
+
+/// Components of a keypath, the wildcard is an empty array and it is allowed only as last step
+fn keypath_from_cbor(cbor: &Cbor) -> Result<(Vec<ChildNumber>, bool, Option<Fingerprint>)> {
+    let map = cbor.untag(TAG_KEYPATH)?;
+    let components = map
+        .get(1)
+        .ok_or_else(fn_err("keypath without components"))?
+        .as_array()?;
+    if components.len() % 2 != 0 {
+        return Err("keypath components must be pairs".into());
+    }
+    let mut path = vec![];
+    let mut wildcard = false;
+    for pair in components.chunks(2) {
+        if wildcard {
+            return Err("wildcard must be the last keypath component".into());
+        }
+        match &pair[0] {
+            Cbor::Array(empty) if empty.is_empty() => wildcard = true,
+            index => {
+                let index = index.as_u64()?;
+                if index >= 1 << 31 {
+                    return Err("keypath index out of range".into());
+                }
+                let index = index as u32;
+                path.push(if pair[1].as_bool()? {
+                    ChildNumber::from_hardened_idx(index)?
+                } else {
+                    ChildNumber::from_normal_idx(index)?
+                });
+            }
+        }
+    }
+    let fingerprint = map.get(2).map(fingerprint_from_cbor).transpose()?;
+    Ok((path, wildcard, fingerprint))
+}
+
+/// `crypto-hdkey` of a descriptor key, with origin and children derivation
+fn hdkey_to_cbor(key: &DescriptorKey) -> Result<Cbor> {
+    let xpub = &key.xpub.xpub;
+    let mut map = vec![
+        (Cbor::Unsigned(3), Cbor::Bytes(xpub.public_key.to_bytes())),
+        (Cbor::Unsigned(4), Cbor::Bytes(xpub.chain_code[..].to_vec())),
+    ];
+    if xpub.network != Network::Bitcoin {
+        let coininfo = Cbor::Map(vec![(Cbor::Unsigned(2), Cbor::Unsigned(1))]);
+        map.push((Cbor::Unsigned(5), tagged(TAG_COININFO, coininfo)));
+    }
+    let origin = match &key.xpub.origin {
+        Some(origin) => keypath_to_cbor(origin.path.as_ref(), false, Some(origin.fingerprint)),
+        None if xpub.depth == 0 => keypath_to_cbor(&[], false, Some(xpub.fingerprint())),
+        None => return Err(format!("key {} without origin", key).into()),
+    };
+    map.push((Cbor::Unsigned(6), origin));
+    let children = keypath_to_cbor(&key.path, key.wildcard, None);
+    map.push((Cbor::Unsigned(7), children));
+    map.push((
+        Cbor::Unsigned(8),
+        fingerprint_to_cbor(xpub.parent_fingerprint),
+    ));
+    Ok(tagged(TAG_HDKEY, Cbor::Map(map)))
+}
+
+fn hdkey_from_cbor(cbor: &Cbor) -> Result<DescriptorKey> {
+    let map = cbor.untag(TAG_HDKEY)?;
+    if let Some(Cbor::Bool(true)) = map.get(2) {
+        return Err("private keys are not accepted".into());
+    }
+    let key_data = map.get(3).ok_or_else(fn_err("hdkey without key"))?;
+    let public_key = key::PublicKey::from_slice(key_data.as_bytes()?)?;
+    if !public_key.compressed {
+        return Err("hdkey must be a compressed public key".into());
+    }
+    let chain_code = map
+        .get(4)
+        .ok_or_else(fn_err("hdkey without chain code"))?
+        .as_bytes()?;
+    if chain_code.len() != 32 {
+        return Err("invalid hdkey chain code".into());
+    }
+    let network = match map.get(5).map(|c| c.untag(TAG_COININFO)).transpose()? {
+        Some(coininfo) => match coininfo.get(2).map(|n| n.as_u64()).transpose()? {
+            Some(1) => Network::Testnet,
+            Some(0) | None => Network::Bitcoin,
+            Some(n) => return Err(format!("unsupported hdkey network {}", n).into()),
+        },
+        None => Network::Bitcoin,
+    };
+    let (origin_path, _, fingerprint) = match map.get(6) {
+        Some(origin) => keypath_from_cbor(origin)?,
+        None => return Err("hdkey without origin".into()),
+    };
+    let (path, wildcard) = match map.get(7) {
+        Some(children) => {
+            let (path, wildcard, _) = keypath_from_cbor(children)?;
+            (path, wildcard)
+        }
+        None => (vec![], false),
+    };
+    let parent_fingerprint = match map.get(8) {
+        Some(parent) => fingerprint_from_cbor(parent)?,
+        None => Fingerprint::from(&[0u8; 4][..]),
+    };
+    if origin_path.len() > u8::max_value() as usize {
+        return Err("hdkey origin too deep".into());
+    }
+    let xpub = ExtendedPubKey {
+        network,
+        depth: origin_path.len() as u8,
+        parent_fingerprint,
+        child_number: origin_path
+            .last()
+            .cloned()
+            .unwrap_or(ChildNumber::Normal { index: 0 }),
+        public_key,
+        chain_code: ChainCode::from(chain_code),
+    };
+    let origin = match fingerprint {
+        Some(fingerprint) if !origin_path.is_empty() => Some(KeyOrigin {
+            fingerprint,
+            path: origin_path.into(),
+        }),
+        _ => None,
+    };
+    Ok(DescriptorKey {
+        xpub: XpubWithOrigin { origin, xpub },
+        path,
+        wildcard,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::cbor::Cbor;
+    use crate::common::descriptor::WalletDescriptor;
+    use crate::common::fixtures::{ACCOUNT_1_XPUB, ACCOUNT_XPUB};
+    use crate::common::fountain::Xoshiro256;
+    use crate::common::ur::*;
+    use crate::{descriptor, ScriptType};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_bytewords() {
+        assert_eq!(bytewords_encode(&[0, 1, 2, 128, 255]), "aeadaolazmjendeoti");
+        assert_eq!(
+            bytewords_decode("aeadaolazmjendeoti").unwrap(),
+            vec![0, 1, 2, 128, 255]
+        );
+        assert!(bytewords_decode("aeadaolazmjendeotu").is_err());
+        assert!(bytewords_decode("aeadaolazmjendeot").is_err());
+        assert!(bytewords_decode("aeadaolazmjendeoxx").is_err());
+    }
+
+    #[test]
+    fn test_ur_multipart() {
+        // vectors of the BC-UR reference implementation
+        let mut rng = Xoshiro256::new(b"Wolf");
+        let message: Vec<u8> = (0..256).map(|_| rng.next_int(0, 255) as u8).collect();
+        let cbor = Cbor::Bytes(message.clone()).encode();
+        let mut encoder = UrEncoder::new("bytes", &cbor, 30).unwrap();
+        assert_eq!(encoder.seq_len(), 9);
+        let parts: Vec<String> = (0..30).map(|_| encoder.next_part()).collect();
+        assert_eq!(parts[0], "ur:bytes/1-9/lpadascfadaxcywenbpljkhdcahkadaemejtswhhylkepmykhhtsytsnoyoyaxaedsuttydmmhhpktpmsrjtdkgslpgh");
+        assert_eq!(parts[1], "ur:bytes/2-9/lpaoascfadaxcywenbpljkhdcagwdpfnsboxgwlbaawzuefywkdplrsrjynbvygabwjldapfcsgmghhkhstlrdcxaefz");
+        assert_eq!(parts[9], "ur:bytes/10-9/lpbkascfadaxcywenbpljkhdcahkadaemejtswhhylkepmykhhtsytsnoyoyaxaedsuttydmmhhpktpmsrjtwdkiplzs");
+        assert_eq!(parts[11], "ur:bytes/12-9/lpbnascfadaxcywenbpljkhdcarllaluzmdmgstospeyiefmwejlwtpedamktksrvlcygmzemovovllarodtmtbnptrs");
+
+        let mut decoder = UrDecoder::default();
+        for part in parts.iter().skip(3).step_by(2) {
+            decoder.receive(&part.to_uppercase()).unwrap();
+        }
+        assert!(decoder.is_complete());
+        let (ur_type, result) = decoder.result().unwrap();
+        assert_eq!(ur_type, "bytes");
+        assert_eq!(Cbor::decode(result).unwrap(), Cbor::Bytes(message));
+
+        let mut decoder = UrDecoder::default();
+        decoder.receive(&parts[0]).unwrap();
+        assert!(!decoder.is_complete());
+        assert_eq!(decoder.progress(), (1, 9));
+        assert!(decoder
+            .receive(&parts[1].replace("bytes", "crypto-psbt"))
+            .is_err());
+        assert!(decoder.receive(&parts[1].replace("2-9", "3-9")).is_err());
+
+        let psbt = vec![0x70, 0x73, 0x62, 0x74, 0xff];
+        let mut encoder = UrEncoder::new(CRYPTO_PSBT, &psbt_to_cbor(&psbt), 100).unwrap();
+        assert!(encoder.is_single_part());
+        let single = encoder.next_part();
+        assert_eq!(single, "ur:crypto-psbt/fejojkidjyzmfxbtnlwt");
+        let mut decoder = UrDecoder::default();
+        decoder.receive(&single).unwrap();
+        let (ur_type, result) = decoder.result().unwrap();
+        assert_eq!(ur_type, CRYPTO_PSBT);
+        assert_eq!(psbt_from_cbor(result).unwrap(), psbt);
+    }
+
+    #[test]
+    fn test_crypto_output() {
+        for script_type in &["wsh", "sh-wsh", "sh"] {
+            let inner = format!("sortedmulti(2,{}/0/*,{}/0/*)", ACCOUNT_XPUB, ACCOUNT_1_XPUB);
+            let expected = ScriptType::from_str(script_type).unwrap().wrap(&inner);
+            let parsed =
+                WalletDescriptor::from_str(&descriptor::add_checksum(&expected).unwrap()).unwrap();
+            let cbor = output_to_cbor(&parsed).unwrap();
+            assert_eq!(output_from_cbor(&cbor).unwrap(), expected);
+        }
+
+        let single = format!("sh(wpkh({}/0/*))", ACCOUNT_XPUB);
+        let parsed =
+            WalletDescriptor::from_str(&descriptor::add_checksum(&single).unwrap()).unwrap();
+        let cbor = output_to_cbor(&parsed).unwrap();
+        // tags sh(400) and wpkh(404) followed by hdkey(303)
+        assert_eq!(hex::encode(&cbor[..9]), "d90190d90194d9012f");
+        assert_eq!(output_from_cbor(&cbor).unwrap(), single);
+
+        let miniscript = format!("wsh(and_v(v:pk({}/0/*),older(10)))", ACCOUNT_XPUB);
+        let parsed =
+            WalletDescriptor::from_str(&descriptor::add_checksum(&miniscript).unwrap()).unwrap();
+        assert!(output_to_cbor(&parsed).is_err());
+        assert!(output_from_cbor(&psbt_to_cbor(&[1, 2, 3])).is_err());
+    }
+}
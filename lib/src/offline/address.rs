@@ -0,0 +1,174 @@
+use crate::*;
+use bitcoin::{Address, Network};
+use serde::{Deserialize, Serialize};
+use structopt::StructOpt;
+
+/// Verify an address belongs to the wallet by deriving it from the descriptors
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "verify_address")]
+pub struct VerifyAddressOptions {
+    /// Name of the wallet
+    #[structopt(long = "wallet")]
+    pub wallet_name: String,
+
+    /// Address to verify
+    #[structopt(long)]
+    pub address: Address,
+
+    /// Main and change addresses are derived from index 0 up to this index
+    #[structopt(long, default_value = "1000")]
+    pub max_index: u32,
+}
+
+/// Show addresses of the wallet derived from the descriptors
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "show_addresses")]
+pub struct ShowAddressesOptions {
+    /// Name of the wallet
+    #[structopt(long = "wallet")]
+    pub wallet_name: String,
+
+    /// Derivation index of the first address
+    #[structopt(long, default_value = "0")]
+    pub start: u32,
+
+    /// Number of addresses to show
+    #[structopt(long, default_value = "10")]
+    pub count: u32,
+
+    /// Show change addresses instead of main ones
+    #[structopt(long)]
+    #[serde(default)]
+    pub change: bool,
+}
+
+pub fn verify(
+    datadir: &str,
+    network: Network,
+    opt: &VerifyAddressOptions,
+) -> Result<VerifyAddressOutput> {
+    if (network == Network::Bitcoin) != (opt.address.network == Network::Bitcoin) {
+        return Err("address is not on the same network as given".into());
+    }
+    let wallet = read_wallet_by_name(datadir, network, &opt.wallet_name)?;
+    let script_pubkey = opt.address.script_pubkey();
+    let (main, change) = wallet.parse_descriptors()?;
+    for index in 0..=opt.max_index {
+        for (descriptor, change) in [(&main, false), (&change, true)].iter() {
+            let address = descriptor.derive_address(index, network)?;
+            if address.script_pubkey() == script_pubkey {
+                return Ok(VerifyAddressOutput {
+                    wallet: wallet.name,
+                    derived: DerivedAddress {
+                        address,
+                        index,
+                        change: *change,
+                    },
+                });
+            }
+        }
+    }
+    Err(format!(
+        "address {} not found in wallet {} up to index {}",
+        opt.address, wallet.name, opt.max_index
+    )
+    .into())
+}
+
+pub fn show(
+    datadir: &str,
+    network: Network,
+    opt: &ShowAddressesOptions,
+) -> Result<ShowAddressesOutput> {
+    let wallet = read_wallet_by_name(datadir, network, &opt.wallet_name)?;
+    let (main, change) = wallet.parse_descriptors()?;
+    let descriptor = if opt.change { change } else { main };
+    let mut addresses = vec![];
+    for index in opt.start..opt.start.saturating_add(opt.count) {
+        addresses.push(DerivedAddress {
+            address: descriptor.derive_address(index, network)?,
+            index,
+            change: opt.change,
+        });
+    }
+    Ok(ShowAddressesOutput {
+        wallet: wallet.name,
+        addresses,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{self, ACCOUNT_XPUB};
+    use crate::offline::address::{show, verify, ShowAddressesOptions, VerifyAddressOptions};
+    use crate::{Kind, PathBuilder};
+    use bitcoin::{Address, Network};
+    use std::str::FromStr;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_verify_and_show_addresses() {
+        let temp_dir = TempDir::new("test_verify_address").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+
+        let wallet = fixtures::wallet("single", &format!("wpkh({}/0/*)", ACCOUNT_XPUB));
+        let path = PathBuilder::new(
+            &temp_dir_str,
+            Network::Testnet,
+            Kind::Wallet,
+            Some(wallet.name.clone()),
+        )
+        .file("descriptor.json")
+        .unwrap();
+        std::fs::write(&path, serde_json::to_vec(&wallet).unwrap()).unwrap();
+
+        let show_opt = ShowAddressesOptions {
+            wallet_name: "single".to_string(),
+            start: 0,
+            count: 3,
+            change: true,
+        };
+        let shown = show(&temp_dir_str, Network::Testnet, &show_opt).unwrap();
+        assert_eq!(shown.addresses.len(), 3);
+        assert!(shown.addresses.iter().all(|a| a.change));
+        assert_eq!(shown.addresses[2].index, 2);
+
+        let mut opt = VerifyAddressOptions {
+            wallet_name: "single".to_string(),
+            address: shown.addresses[2].address.clone(),
+            max_index: 10,
+        };
+        let verified = verify(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert_eq!(verified.derived, shown.addresses[2]);
+        assert_eq!(verified.wallet, "single");
+
+        opt.address = Address::from_str("tb1q7fa4zp8vxqqry9df9y630zzj0rrkycmj4d3e98").unwrap();
+        let verified = verify(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert_eq!(verified.derived.index, 0);
+        assert!(!verified.derived.change);
+
+        opt.max_index = 1;
+        opt.address = shown.addresses[2].address.clone();
+        let result = verify(&temp_dir_str, Network::Testnet, &opt);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("not found in wallet"));
+
+        let result = verify(&temp_dir_str, Network::Bitcoin, &opt);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "address is not on the same network as given"
+        );
+
+        let tampered = serde_json::to_string(&wallet)
+            .unwrap()
+            .replace("/1/*", "/2/*");
+        std::fs::write(&path, tampered).unwrap();
+        let result = show(&temp_dir_str, Network::Testnet, &show_opt);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("checksum mismatch"));
+    }
+}
@@ -0,0 +1,412 @@
+use crate::common::document::{self, Page};
+use crate::common::list::{self, ListOptions};
+use crate::*;
+use bitcoin::Network;
+use log::warn;
+use qrcode::{EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Words of the mnemonic in every line of the sheet
+const WORDS_PER_LINE: usize = 6;
+
+/// Create a printable backup sheet of a master key, with the secret needed to restore it and the
+/// wallets it is part of
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "backup_sheet")]
+pub struct BackupSheetOptions {
+    /// File containing the master key (PRIVATE.json)
+    #[structopt(short, long = "key", parse(from_os_str))]
+    pub key_file: PathBuf,
+
+    /// Pdf file to create, it contains the secret in clear: keep it out of the firma data directory
+    /// and delete it once printed
+    #[structopt(short, long, parse(from_os_str))]
+    pub output: PathBuf,
+
+    /// Passphrase to decrypt the key file, if encrypted, the command line asks it at the prompt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+}
+
+/// Arguments of the `restore` command recreating the key, the secret is the mnemonic if present,
+/// otherwise the seed in bech32 or the xprv
+pub fn restore_args(key: &PrivateMasterKey) -> Vec<String> {
+    let (nature, value) = match (&key.mnemonic, &key.seed) {
+        (Some(mnemonic), _) => ("mnemonic", mnemonic.words.clone()),
+        (None, Some(seed)) => ("bech32-seed", seed.bech32.clone()),
+        (None, None) => ("xprv", key.xprv.to_string()),
+    };
+    vec![
+        "restore".to_string(),
+        "--key-name".to_string(),
+        key.name.clone(),
+        "--nature".to_string(),
+        nature.to_string(),
+        value,
+    ]
+}
+
+pub fn start(
+    datadir: &str,
+    network: Network,
+    opt: &BackupSheetOptions,
+) -> Result<BackupSheetOutput> {
+    if opt.output.exists() {
+        return Err(Error::FileExist(opt.output.clone()));
+    }
+    let key_file = read_key_file(&opt.key_file)?;
+    if key_file.is_encrypted() {
+        warn!(
+            "The key file is encrypted but the backup sheet {:?} contains the secret in clear",
+            opt.output
+        );
+    }
+    let key = key_file.into_key(opt.encryption_passphrase.as_ref().map(|s| s.as_str()))?;
+    let key_network = key.xpub.network;
+    if key_network != network && !(network == Network::Regtest && key_network == Network::Testnet) {
+        return Err(format!("key is for network {}, not {}", key_network, network).into());
+    }
+
+    let wallets: Vec<WalletJson> = list::list(datadir, network, &ListOptions::new(Kind::Wallet))?
+        .wallets
+        .into_iter()
+        .map(|w| w.wallet)
+        .filter(|w| w.fingerprints.contains(&key.fingerprint))
+        .collect();
+    let wallet_names: Vec<String> = wallets.iter().map(|w| w.name.clone()).collect();
+    let created = key
+        .created_at
+        .map(date)
+        .unwrap_or_else(|| "unknown".to_string());
+    let args = restore_args(&key);
+
+    let mut lines = vec![
+        format!("name: {}", key.name),
+        format!("fingerprint: {}", key.fingerprint),
+        format!("network: {}", network),
+        format!("created: {}", created),
+        String::new(),
+        format!("secret ({}):", args[4]),
+    ];
+    match &key.mnemonic {
+        Some(mnemonic) => {
+            let words: Vec<String> = mnemonic
+                .words
+                .split_whitespace()
+                .enumerate()
+                .map(|(i, word)| format!("{:>2} {:<9}", i + 1, word))
+                .collect();
+            for chunk in words.chunks(WORDS_PER_LINE) {
+                lines.push(chunk.join(" ").trim_end().to_string());
+            }
+            if mnemonic.passphrase_protected {
+                lines.push(
+                    "the mnemonic is protected by a passphrase, restore it with --passphrase"
+                        .to_string(),
+                );
+            }
+        }
+        None => lines.extend(document::wrap(&args[5])),
+    }
+    lines.push(String::new());
+    if wallet_names.is_empty() {
+        lines.push("wallets: none".to_string());
+    } else {
+        lines.push(format!("wallets: {}", wallet_names.join(" ")));
+    }
+    lines.push(String::new());
+    lines.push("restore with:".to_string());
+    let quoted: Vec<String> = args
+        .iter()
+        .map(|arg| {
+            if arg.contains(' ') {
+                format!("\"{}\"", arg)
+            } else {
+                arg.clone()
+            }
+        })
+        .collect();
+    lines.extend(document::wrap(&format!(
+        "firma-offline --network {} {}",
+        network,
+        quoted.join(" ")
+    )));
+
+    let mut pages = vec![Page {
+        title: format!("Master key {}", key.name),
+        lines,
+        qr: QrCode::with_error_correction_level(args[5].as_bytes(), EcLevel::M)?,
+        footer: String::new(),
+    }];
+    for wallet in wallets.iter() {
+        let mut lines = document::wallet_summary(wallet)?;
+        lines.push(String::new());
+        lines.push("main descriptor (in the QR code):".to_string());
+        lines.extend(document::wrap(&wallet.descriptor_main));
+        lines.push("change descriptor:".to_string());
+        lines.extend(document::wrap(&wallet.descriptor_change));
+        lines.push(String::new());
+        if change_is_derived(wallet) {
+            lines.extend(document::wrap(
+                "restore by writing {\"descriptor\": \"<main descriptor>\"} in a file and running:",
+            ));
+            lines.extend(document::wrap(&format!(
+                "firma-offline --network {} import-wallet --wallet-name {} --file <file>",
+                network, wallet.name
+            )));
+        } else {
+            lines.extend(document::wrap(
+                "the change descriptor is not the main one with /1/*, restore the wallet in a \
+                 coordinator accepting both descriptors",
+            ));
+        }
+        pages.push(Page {
+            title: format!("Wallet {}", wallet.name),
+            lines,
+            qr: QrCode::with_error_correction_level(wallet.descriptor_main.as_bytes(), EcLevel::M)?,
+            footer: String::new(),
+        });
+    }
+    let total = pages.len();
+    for (i, page) in pages.iter_mut().enumerate() {
+        page.footer = format!("key {}   page {}/{}", key.fingerprint, i + 1, total);
+    }
+
+    let file = opt.output.clone();
+    document::save_pages(&pages, &file)?;
+
+    Ok(BackupSheetOutput {
+        fingerprint: key.fingerprint,
+        nature: args[4].clone(),
+        created,
+        wallets: wallet_names,
+        file,
+    })
+}
+
+/// True if the change descriptor is the main one with `/1/*` instead of `/0/*`, as derived by
+/// `import-wallet` from the main descriptor only
+fn change_is_derived(wallet: &WalletJson) -> bool {
+    let without_checksum = |d: &str| d.split('#').next().unwrap_or("").to_string();
+    without_checksum(&wallet.descriptor_main).replace("/0/*", "/1/*")
+        == without_checksum(&wallet.descriptor_change)
+}
+
+/// Date of the seconds since epoch as YYYY-MM-DD in UTC
+fn date(seconds: u64) -> String {
+    let (year, month, day) = civil_date(seconds / 86_400);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Gregorian date of the days since 1970-01-01
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468; // days since 0000-03-01
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::ACCOUNT_XPUB;
+    use crate::common::key_origin::XpubWithOrigin;
+    use crate::common::qr::QrEncoding;
+    use crate::offline::backup_sheet::{civil_date, date, restore_args, start, BackupSheetOptions};
+    use crate::offline::create_wallet::{self, CreateWalletOptions, WalletDescriptorOptions};
+    use crate::offline::export_xpub::{self, ExportXpubOptions};
+    use crate::offline::import_wallet::{self, ImportWalletOptions};
+    use crate::offline::random::{self, RandomOptions};
+    use crate::offline::restore::{self, RestoreOptions};
+    use crate::{save_keys, PrivateMasterKey, ScriptType};
+    use bitcoin::Network;
+    use std::str::FromStr;
+    use structopt::StructOpt;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_backup_sheet() {
+        let temp_dir = TempDir::new("test_backup_sheet").unwrap();
+        let datadir = format!("{}/", temp_dir.path().display());
+
+        let mut rand_opts = RandomOptions::new("random".to_string());
+        rand_opts.mnemonic_words = Some(12);
+        let key = random::create_key(&datadir, Network::Testnet, &rand_opts).unwrap();
+        let output = temp_dir.path().join("random.pdf");
+        let opt = BackupSheetOptions {
+            key_file: key.private_file.clone(),
+            output: output.clone(),
+            encryption_passphrase: None,
+        };
+        let result = start(&datadir, Network::Testnet, &opt).unwrap();
+        assert_eq!(result.fingerprint, key.key.fingerprint);
+        assert_eq!(result.nature, "mnemonic");
+        assert_eq!(result.created, date(key.key.created_at.unwrap()));
+        assert!(result.wallets.is_empty());
+        assert_eq!(result.file, output);
+        assert_eq!(
+            start(&datadir, Network::Testnet, &opt)
+                .unwrap_err()
+                .to_string(),
+            format!("file {} already exist", output.display())
+        );
+        let pdf = std::fs::read_to_string(&result.file).unwrap();
+        assert_eq!(pdf.matches("/Type /Page ").count(), 1);
+        let words: Vec<&str> = key
+            .key
+            .mnemonic
+            .as_ref()
+            .unwrap()
+            .words
+            .split(' ')
+            .collect();
+        assert!(pdf.contains(&format!("( 1 {:<9}", words[0])));
+        std::fs::remove_file(&output).unwrap();
+        assert!(start(&datadir, Network::Bitcoin, &opt).is_err());
+
+        // keys created by older versions have no creation time
+        let mut old = key.key.clone();
+        old.created_at = None;
+        let old_file = temp_dir.path().join("old").join("PRIVATE.json");
+        std::fs::create_dir(old_file.parent().unwrap()).unwrap();
+        std::fs::write(&old_file, serde_json::to_string(&old).unwrap()).unwrap();
+        let opt = BackupSheetOptions {
+            key_file: old_file,
+            output: temp_dir.path().join("old.pdf"),
+            encryption_passphrase: None,
+        };
+        assert_eq!(
+            start(&datadir, Network::Testnet, &opt).unwrap().created,
+            "unknown"
+        );
+
+        // the restore command on the sheet recreates the same key
+        let args = restore_args(&key.key);
+        let restore_opt = RestoreOptions::from_iter_safe(args).unwrap();
+        let restored = restore::start(
+            &format!("{}restored/", datadir),
+            Network::Testnet,
+            &restore_opt,
+        )
+        .unwrap();
+        assert_eq!(restored.key.xprv, key.key.xprv);
+
+        // key without mnemonic, part of a wallet
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let bip32 = PrivateMasterKey::new(Network::Testnet, &seed, "bip32").unwrap();
+        let bip32 = save_keys(&datadir, Network::Testnet, "bip32", bip32, 14, None).unwrap();
+        let mut xpub_files = vec![];
+        for key_file in &[&bip32.private_file, &key.private_file] {
+            let xpub_opt = ExportXpubOptions {
+                key_file: key_file.to_path_buf(),
+                account: 0,
+                script_type: ScriptType::Wsh,
+                qr_version: 14,
+                encryption_passphrase: None,
+            };
+            let xpub = export_xpub::start(&datadir, Network::Testnet, &xpub_opt).unwrap();
+            xpub_files.push(xpub.public_file);
+        }
+        let wallet_opt = CreateWalletOptions {
+            wallet_name: "backup-wallet".to_string(),
+            descriptor_opts: WalletDescriptorOptions {
+                r: Some(2),
+                policy: None,
+                unsorted: false,
+                script_type: ScriptType::Wsh,
+                xpubs: vec![],
+                xpub_files,
+            },
+            qr_version: 14,
+            qr_encoding: QrEncoding::StructuredAppend,
+        };
+        create_wallet::start(&datadir, Network::Testnet, &wallet_opt).unwrap();
+
+        let opt = BackupSheetOptions {
+            key_file: bip32.private_file.clone(),
+            output: temp_dir.path().join("bip32.pdf"),
+            encryption_passphrase: None,
+        };
+        let result = start(&datadir, Network::Testnet, &opt).unwrap();
+        assert_eq!(result.nature, "bech32-seed");
+        assert_eq!(result.wallets, vec!["backup-wallet".to_string()]);
+        let pdf = std::fs::read_to_string(&result.file).unwrap();
+        assert_eq!(pdf.matches("/Type /Page ").count(), 2);
+        assert!(pdf.contains("(Wallet backup-wallet) Tj"));
+        assert!(pdf.contains("(key 3442193e   page 2/2) Tj"));
+        assert!(pdf.contains("(change descriptor:) Tj"));
+        assert!(pdf.contains("import-wallet --wallet-name backup-wallet"));
+        assert!(!pdf.contains("import-qr"));
+        let args = restore_args(&bip32.key);
+        assert!(pdf.contains(&args[5]));
+        let restore_opt = RestoreOptions::from_iter_safe(args).unwrap();
+        let restored = restore::start(
+            &format!("{}restored/", datadir),
+            Network::Testnet,
+            &restore_opt,
+        )
+        .unwrap();
+        assert_eq!(restored.key.xprv, bip32.key.xprv);
+
+        // single signature wallets are restored by the import on the sheet too
+        let single_opt = CreateWalletOptions {
+            wallet_name: "backup-single".to_string(),
+            descriptor_opts: WalletDescriptorOptions {
+                r: Some(1),
+                policy: None,
+                unsorted: false,
+                script_type: ScriptType::Wpkh,
+                xpubs: vec![XpubWithOrigin::from_str(ACCOUNT_XPUB).unwrap()],
+                xpub_files: vec![],
+            },
+            qr_version: 14,
+            qr_encoding: QrEncoding::StructuredAppend,
+        };
+        let single = create_wallet::start(&datadir, Network::Testnet, &single_opt).unwrap();
+        let opt = BackupSheetOptions {
+            key_file: bip32.private_file.clone(),
+            output: temp_dir.path().join("bip32-single.pdf"),
+            encryption_passphrase: None,
+        };
+        let result = start(&datadir, Network::Testnet, &opt).unwrap();
+        assert!(result.wallets.contains(&"backup-single".to_string()));
+        let pdf = std::fs::read_to_string(&result.file).unwrap();
+        assert_eq!(pdf.matches("/Type /Page ").count(), 3);
+        assert!(pdf.contains("import-wallet --wallet-name backup-single"));
+        let descriptor_file = temp_dir.path().join("single.json");
+        let content = serde_json::json!({ "descriptor": single.wallet.descriptor_main });
+        std::fs::write(&descriptor_file, content.to_string()).unwrap();
+        let import_opt = ImportWalletOptions {
+            file: descriptor_file,
+            format: None,
+            wallet_name: Some("backup-single".to_string()),
+            script_type: None,
+            qr_version: 14,
+        };
+        let imported = import_wallet::start(
+            &format!("{}restored/", datadir),
+            Network::Testnet,
+            &import_opt,
+        )
+        .unwrap();
+        assert_eq!(imported.wallet, single.wallet);
+
+        assert_eq!(date(1_609_459_199), "2020-12-31");
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(18_628), (2021, 1, 1));
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+    }
+}
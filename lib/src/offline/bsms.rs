@@ -0,0 +1,261 @@
+use crate::common::bsms::KeyRecord;
+use crate::common::coordinator::{self, WalletFormat};
+use crate::common::descriptor::WalletDescriptor;
+use crate::common::qr::QrEncoding;
+use crate::offline::export_xpub::account_key;
+use crate::*;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::bip32::ExtendedPubKey;
+use bitcoin::Network;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Create the BIP129 key record of the BIP48 account xpub, signed with the account key
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "bsms_key_record")]
+pub struct KeyRecordOptions {
+    /// File containing the master key (PRIVATE.json)
+    #[structopt(short, long, parse(from_os_str))]
+    pub key_file: PathBuf,
+
+    /// Account number used in the derivation path
+    #[structopt(long, default_value = "0")]
+    pub account: u32,
+
+    /// Session token given by the coordinator, only `00` is supported since BIP129 encrypted
+    /// records are not
+    #[structopt(long)]
+    pub token: String,
+
+    /// Description of the signer shown to the coordinator, the key name if missing
+    #[structopt(long)]
+    #[serde(default)]
+    pub description: Option<String>,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+
+    /// Passphrase to decrypt the key file, if encrypted, the command line asks it at the prompt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+}
+
+/// Verify the BIP129 descriptor record of the coordinator contains the key and save the wallet
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "bsms_verify")]
+pub struct VerifyDescriptorOptions {
+    /// File containing the master key (PRIVATE.json)
+    #[structopt(short, long, parse(from_os_str))]
+    pub key_file: PathBuf,
+
+    /// Descriptor record created by the coordinator
+    #[structopt(long, parse(from_os_str))]
+    pub descriptor_record: PathBuf,
+
+    /// Name of the wallet
+    #[structopt(long)]
+    pub wallet_name: String,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+
+    /// Passphrase to decrypt the key file, if encrypted, the command line asks it at the prompt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+}
+
+pub fn key_record(
+    datadir: &str,
+    network: Network,
+    opt: &KeyRecordOptions,
+) -> Result<BsmsKeyRecordOutput> {
+    let key = read_key(
+        &opt.key_file,
+        opt.encryption_passphrase.as_ref().map(|s| s.as_str()),
+    )?;
+    let (account_xprv, xpub) = account_key(&key, network, opt.account, ScriptType::Wsh)?;
+    let description = opt.description.as_ref().unwrap_or(&key.name);
+    let key_record = KeyRecord::sign(&opt.token, xpub, &account_xprv, description)?.to_string();
+
+    let path_builder = PathBuilder::new(datadir, network, Kind::Key, Some(key.name.clone()));
+    let subdir = format!("bsms-{}", opt.token);
+    let key_record_file = path_builder.file_with_subdir(Some(&subdir), "key-record.txt")?;
+    info!("Saving key record in {:?}", &key_record_file);
+    fs::write(&key_record_file, &key_record)?;
+    let path_for_qr = path_builder.file_with_subdir(Some(&subdir), "qr")?;
+    let qr_files = qr::save_qrs(key_record.as_bytes().to_vec(), path_for_qr, opt.qr_version)?;
+
+    Ok(BsmsKeyRecordOutput {
+        key_record,
+        key_record_file,
+        qr_files,
+    })
+}
+
+/// The returned first address must be compared with the one shown by the coordinator
+pub fn verify_descriptor(
+    datadir: &str,
+    network: Network,
+    opt: &VerifyDescriptorOptions,
+) -> Result<BsmsVerifyOutput> {
+    let key = read_key(
+        &opt.key_file,
+        opt.encryption_passphrase.as_ref().map(|s| s.as_str()),
+    )?;
+    let content = fs::read_to_string(&opt.descriptor_record)?;
+    // the first address in the record is checked against the derived one during the import
+    let wallet = coordinator::import(&content, WalletFormat::Bsms, Some(&opt.wallet_name), None)?;
+    let descriptor = WalletDescriptor::from_str(&wallet.descriptor_main)?;
+    if !descriptor.is_for_network(network) {
+        return Err(format!("wallet keys are not for network {}", network).into());
+    }
+
+    let secp = Secp256k1::signing_only();
+    let mut found = false;
+    for descriptor_key in descriptor.script.keys() {
+        if let Some(origin) = &descriptor_key.xpub.origin {
+            if origin.fingerprint == key.fingerprint {
+                let xprv = key.xprv.derive_priv(&secp, &origin.path)?;
+                found |= ExtendedPubKey::from_private(&secp, &xprv) == descriptor_key.xpub.xpub;
+            }
+        }
+    }
+    if !found {
+        return Err(format!("descriptor record does not contain key {}", key.name).into());
+    }
+
+    let context = Context {
+        network,
+        wallet_name: wallet.name.clone(),
+        firma_datadir: datadir.to_string(),
+    };
+    let wallet_file = context.save_wallet(&wallet)?;
+    let qr_files =
+        context.save_wallet_qrs(&wallet, opt.qr_version, QrEncoding::StructuredAppend)?;
+
+    Ok(BsmsVerifyOutput {
+        first_address: wallet.derive_address(0, false, network)?,
+        wallet,
+        wallet_file,
+        qr_files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::offline::bsms::{
+        key_record, verify_descriptor, KeyRecordOptions, VerifyDescriptorOptions,
+    };
+    use crate::online::bsms::{finalize, start, BsmsFinalizeOptions, BsmsStartOptions};
+    use crate::{save_keys, Context, PrivateMasterKey, ScriptType};
+    use bitcoin::Network;
+    use std::path::PathBuf;
+    use tempdir::TempDir;
+
+    fn key_file(datadir: &str, name: &str, seed: u8) -> PathBuf {
+        let key = PrivateMasterKey::new(Network::Testnet, &[seed; 32], name).unwrap();
+        save_keys(datadir, Network::Testnet, name, key, 14, None)
+            .unwrap()
+            .private_file
+    }
+
+    #[test]
+    fn test_bsms() {
+        let coordinator_dir = TempDir::new("test_bsms_coordinator").unwrap();
+        let signer_dir = TempDir::new("test_bsms_signer").unwrap();
+        let signer_dir_str = format!("{}/", signer_dir.path().display());
+        let coordinator = Context {
+            network: Network::Testnet,
+            wallet_name: "bsms".to_string(),
+            firma_datadir: format!("{}/", coordinator_dir.path().display()),
+        };
+
+        let start_opt = BsmsStartOptions {
+            r: 2,
+            signers: 2,
+            script_type: ScriptType::Wsh,
+        };
+        let session = start(&coordinator, &start_opt).unwrap().session;
+        assert_eq!(session.token, "00");
+        let result = start(&coordinator, &start_opt);
+        assert!(result.unwrap_err().to_string().contains("already exist"));
+
+        let key_files = vec![
+            key_file(&signer_dir_str, "signer-a", 1),
+            key_file(&signer_dir_str, "signer-b", 2),
+        ];
+        let mut opt = KeyRecordOptions {
+            key_file: key_files[0].clone(),
+            account: 0,
+            token: session.token.clone(),
+            description: None,
+            qr_version: 14,
+            encryption_passphrase: None,
+        };
+        let record_a = key_record(&signer_dir_str, Network::Testnet, &opt).unwrap();
+        assert!(record_a.key_record.contains("\nsigner-a\n"));
+        assert!(!record_a.qr_files.is_empty());
+        opt.key_file = key_files[1].clone();
+        opt.token = "a54044308ceac9b7".to_string();
+        let result = key_record(&signer_dir_str, Network::Testnet, &opt);
+        assert!(result.unwrap_err().to_string().contains("not supported"));
+        opt.token = session.token.clone();
+        let record_b = key_record(&signer_dir_str, Network::Testnet, &opt).unwrap();
+
+        let mut finalize_opt = BsmsFinalizeOptions {
+            key_records: vec![record_a.key_record_file.clone()],
+            qr_version: 14,
+        };
+        let result = finalize(&coordinator, &finalize_opt);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "session requires 2 key records, found 1"
+        );
+        let tampered_file = signer_dir.path().join("tampered-key-record.txt");
+        let tampered = record_b.key_record.replace("signer-b", "signer-c");
+        std::fs::write(&tampered_file, tampered).unwrap();
+        finalize_opt.key_records.push(tampered_file);
+        let result = finalize(&coordinator, &finalize_opt);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("has an invalid signature"));
+        finalize_opt.key_records[1] = record_b.key_record_file.clone();
+        let descriptor = finalize(&coordinator, &finalize_opt).unwrap();
+        assert_eq!(descriptor.wallet.required_sig, Some(2));
+        assert!(descriptor.descriptor_record.starts_with("BSMS 1.0\n"));
+
+        let mut verify_opt = VerifyDescriptorOptions {
+            key_file: key_files[0].clone(),
+            descriptor_record: descriptor.descriptor_record_file.clone(),
+            wallet_name: "bsms".to_string(),
+            qr_version: 14,
+            encryption_passphrase: None,
+        };
+        let verified = verify_descriptor(&signer_dir_str, Network::Testnet, &verify_opt).unwrap();
+        assert_eq!(
+            verified.wallet.descriptor_main,
+            descriptor.wallet.descriptor_main
+        );
+        assert_eq!(
+            descriptor.descriptor_record.lines().last().unwrap(),
+            verified.first_address.to_string()
+        );
+
+        verify_opt.key_file = key_file(&signer_dir_str, "outsider", 3);
+        verify_opt.wallet_name = "bsms-outsider".to_string();
+        let result = verify_descriptor(&signer_dir_str, Network::Testnet, &verify_opt);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "descriptor record does not contain key outsider"
+        );
+    }
+}
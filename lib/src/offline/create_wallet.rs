@@ -0,0 +1,352 @@
+use crate::common::key_origin::XpubWithOrigin;
+use crate::common::qr::QrEncoding;
+use crate::*;
+use bitcoin::Network;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+const POLICY_NOT_SUPPORTED: &str =
+    "--policy wallets are not supported yet, the bitcoin node cannot import miniscript descriptors";
+
+/// Keys and script of the wallet, used to build the descriptors both offline and online
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+pub struct WalletDescriptorOptions {
+    /// number of signatures required
+    #[structopt(short, required_unless = "policy")]
+    #[serde(default)]
+    pub r: Option<usize>,
+
+    /// Spending policy compiled to a miniscript wsh() descriptor instead of multi(),
+    /// keys are referenced by fingerprint, eg. "or(thresh(2,pk(F1),pk(F2)),and(pk(F3),older(26000)))".
+    /// Not supported yet: refused until the bitcoin node can import miniscript descriptors
+    #[structopt(long, conflicts_with = "r")]
+    #[serde(default)]
+    pub policy: Option<String>,
+
+    /// Use multi() with keys in the given order instead of the default sortedmulti() (BIP67)
+    #[structopt(long, conflicts_with = "policy")]
+    #[serde(default)]
+    pub unsorted: bool,
+
+    /// Script type of the wallet: wsh (native segwit), sh-wsh (nested segwit) or sh (legacy)
+    /// multisig, wpkh or sh-wpkh for a single signature wallet of one xpub with -r 1
+    #[structopt(long, default_value = "wsh")]
+    #[serde(default)]
+    pub script_type: ScriptType,
+
+    /// Extended Public Keys (xpub) that are composing the wallet, given as String (xpub...)
+    /// optionally with key origin (eg. [d34db33f/48'/1'/0'/2']tpub...), SLIP-132 Zpub/Vpub accepted
+    #[structopt(long = "xpub")]
+    #[serde(default)]
+    pub xpubs: Vec<XpubWithOrigin>,
+
+    /// Extended Public Keys (xpub) that are composing the wallet, given as a json file
+    #[structopt(long = "xpub-file")]
+    #[serde(default)]
+    pub xpub_files: Vec<PathBuf>,
+}
+
+/// Create a wallet descriptor from xpubs without a bitcoin node,
+/// it can be imported in the node later with `firma-online import-wallet`
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "create_wallet")]
+pub struct CreateWalletOptions {
+    /// Name of the wallet
+    #[structopt(long)]
+    pub wallet_name: String,
+
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub descriptor_opts: WalletDescriptorOptions,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+
+    /// Encoding of data not fitting a single QR code: structured-append (max 16 codes)
+    /// or ur (BC-UR animated QR of any size, readable by mobile wallets, of the main descriptor
+    /// only, the receiver derives the change descriptor with /1/*)
+    #[structopt(long, default_value = "structured-append")]
+    #[serde(default)]
+    pub qr_encoding: QrEncoding,
+}
+
+impl WalletDescriptorOptions {
+    fn validate(&self, network: Network) -> Result<()> {
+        match (self.r, &self.policy) {
+            (Some(_), Some(_)) => return Err("-r and --policy are mutually exclusive".into()),
+            (None, None) => return Err("one of -r or --policy is required".into()),
+            (Some(r), None) => {
+                if r == 0 {
+                    return Err("required signatures cannot be 0".into());
+                }
+
+                if r > 15 {
+                    return Err("required signatures cannot be greater than 15".into());
+                }
+
+                if r > (self.xpubs.len() + self.xpub_files.len()) {
+                    return Err(
+                        "required signatures cannot be greater than the number of xpubs".into(),
+                    );
+                }
+
+                if self.script_type.is_single_sig()
+                    && (r != 1 || self.xpubs.len() + self.xpub_files.len() != 1)
+                {
+                    return Err(
+                        "single signature script types require -r 1 and a single xpub".into(),
+                    );
+                }
+            }
+            (None, Some(_)) => {
+                if self.unsorted {
+                    return Err("--unsorted cannot be used with --policy".into());
+                }
+                if self.script_type == ScriptType::Sh || self.script_type.is_single_sig() {
+                    return Err("--policy requires a segwit script type (wsh or sh-wsh)".into());
+                }
+                // the supported node (0.20) can't import miniscript descriptors, a policy wallet
+                // could receive but the node could never fund a PSBT spending its coins
+                return Err(POLICY_NOT_SUPPORTED.into());
+            }
+        }
+
+        let mut xpubs = read_xpubs_files(&self.xpub_files)?;
+        xpubs.extend(self.xpubs.iter().cloned());
+
+        for xpub in xpubs.iter().map(|x| &x.xpub) {
+            if !(network == xpub.network
+                || (network == Network::Regtest && xpub.network == Network::Testnet))
+            {
+                return Err(format!(
+                    "detected xpub of another network (cmd:{}) (xpub:{})",
+                    network, xpub.network
+                )
+                .into());
+            }
+
+            if xpubs.iter().filter(|xpub2| xpub2.xpub == *xpub).count() > 1 {
+                return Err("Cannot use same xpub twice".into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build the wallet with checksummed main and change descriptors, without daemon options
+    pub fn build(&self, name: &str, network: Network) -> Result<WalletJson> {
+        self.validate(network)?;
+
+        let mut xpubs = read_xpubs_files(&self.xpub_files)?;
+        xpubs.extend(self.xpubs.iter().cloned());
+
+        let mut descriptors = vec![];
+        let required_sig = match (&self.policy, self.r) {
+            (Some(policy), _) => {
+                let compiled = policy::compile(policy, &xpubs)?;
+                let wrap = |descriptor: String| match self.script_type {
+                    ScriptType::ShWsh => format!("sh({})", descriptor),
+                    _ => descriptor,
+                };
+                descriptors.push(wrap(compiled.descriptor_main));
+                descriptors.push(wrap(compiled.descriptor_change));
+                None
+            }
+            (None, Some(_)) if self.script_type.is_single_sig() => {
+                for i in 0..=1 {
+                    let key = format!("{}/{}/*", xpubs[0], i);
+                    descriptors.push(self.script_type.wrap(&key));
+                }
+                Some(1)
+            }
+            (None, Some(r)) => {
+                for i in 0..=1 {
+                    let mut xpub_paths = vec![];
+                    for xpub in xpubs.iter() {
+                        let xpub_path = format!("{}/{}/*", xpub, i);
+                        xpub_paths.push(xpub_path)
+                    }
+                    let multi = if self.unsorted {
+                        "multi"
+                    } else {
+                        "sortedmulti"
+                    };
+                    let inner = format!("{}({},{})", multi, r, xpub_paths.join(","));
+                    let descriptor = self.script_type.wrap(&inner);
+                    descriptors.push(descriptor);
+                }
+                Some(r)
+            }
+            (None, None) => return Err("one of -r or --policy is required".into()),
+        };
+
+        let wallet = WalletJson {
+            name: name.to_string(),
+            descriptor_main: descriptor::add_checksum(&descriptors[0])?,
+            descriptor_change: descriptor::add_checksum(&descriptors[1])?,
+            fingerprints: xpubs.iter().map(|x| x.fingerprint()).collect(),
+            required_sig,
+            policy: self.policy.clone(),
+            sorted: self.policy.is_none() && !self.unsorted && !self.script_type.is_single_sig(),
+            script_type: self.script_type,
+            daemon_opts: None,
+            created_at_height: 0,
+        };
+        wallet.validate()?;
+        Ok(wallet)
+    }
+}
+
+pub fn start(
+    datadir: &str,
+    network: Network,
+    opt: &CreateWalletOptions,
+) -> Result<CreateWalletOutput> {
+    let wallet = opt.descriptor_opts.build(&opt.wallet_name, network)?;
+    let context = Context {
+        network,
+        wallet_name: opt.wallet_name.clone(),
+        firma_datadir: datadir.to_string(),
+    };
+    let wallet_file = context.save_wallet(&wallet)?;
+    let qr_files = context.save_wallet_qrs(&wallet, opt.qr_version, opt.qr_encoding)?;
+    info!("Wallet created offline, import it with firma-online import-wallet");
+
+    Ok(CreateWalletOutput {
+        qr_files,
+        wallet_file,
+        wallet,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{ACCOUNT_1_XPUB, ACCOUNT_XPUB};
+    use crate::common::key_origin::XpubWithOrigin;
+    use crate::common::qr::QrEncoding;
+    use crate::offline::address::{verify, VerifyAddressOptions};
+    use crate::offline::create_wallet::{
+        start, CreateWalletOptions, WalletDescriptorOptions, POLICY_NOT_SUPPORTED,
+    };
+    use crate::{descriptor, read_wallet, ScriptType};
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey};
+    use bitcoin::{Address, Network};
+    use std::str::FromStr;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_create_wallet_offline() {
+        let temp_dir = TempDir::new("test_create_wallet_offline").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+
+        let xpubs = vec![
+            XpubWithOrigin::from_str(ACCOUNT_1_XPUB).unwrap(),
+            XpubWithOrigin::from_str(ACCOUNT_XPUB).unwrap(),
+        ];
+        let mut opt = CreateWalletOptions {
+            wallet_name: "offline".to_string(),
+            descriptor_opts: WalletDescriptorOptions {
+                r: Some(1),
+                policy: None,
+                unsorted: false,
+                script_type: ScriptType::Wsh,
+                xpubs,
+                xpub_files: vec![],
+            },
+            qr_version: 14,
+            qr_encoding: QrEncoding::StructuredAppend,
+        };
+        let result = start(&temp_dir_str, Network::Bitcoin, &opt);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("detected xpub of another network"));
+
+        let created = start(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        let expected = format!(
+            "wsh(sortedmulti(1,{}/0/*,{}/0/*))",
+            ACCOUNT_1_XPUB, ACCOUNT_XPUB
+        );
+        assert_eq!(
+            created.wallet.descriptor_main,
+            descriptor::add_checksum(&expected).unwrap()
+        );
+        assert!(created.wallet.sorted);
+        assert!(created.wallet.daemon_opts.is_none());
+        assert!(!created.qr_files.is_empty());
+        assert_eq!(read_wallet(&created.wallet_file).unwrap(), created.wallet);
+
+        let address = "tb1q02n47zer7q6elg09j9acauc32juz94v7nash0ysty8hhzwa95kpsk0v3cm";
+        let mut verify_opt = VerifyAddressOptions {
+            wallet_name: "offline".to_string(),
+            address: Address::from_str(address).unwrap(),
+            max_index: 10,
+        };
+        let verified = verify(&temp_dir_str, Network::Testnet, &verify_opt).unwrap();
+        assert_eq!(verified.derived.index, 3);
+
+        let result = start(&temp_dir_str, Network::Testnet, &opt);
+        assert!(result.unwrap_err().to_string().contains("already exist"));
+
+        opt.wallet_name = "single".to_string();
+        opt.descriptor_opts.script_type = ScriptType::ShWpkh;
+        assert_eq!(
+            start(&temp_dir_str, Network::Testnet, &opt)
+                .unwrap_err()
+                .to_string(),
+            "single signature script types require -r 1 and a single xpub"
+        );
+        opt.descriptor_opts.xpubs = vec![XpubWithOrigin::from_str(ACCOUNT_XPUB).unwrap()];
+        let created = start(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert!(created.wallet.descriptor_main.starts_with("sh(wpkh("));
+        assert_eq!(created.wallet.script_type, ScriptType::ShWpkh);
+        assert!(!created.wallet.sorted);
+
+        opt.wallet_name = "one-of-one".to_string();
+        opt.descriptor_opts.script_type = ScriptType::Wsh;
+        let created = start(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert!(created
+            .wallet
+            .descriptor_main
+            .starts_with("wsh(sortedmulti(1,"));
+        verify_opt.wallet_name = "single".to_string();
+        verify_opt.address = Address::from_str("2NDrXXsZn5KMfphX49amwV7gCTdNhKpqBo8").unwrap();
+        let verified = verify(&temp_dir_str, Network::Testnet, &verify_opt).unwrap();
+        assert_eq!((verified.derived.index, verified.derived.change), (5, true));
+    }
+
+    #[test]
+    fn test_create_policy_wallet() {
+        let secp = Secp256k1::signing_only();
+        let xpubs: Vec<XpubWithOrigin> = (0..2u8)
+            .map(|i| {
+                let xprv = ExtendedPrivKey::new_master(Network::Testnet, &[i; 32]).unwrap();
+                ExtendedPubKey::from_private(&secp, &xprv).into()
+            })
+            .collect();
+        let policy = format!(
+            "or(pk({}),and(pk({}),older(144)))",
+            xpubs[0].fingerprint(),
+            xpubs[1].fingerprint()
+        );
+        let descriptor_opts = WalletDescriptorOptions {
+            r: None,
+            policy: Some(policy),
+            unsorted: false,
+            script_type: ScriptType::Wsh,
+            xpubs,
+            xpub_files: vec![],
+        };
+        assert_eq!(
+            descriptor_opts
+                .build("policy", Network::Testnet)
+                .unwrap_err()
+                .to_string(),
+            POLICY_NOT_SUPPORTED
+        );
+    }
+}
@@ -1,5 +1,4 @@
-use crate::file::save_keys;
-use crate::offline::sign::read_key;
+use crate::file::{read_key, save_keys};
 use crate::{MasterKeyOutput, PrivateMasterKey};
 use bitcoin::secp256k1::Secp256k1;
 use bitcoin::util::bip32::ChildNumber;
@@ -13,7 +12,7 @@ use structopt::StructOpt;
 pub struct DeriveKeyOptions {
     /// Name of the master^2 key
     #[structopt(short, long)]
-    from_key_file: PathBuf,
+    pub from_key_file: PathBuf,
 
     /// Name of the generated master key, used as path to generate the child key
     #[structopt(short, long)]
@@ -22,6 +21,11 @@ pub struct DeriveKeyOptions {
     /// QR code max version to use (max size)
     #[structopt(long, default_value = "14")]
     pub qr_version: i16,
+
+    /// Passphrase to decrypt the master^2 key file, if encrypted, the derived key is encrypted with the same passphrase.
+    /// The command line asks it at the prompt
+    #[structopt(skip)]
+    pub encryption_passphrase: Option<String>,
 }
 
 pub fn start(
@@ -33,7 +37,8 @@ pub fn start(
         return Err("--to-key-name must have 1 or more characters".into());
     }
     let secp = Secp256k1::signing_only();
-    let from_key_json = read_key(&opt.from_key_file)?;
+    let passphrase = opt.encryption_passphrase.as_ref().map(|s| s.as_str());
+    let from_key_json = read_key(&opt.from_key_file, passphrase)?;
     let mut child_key = from_key_json.xprv;
     let bytes = opt.to_key_name.as_bytes();
     for byte in bytes {
@@ -48,6 +53,7 @@ pub fn start(
         &opt.to_key_name,
         child_key_json,
         opt.qr_version,
+        passphrase,
     )?;
 
     Ok(output)
@@ -75,6 +81,7 @@ mod tests {
             from_key_file: key.private_file.clone(),
             to_key_name,
             qr_version: 14,
+            encryption_passphrase: None,
         };
         let derived =
             crate::offline::derive_key::start(&temp_dir_str, Network::Testnet, &der_opts.clone())
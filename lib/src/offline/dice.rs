@@ -28,6 +28,27 @@ pub struct DiceOptions {
     /// QR code max version to use (max size)
     #[structopt(long, default_value = "14")]
     pub qr_version: i16,
+
+    /// Encode the entropy as a bip39 mnemonic (12, 18 or 24 words for 128, 192 or 256 bits)
+    #[structopt(long)]
+    mnemonic: bool,
+
+    /// Protect the mnemonic with a bip39 passphrase asked at the prompt, used only with
+    /// --mnemonic
+    #[structopt(long)]
+    pub passphrase: bool,
+
+    /// bip39 passphrase, the command line asks it with --passphrase
+    #[structopt(skip)]
+    pub mnemonic_passphrase: Option<String>,
+
+    /// Encrypt the private key file with a passphrase asked at the prompt
+    #[structopt(long)]
+    pub encrypt: bool,
+
+    /// Passphrase to encrypt the private key file, the command line asks it with --encrypt
+    #[structopt(skip)]
+    pub encryption_passphrase: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -68,6 +89,10 @@ impl DiceOptions {
             return Err(format!("Numbers must be from 1 to {} included", faces).into());
         }
 
+        if self.passphrase && !self.mnemonic {
+            return Err("--passphrase requires --mnemonic".into());
+        }
+
         Ok(())
     }
 }
@@ -75,8 +100,27 @@ impl DiceOptions {
 pub fn roll(datadir: &str, network: Network, opt: &DiceOptions) -> Result<MasterKeyOutput> {
     opt.validate()?;
 
-    let master_key = calculate_key(&opt.launches, opt.faces as u32, network, &opt.key_name)?;
-    let output = save_keys(datadir, network, &opt.key_name, master_key, opt.qr_version)?;
+    let master_key = if opt.mnemonic {
+        let passphrase = opt.mnemonic_passphrase.as_ref().map(|s| s.as_str());
+        calculate_mnemonic_key(
+            &opt.launches,
+            opt.faces as u32,
+            &opt.bits,
+            passphrase,
+            network,
+            &opt.key_name,
+        )?
+    } else {
+        calculate_key(&opt.launches, opt.faces as u32, network, &opt.key_name)?
+    };
+    let output = save_keys(
+        datadir,
+        network,
+        &opt.key_name,
+        master_key,
+        opt.qr_version,
+        opt.encryption_passphrase.as_ref().map(|s| s.as_str()),
+    )?;
 
     Ok(output)
 }
@@ -123,6 +167,42 @@ fn calculate_key(
     Ok(key)
 }
 
+fn calculate_mnemonic_key(
+    launches: &[u32],
+    faces: u32,
+    bits: &Bits,
+    passphrase: Option<&str>,
+    network: Network,
+    name: &str,
+) -> Result<PrivateMasterKey> {
+    let acc = multiply_dice_launches(&launches, faces);
+
+    // entropy must have the exact length required by the mnemonic, so left pad with zeros
+    let bytes = acc.to_bytes_be();
+    let mut entropy = vec![0u8; bits.bytes() - bytes.len()];
+    entropy.extend(bytes);
+
+    let mut key = PrivateMasterKey::from_entropy_mnemonic(network, &entropy, passphrase, name)?;
+    let dice = Dice {
+        faces,
+        launches: format!("{:?}", launches),
+        value: acc.to_string(),
+    };
+    key.dice = Some(dice);
+
+    Ok(key)
+}
+
+impl Bits {
+    fn bytes(&self) -> usize {
+        match self {
+            Bits::_128 => 16,
+            Bits::_192 => 24,
+            Bits::_256 => 32,
+        }
+    }
+}
+
 impl From<Bits> for BigUint {
     fn from(bits: Bits) -> Self {
         let one = BigUint::from(1u32);
@@ -188,6 +268,11 @@ mod tests {
             key_name: "a".to_string(),
             launches,
             qr_version: 14,
+            mnemonic: false,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
         };
 
         roll(&temp_dir_str, Network::Testnet, &opt).unwrap();
@@ -291,4 +376,29 @@ mod tests {
         );
         assert_eq!(calculated, expected);
     }
+
+    #[test]
+    fn test_mnemonic_from_dice() {
+        // all launches at the minimum value give zero entropy
+        let calculated = calculate_mnemonic_key(
+            &vec![1; 29],
+            20,
+            &Bits::_128,
+            Some("TREZOR"),
+            Network::Bitcoin,
+            "name",
+        )
+        .unwrap();
+        assert_eq!(
+            calculated.mnemonic.unwrap().words,
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about"
+        );
+        assert_eq!(calculated.xprv.to_string(), "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF");
+        assert_eq!(calculated.dice.unwrap().value, "0");
+
+        let calculated =
+            calculate_mnemonic_key(&vec![1; 99], 6, &Bits::_256, None, Network::Bitcoin, "name")
+                .unwrap();
+        assert_eq!(calculated.mnemonic.unwrap().words.split(' ').count(), 24);
+    }
 }
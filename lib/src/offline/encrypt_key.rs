@@ -0,0 +1,81 @@
+use crate::common::encryption::{EncryptedPrivateKey, PrivateKeyFile};
+use crate::*;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Encrypt an existing plaintext private key file
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "encrypt_key")]
+pub struct EncryptKeyOptions {
+    /// File containing the master key in clear (PRIVATE.json)
+    #[structopt(short, long, parse(from_os_str))]
+    pub key_file: PathBuf,
+
+    /// Passphrase used to encrypt the key file, the command line asks it at the prompt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: String,
+}
+
+pub fn start(opt: &EncryptKeyOptions) -> Result<EncryptKeyOutput> {
+    let key = match read_key_file(&opt.key_file)? {
+        PrivateKeyFile::Encrypted(_) => return Err("key file is already encrypted".into()),
+        PrivateKeyFile::Plain(key) => key,
+    };
+    let encrypted = EncryptedPrivateKey::encrypt(&key, &opt.encryption_passphrase)?;
+    if encrypted.decrypt(&opt.encryption_passphrase)? != key {
+        return Err("decrypted key does not match the original".into());
+    }
+
+    // write in a temporary file and then rename, so that the original is never lost
+    let mut temp_file = opt.key_file.clone();
+    temp_file.set_file_name("PRIVATE.json.tmp");
+    fs::write(&temp_file, serde_json::to_string_pretty(&encrypted)?)?;
+    fs::rename(&temp_file, &opt.key_file)?;
+    info!("Encrypted key saved in {:?}", &opt.key_file);
+
+    Ok(EncryptKeyOutput {
+        private_file: opt.key_file.clone(),
+        fingerprint: key.fingerprint,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::offline::encrypt_key::{start, EncryptKeyOptions};
+    use crate::offline::random::RandomOptions;
+    use crate::read_key;
+    use bitcoin::Network;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_encrypt_key() {
+        let temp_dir = TempDir::new("test_encrypt_key").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+
+        let rand_opts = RandomOptions::new("encrypt".to_string());
+        let key = crate::offline::random::create_key(&temp_dir_str, Network::Testnet, &rand_opts)
+            .unwrap();
+
+        let opt = EncryptKeyOptions {
+            key_file: key.private_file.clone(),
+            encryption_passphrase: "passphrase".to_string(),
+        };
+        let result = start(&opt).unwrap();
+        assert_eq!(result.fingerprint, key.key.fingerprint);
+
+        assert!(read_key(&key.private_file, None).is_err());
+        assert!(read_key(&key.private_file, Some("wrong")).is_err());
+        let decrypted = read_key(&key.private_file, Some("passphrase")).unwrap();
+        assert_eq!(decrypted, key.key);
+
+        let result = start(&opt);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "key file is already encrypted"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use crate::common::coordinator::{self, WalletFormat};
+use crate::*;
+use bitcoin::Network;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Export the wallet to register it on hardware signers and watch-only apps
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "export_wallet")]
+pub struct ExportWalletOptions {
+    /// Name of the wallet
+    #[structopt(long = "wallet")]
+    pub wallet_name: String,
+
+    /// Format of the export: coldcard, specter, sparrow (electrum) or bsms
+    #[structopt(long)]
+    pub format: WalletFormat,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+}
+
+/// Render the wallet in the given format and save it with its QR codes in the wallet directory
+pub fn start(
+    datadir: &str,
+    network: Network,
+    opt: &ExportWalletOptions,
+) -> Result<ExportWalletOutput> {
+    let wallet = read_wallet_by_name(datadir, network, &opt.wallet_name)?;
+    let context = Context {
+        network,
+        wallet_name: wallet.name.clone(),
+        firma_datadir: datadir.to_string(),
+    };
+    let content = coordinator::export(&wallet, opt.format, network)?;
+    let (export_file, qr_files) = save_export(&context, opt.format, &content, opt.qr_version)?;
+
+    Ok(ExportWalletOutput {
+        format: opt.format,
+        content,
+        export_file,
+        qr_files,
+    })
+}
+
+/// Save the exported wallet and its QR codes in the wallet directory
+pub(crate) fn save_export(
+    context: &Context,
+    format: WalletFormat,
+    content: &str,
+    qr_version: i16,
+) -> Result<(PathBuf, Vec<PathBuf>)> {
+    let extension = match format {
+        WalletFormat::Coldcard | WalletFormat::Bsms => "txt",
+        WalletFormat::Specter | WalletFormat::Sparrow => "json",
+    };
+    let format = format.to_string();
+    let export_file = context.filename_for_wallet(&format!("{}.{}", format, extension))?;
+    info!("Saving {} export in {:?}", format, &export_file);
+    fs::write(&export_file, content)?;
+
+    let qr_path = PathBuilder::new(
+        &context.firma_datadir,
+        context.network,
+        Kind::Wallet,
+        Some(context.wallet_name.clone()),
+    )
+    .file_with_subdir(Some(&format!("qr-{}", format)), "filename")?;
+    let qr_files = qr::save_qrs(content.as_bytes().to_vec(), qr_path, qr_version)?;
+
+    Ok((export_file, qr_files))
+}
@@ -0,0 +1,161 @@
+use crate::common::key_origin::{bip48_path, KeyOrigin, XpubWithOrigin};
+use crate::*;
+use bitcoin::secp256k1::Secp256k1;
+use bitcoin::util::bip32::{ExtendedPrivKey, ExtendedPubKey};
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Export the BIP48 account xpub (m/48'/coin'/account'/script') with key origin, for multisig
+/// p2wsh (script 2') or p2sh-p2wsh (script 1')
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "export_xpub")]
+pub struct ExportXpubOptions {
+    /// File containing the master key (PRIVATE.json)
+    #[structopt(short, long, parse(from_os_str))]
+    pub key_file: PathBuf,
+
+    /// Account number used in the derivation path
+    #[structopt(long, default_value = "0")]
+    pub account: u32,
+
+    /// Script type of the multisig wallets using the key: wsh or sh-wsh, which use different
+    /// BIP48 derivation paths
+    #[structopt(long, default_value = "wsh")]
+    #[serde(default)]
+    pub script_type: ScriptType,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+
+    /// Passphrase to decrypt the key file, if encrypted, the command line asks it at the prompt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+}
+
+/// BIP48 account private key and its xpub with key origin
+pub(crate) fn account_key(
+    key: &PrivateMasterKey,
+    network: Network,
+    account: u32,
+    script_type: ScriptType,
+) -> Result<(ExtendedPrivKey, XpubWithOrigin)> {
+    let secp = Secp256k1::signing_only();
+    let path = bip48_path(network, account, script_type)?;
+    let account_xprv = key.xprv.derive_priv(&secp, &path)?;
+    let xpub = XpubWithOrigin {
+        origin: Some(KeyOrigin {
+            fingerprint: key.fingerprint,
+            path,
+        }),
+        xpub: ExtendedPubKey::from_private(&secp, &account_xprv),
+    };
+    Ok((account_xprv, xpub))
+}
+
+pub fn start(datadir: &str, network: Network, opt: &ExportXpubOptions) -> Result<ExportXpubOutput> {
+    let key = read_key(
+        &opt.key_file,
+        opt.encryption_passphrase.as_ref().map(|s| s.as_str()),
+    )?;
+    let (_, xpub) = account_key(&key, network, opt.account, opt.script_type)?;
+
+    let path_builder = PathBuilder::new(datadir, network, Kind::Key, Some(key.name.clone()));
+    let subdir = match opt.script_type {
+        ScriptType::Wsh => format!("account-{}", opt.account),
+        script_type => format!("account-{}-{}", opt.account, script_type.to_string()),
+    };
+    let public_file = path_builder.file_with_subdir(Some(&subdir), "public.json")?;
+    let public_key = PublicMasterKey {
+        xpub: xpub.xpub,
+        key_origin: xpub.origin.clone(),
+    };
+    save_public(&public_key, &public_file)?;
+
+    let path_for_qr = path_builder.file_with_subdir(Some(&subdir), "qr")?;
+    let public_qr_files = qr::save_qrs(
+        xpub.to_string().as_bytes().to_vec(),
+        path_for_qr,
+        opt.qr_version,
+    )?;
+
+    Ok(ExportXpubOutput {
+        slip132: xpub.to_slip132_for(opt.script_type),
+        xpub,
+        public_file,
+        public_qr_files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{ACCOUNT_VPUB, ACCOUNT_XPUB};
+    use crate::common::key_origin::XpubWithOrigin;
+    use crate::offline::export_xpub::{start, ExportXpubOptions};
+    use crate::{save_keys, PrivateMasterKey, PublicMasterKey, ScriptType};
+    use bitcoin::Network;
+    use std::str::FromStr;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_export_xpub() {
+        let temp_dir = TempDir::new("test_export_xpub").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let key = PrivateMasterKey::new(Network::Testnet, &seed, "bip32-vector-1").unwrap();
+        let key = save_keys(
+            &temp_dir_str,
+            Network::Testnet,
+            "bip32-vector-1",
+            key,
+            14,
+            None,
+        )
+        .unwrap();
+
+        let mut opt = ExportXpubOptions {
+            key_file: key.private_file.clone(),
+            account: 0,
+            script_type: ScriptType::Wsh,
+            qr_version: 14,
+            encryption_passphrase: None,
+        };
+        let result = start(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert_eq!(result.xpub.to_string(), ACCOUNT_XPUB);
+        assert_eq!(result.slip132, ACCOUNT_VPUB);
+        assert_eq!(result.xpub.fingerprint(), key.key.fingerprint);
+
+        let content = std::fs::read(&result.public_file).unwrap();
+        let public: PublicMasterKey = serde_json::from_slice(&content).unwrap();
+        let from_file: XpubWithOrigin = public.into();
+        assert_eq!(from_file, XpubWithOrigin::from_str(ACCOUNT_XPUB).unwrap());
+
+        let result = start(&temp_dir_str, Network::Testnet, &opt);
+        assert!(result.unwrap_err().to_string().contains("already exist"));
+
+        opt.account = 1;
+        let result = start(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert!(result
+            .xpub
+            .to_string()
+            .starts_with("[3442193e/48'/1'/1'/2']"));
+
+        // nested segwit multisig uses the 1' script index and the Upub version bytes
+        opt.account = 0;
+        opt.script_type = ScriptType::ShWsh;
+        let result = start(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert!(result
+            .xpub
+            .to_string()
+            .starts_with("[3442193e/48'/1'/0'/1']"));
+        assert!(result.slip132.starts_with("Upub"));
+        assert!(result.public_file.ends_with("account-0-sh-wsh/public.json"));
+
+        opt.script_type = ScriptType::Wpkh;
+        assert!(start(&temp_dir_str, Network::Testnet, &opt).is_err());
+    }
+}
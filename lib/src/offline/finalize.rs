@@ -0,0 +1,270 @@
+use crate::common::signatures;
+use crate::*;
+use bitcoin::blockdata::script::Builder;
+use bitcoin::consensus::serialize;
+use bitcoin::hashes::{hash160, Hash};
+use bitcoin::util::psbt::Input;
+use bitcoin::Script;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Combine signed PSBTs and finalize them in a transaction ready to be broadcast, without a node
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "finalize")]
+pub struct FinalizeOptions {
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+
+    /// PSBT json files of the same transaction, signed by different keys
+    #[structopt(required = true)]
+    pub psbt_files: Vec<PathBuf>,
+}
+
+/// Merge the PSBTs of the same unsigned transaction, as the BIP174 combiner
+pub fn combine(psbts: Vec<PSBT>) -> Result<PSBT> {
+    let mut iter = psbts.into_iter();
+    let mut combined = iter.next().ok_or_else(fn_err("no PSBT to combine"))?;
+    for psbt in iter {
+        if psbt.global.unsigned_tx != combined.global.unsigned_tx {
+            return Err("PSBTs to combine have a different unsigned transaction".into());
+        }
+        combined.merge(psbt)?;
+    }
+    Ok(combined)
+}
+
+/// Build the final script sig and witness of every input from the verified partial signatures,
+/// as the BIP174 finalizer, supported inputs are single key (p2pkh, p2wpkh) and multisig (p2sh,
+/// p2wsh) also when wrapped in p2sh
+pub fn finalize(psbt: &mut PSBT) -> Result<()> {
+    for i in 0..psbt.inputs.len() {
+        let input = &psbt.inputs[i];
+        if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+            continue;
+        }
+        signatures::verify_partial_sigs(psbt, i)?;
+        let script_code = signatures::script_code(psbt, i)?;
+        let stack = satisfy(input, i, &script_code)?;
+
+        let (final_script_sig, final_script_witness) = match &input.witness_utxo {
+            Some(_) => {
+                let script_sig = input
+                    .redeem_script
+                    .as_ref()
+                    .map(|redeem_script| Builder::new().push_slice(redeem_script.as_bytes()));
+                (script_sig.map(Builder::into_script), Some(stack))
+            }
+            None => {
+                let builder = stack
+                    .iter()
+                    .fold(Builder::new(), |builder, el| builder.push_slice(el));
+                (Some(builder.into_script()), None)
+            }
+        };
+        psbt.inputs[i] = Input {
+            non_witness_utxo: input.non_witness_utxo.clone(),
+            witness_utxo: input.witness_utxo.clone(),
+            final_script_sig,
+            final_script_witness,
+            unknown: input.unknown.clone(),
+            ..Default::default()
+        };
+    }
+    Ok(())
+}
+
+/// Elements satisfying the script code, the witness stack or the pushes of the script sig
+fn satisfy(input: &Input, input_index: usize, script_code: &Script) -> Result<Vec<Vec<u8>>> {
+    if script_code.is_p2pkh() {
+        let pubkey_hash = &script_code.as_bytes()[3..23];
+        for (key, signature) in input.partial_sigs.iter() {
+            if hash160::Hash::hash(&key.to_bytes())[..] == *pubkey_hash {
+                return Ok(vec![signature.clone(), key.to_bytes()]);
+            }
+        }
+        return Err(format!("input {} has no signature of its key", input_index).into());
+    }
+
+    let keys = extract_pub_keys(script_code)?;
+    let required = expected_signatures(script_code)?;
+    if required == 0 || *script_code != multisig_script(required, &keys, false) {
+        return Err(format!(
+            "input {} script is not supported, only single key and multisig",
+            input_index
+        )
+        .into());
+    }
+    // CHECKMULTISIG pops an extra element and needs the signatures in the order of the keys
+    let mut stack = vec![vec![]];
+    stack.extend(
+        keys.iter()
+            .filter_map(|key| input.partial_sigs.get(key).cloned())
+            .take(required),
+    );
+    let signatures = stack.len() - 1;
+    if signatures < required {
+        return Err(format!(
+            "input {} has {} of the {} required signatures",
+            input_index, signatures, required
+        )
+        .into());
+    }
+    stack.push(script_code.to_bytes());
+    Ok(stack)
+}
+
+pub fn start(opt: &FinalizeOptions) -> Result<FinalizeOutput> {
+    let psbts = opt
+        .psbt_files
+        .iter()
+        .map(|path| read_psbt(path))
+        .collect::<Result<Vec<_>>>()?;
+    let mut psbt = combine(psbts)?;
+    finalize(&mut psbt)?;
+    let tx = psbt.extract_tx();
+    let hex = hex::encode(serialize(&tx));
+    let txid = tx.txid();
+
+    let psbt_dir = opt.psbt_files[0].parent().ok_or_else(fn_err("no parent"))?;
+    let psbt_name = psbt_dir
+        .file_name()
+        .ok_or_else(fn_err("no name"))?
+        .to_str()
+        .ok_or_else(fn_err("OsStr"))?;
+    let mut file = psbt_dir.to_path_buf();
+    file.set_file_name(format!("{}-final", psbt_name));
+    if !file.exists() {
+        fs::create_dir(&file)?;
+    }
+    let mut qr_path = file.clone();
+    file.push("tx.hex");
+    info!("Saving transaction {} in {:?}", txid, &file);
+    fs::write(&file, &hex)?;
+
+    qr_path.push("qr");
+    if !qr_path.exists() {
+        fs::create_dir(&qr_path)?;
+    }
+    qr_path.push("filename");
+    let qr_files = qr::save_qrs(hex.clone().into_bytes(), qr_path, opt.qr_version)?;
+
+    Ok(FinalizeOutput {
+        txid: txid.to_string(),
+        hex,
+        file,
+        qr_files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::offline::finalize::{combine, finalize, start, FinalizeOptions};
+    use crate::{psbt_from_base64, PsbtJson, PSBT};
+    use bitcoin::consensus::serialize;
+    use tempdir::TempDir;
+
+    fn from_json(bytes: &[u8]) -> PSBT {
+        let psbt_json: PsbtJson = serde_json::from_slice(bytes).unwrap();
+        psbt_from_base64(&psbt_json.psbt).unwrap().1
+    }
+
+    #[test]
+    fn test_finalize_bip() {
+        let mut psbt = from_json(include_bytes!("../../test_data/sign/psbt_bip.signed.json"));
+        finalize(&mut psbt).unwrap();
+        assert!(psbt.inputs.iter().all(|i| i.partial_sigs.is_empty()));
+        assert!(psbt.inputs[0].final_script_witness.is_none());
+        assert_eq!(
+            psbt.inputs[1].final_script_witness.as_ref().unwrap().len(),
+            4
+        );
+        let tx = psbt.extract_tx();
+        // extracted transaction of the BIP174 test vector
+        let expected = "0200000000010258e87a21b56daf0c23be8e7070456c336f7cbaa5c8757924f545887bb2abdd7500000000da00473044022074018ad4180097b873323c0015720b3684cc8123891048e7dbcd9b55ad679c99022073d369b740e3eb53dcefa33823c8070514ca55a7dd9544f157c167913261118c01483045022100f61038b308dc1da865a34852746f015772934208c6d24454393cd99bdf2217770220056e675a675a6d0a02b85b14e5e29074d8a25a9b5760bea2816f661910a006ea01475221029583bf39ae0a609747ad199addd634fa6108559d6c5cd39b4c2183f1ab96e07f2102dab61ff49a14db6a7d02b0cd1fbb78fc4b18312b5b4e54dae4dba2fbfef536d752aeffffffff838d0427d0ec650a68aa46bb0b098aea4422c071b2ca78352a077959d07cea1d01000000232200208c2353173743b595dfb4a07b72ba8e42e3797da74e87fe7d9d7497e3b2028903ffffffff0270aaf00800000000160014d85c2b71d0060b09c9886aeb815e50991dda124d00e1f5050000000016001400aea9a2e5f0f876a588df5546e8742d1d87008f000400473044022062eb7a556107a7c73f45ac4ab5a1dddf6f7075fb1275969a7f383efff784bcb202200c05dbb7470dbf2f08557dd356c7325c1ed30913e996cd3840945db12228da5f01473044022065f45ba5998b59a27ffe1a7bed016af1f1f90d54b3aa8f7450aa5f56a25103bd02207f724703ad1edb96680b284b56d4ffcb88f7fb759eabbe08aa30f29b851383d20147522103089dc10c7ac6db54f91329af617333db388cead0c231f723379d1b99030b02dc21023add904f3d6dcf59ddb906b0dee23529b7ffb9ed50e5e86151926860221f0e7352ae00000000";
+        assert_eq!(hex::encode(serialize(&tx)), expected);
+
+        let mut unsigned = from_json(include_bytes!("../../test_data/sign/psbt_bip.json"));
+        assert_eq!(
+            finalize(&mut unsigned).unwrap_err().to_string(),
+            "input 0 has 0 of the 2 required signatures"
+        );
+    }
+
+    #[test]
+    fn test_combine_and_finalize() {
+        let first = from_json(include_bytes!(
+            "../../test_data/sign/psbt_testnet.1.signed.json"
+        ));
+        let second = from_json(include_bytes!(
+            "../../test_data/sign/psbt_testnet.2.signed.json"
+        ));
+        let signed = from_json(include_bytes!(
+            "../../test_data/sign/psbt_testnet.signed.json"
+        ));
+
+        let mut partial = first.clone();
+        assert_eq!(
+            finalize(&mut partial).unwrap_err().to_string(),
+            "input 0 has 1 of the 2 required signatures"
+        );
+
+        let mut combined = combine(vec![first.clone(), second]).unwrap();
+        assert_eq!(combined, combine(vec![signed]).unwrap());
+        finalize(&mut combined).unwrap();
+        let witness = combined.inputs[0].final_script_witness.clone().unwrap();
+        assert_eq!(witness.len(), 4);
+        assert!(witness[0].is_empty());
+        assert!(combined.inputs[0].final_script_sig.is_none());
+
+        let other = from_json(include_bytes!("../../test_data/sign/psbt_bip.json"));
+        assert!(combine(vec![first, other]).is_err());
+        assert!(combine(vec![]).is_err());
+
+        // a finalized PSBT is left unchanged
+        let mut finalized = combined.clone();
+        finalize(&mut finalized).unwrap();
+        assert_eq!(finalized, combined);
+    }
+
+    #[test]
+    fn test_finalize_files() {
+        let temp_dir = TempDir::new("test_finalize_files").unwrap();
+        let mut psbt_files = vec![];
+        for (i, bytes) in [
+            &include_bytes!("../../test_data/sign/psbt_testnet.1.signed.json")[..],
+            &include_bytes!("../../test_data/sign/psbt_testnet.2.signed.json")[..],
+        ]
+        .iter()
+        .enumerate()
+        {
+            let mut path = temp_dir.path().to_path_buf();
+            path.push(format!("psbt-{}", i));
+            std::fs::create_dir(&path).unwrap();
+            path.push("psbt.json");
+            std::fs::write(&path, bytes).unwrap();
+            psbt_files.push(path);
+        }
+        let opt = FinalizeOptions {
+            qr_version: 14,
+            psbt_files,
+        };
+        let result = start(&opt).unwrap();
+        assert_eq!(
+            result.txid,
+            "38b764d2db824cccc14ed663fa367ecca36ca8f5ccaf27252f6c00ea25b71e91"
+        );
+        assert!(result.file.ends_with("psbt-0-final/tx.hex"));
+        assert_eq!(std::fs::read_to_string(&result.file).unwrap(), result.hex);
+        assert!(!result.qr_files.is_empty());
+
+        let opt = FinalizeOptions {
+            qr_version: 14,
+            psbt_files: vec![opt.psbt_files[0].clone()],
+        };
+        assert!(start(&opt).is_err());
+    }
+}
@@ -0,0 +1,434 @@
+use crate::common::coordinator;
+use crate::common::descriptor::WalletDescriptor;
+use crate::common::key_origin::XpubWithOrigin;
+use crate::common::qr::{self, QrEncoding};
+use crate::common::qr_reader::read_qr;
+use crate::common::transfer;
+use crate::common::ur::{self, UrDecoder};
+use crate::*;
+use bitcoin::consensus::deserialize;
+use bitcoin::{Amount, Network};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Import a PSBT, a wallet or an xpub from the images of its QR codes
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "import_qr")]
+pub struct ImportQrOptions {
+    /// QR code images (png) or directories containing them, all the parts of a single item
+    #[structopt(parse(from_os_str), required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Name of the imported item, required for descriptors, defaults to the txid for raw PSBTs
+    /// and to the fingerprint for xpubs
+    #[structopt(long)]
+    #[serde(default)]
+    pub name: Option<String>,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+}
+
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+
+/// Content of the QR codes, merged structured append parts or decoded UR parts
+enum QrContent {
+    Bytes(Vec<u8>),
+    Ur(String, Vec<u8>),
+}
+
+enum Payload {
+    Psbt(PsbtJson),
+    Wallet(WalletJson),
+    Xpub(PublicMasterKey),
+}
+
+pub fn start(datadir: &str, network: Network, opt: &ImportQrOptions) -> Result<ImportQrOutput> {
+    let images = image_files(&opt.files)?;
+    let name = opt.name.as_ref().map(|s| s.as_str());
+    let payload = match read_content(&images)? {
+        QrContent::Bytes(bytes) => detect(&bytes, name)?,
+        QrContent::Ur(ur_type, cbor) => match ur_type.as_str() {
+            ur::CRYPTO_PSBT => raw_psbt(ur::psbt_from_cbor(&cbor)?, name)?,
+            ur::CRYPTO_OUTPUT => wallet_of_descriptor(&ur::output_from_cbor(&cbor)?, name)?,
+            _ => return Err(format!("unsupported ur type {}", ur_type).into()),
+        },
+    };
+
+    match payload {
+        Payload::Psbt(psbt) => save_psbt(datadir, network, psbt, opt.qr_version),
+        Payload::Wallet(wallet) => save_wallet(datadir, network, wallet, opt.qr_version),
+        Payload::Xpub(key) => save_xpub(datadir, network, key, name, opt.qr_version),
+    }
+}
+
+/// The given files and the png files in the given directories, sorted
+fn image_files(files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut images = vec![];
+    for file in files {
+        if file.is_dir() {
+            for entry in fs::read_dir(file)? {
+                let path = entry?.path();
+                if path.extension().map(|e| e == "png").unwrap_or(false) {
+                    images.push(path);
+                }
+            }
+        } else {
+            images.push(file.clone());
+        }
+    }
+    if images.is_empty() {
+        return Err("no qr code images found".into());
+    }
+    images.sort();
+    Ok(images)
+}
+
+fn read_content(images: &[PathBuf]) -> Result<QrContent> {
+    let mut structured = vec![];
+    let mut singles = vec![];
+    let mut ur_decoder = UrDecoder::default();
+    let mut ur_parts = 0;
+
+    for image in images {
+        info!("reading qr {:?}", image);
+        let data = read_qr(image)
+            .map_err(|e| Error::Generic(format!("{}: {}", image.display(), e.to_string())))?;
+        if data.is_structured_append() {
+            structured.push(data.codewords);
+            continue;
+        }
+        let content = data.content()?;
+        match std::str::from_utf8(&content) {
+            Ok(text) if text.to_lowercase().starts_with("ur:") => {
+                ur_decoder.receive(text)?;
+                ur_parts += 1;
+            }
+            _ => singles.push(content),
+        }
+    }
+
+    match (structured.is_empty(), ur_parts, singles.len()) {
+        (false, 0, 0) => Ok(QrContent::Bytes(qr::merge_qrs(structured)?)),
+        (true, 0, 1) => Ok(QrContent::Bytes(singles.remove(0))),
+        (true, parts, 0) if parts > 0 => {
+            let (ur_type, cbor) = ur_decoder.result().ok_or_else(|| {
+                let (known, total) = ur_decoder.progress();
+                Error::Generic(format!(
+                    "ur parts are not enough, decoded {} of {} fragments",
+                    known, total
+                ))
+            })?;
+            Ok(QrContent::Ur(ur_type.to_string(), cbor.to_vec()))
+        }
+        _ => Err("the images must be the QR codes of a single item".into()),
+    }
+}
+
+/// Detect the firma json of PSBTs, wallets and public keys, raw or base64 PSBTs, xpubs with
+/// optional key origin and multisig descriptors
+fn detect(content: &[u8], name: Option<&str>) -> Result<Payload> {
+    if transfer::is_compact_psbt(content) {
+        let mut psbt = transfer::decode_psbt(content)?;
+        if let Some(name) = name {
+            psbt.name = name.to_string();
+        }
+        return Ok(Payload::Psbt(psbt));
+    }
+    if let Ok(mut wallet) = serde_json::from_slice::<WalletJson>(content) {
+        if let Some(name) = name {
+            wallet.name = name.to_string();
+        }
+        return Ok(Payload::Wallet(wallet));
+    }
+    if let Ok(mut psbt) = serde_json::from_slice::<PsbtJson>(content) {
+        psbt_from_base64(&psbt.psbt)?;
+        if let Some(name) = name {
+            psbt.name = name.to_string();
+        }
+        return Ok(Payload::Psbt(psbt));
+    }
+    if let Ok(key) = serde_json::from_slice::<PublicMasterKey>(content) {
+        return Ok(Payload::Xpub(key));
+    }
+    if content.starts_with(PSBT_MAGIC) {
+        return raw_psbt(content.to_vec(), name);
+    }
+
+    let text = std::str::from_utf8(content).map(|s| s.trim()).unwrap_or("");
+    if let Ok(bytes) = base64::decode(text) {
+        if bytes.starts_with(PSBT_MAGIC) {
+            return raw_psbt(bytes, name);
+        }
+    }
+    if let Ok(xpub) = XpubWithOrigin::from_str(text) {
+        return Ok(Payload::Xpub(PublicMasterKey {
+            xpub: xpub.xpub,
+            key_origin: xpub.origin,
+        }));
+    }
+    if text.contains('(') {
+        return wallet_of_descriptor(text, name);
+    }
+    Err("unrecognized qr content, expected a PSBT, a wallet or an xpub".into())
+}
+
+/// PSBT json of a raw PSBT, the fee is known only if all the inputs have the previous output
+fn raw_psbt(bytes: Vec<u8>, name: Option<&str>) -> Result<Payload> {
+    let psbt: PSBT = deserialize(&bytes)?;
+    let tx = &psbt.global.unsigned_tx;
+    let input_values: Option<u64> = psbt
+        .inputs
+        .iter()
+        .zip(tx.input.iter())
+        .map(
+            |(input, txin)| match (&input.witness_utxo, &input.non_witness_utxo) {
+                (Some(utxo), _) => Some(utxo.value),
+                (None, Some(prev_tx)) => prev_tx
+                    .output
+                    .get(txin.previous_output.vout as usize)
+                    .map(|o| o.value),
+                (None, None) => None,
+            },
+        )
+        .sum();
+    let output_values: u64 = tx.output.iter().map(|o| o.value).sum();
+    let fee = input_values
+        .map(|v| Amount::from_sat(v.saturating_sub(output_values)).as_btc())
+        .unwrap_or(0.0);
+
+    Ok(Payload::Psbt(PsbtJson {
+        name: name
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| tx.txid().to_string()),
+        psbt: base64::encode(&bytes),
+        fee,
+        changepos: -1,
+    }))
+}
+
+fn wallet_of_descriptor(descriptor: &str, name: Option<&str>) -> Result<Payload> {
+    let name = name.ok_or_else(fn_err(
+        "wallet name missing in the qr, specify one with --name",
+    ))?;
+    Ok(Payload::Wallet(coordinator::from_descriptor(
+        descriptor, name,
+    )?))
+}
+
+fn save_psbt(
+    datadir: &str,
+    network: Network,
+    psbt: PsbtJson,
+    qr_version: i16,
+) -> Result<ImportQrOutput> {
+    let path_builder = PathBuilder::new(datadir, network, Kind::PSBT, Some(psbt.name.clone()));
+    let file = path_builder.file("psbt.json")?;
+    if file.exists() {
+        return Err(Error::FileExist(file));
+    }
+    info!("Saving psbt in {:?}", &file);
+    fs::write(&file, serde_json::to_string_pretty(&psbt)?)?;
+    let qr_path = path_builder.file_with_subdir(Some("qr"), "filename")?;
+    let qr_files = qr::save_psbt_qrs(
+        &psbt,
+        qr_path,
+        qr_version,
+        QrEncoding::StructuredAppend,
+        false,
+    )?;
+
+    Ok(ImportQrOutput {
+        kind: Kind::PSBT,
+        name: psbt.name,
+        file,
+        qr_files,
+    })
+}
+
+fn save_wallet(
+    datadir: &str,
+    network: Network,
+    wallet: WalletJson,
+    qr_version: i16,
+) -> Result<ImportQrOutput> {
+    wallet.validate()?;
+    if !WalletDescriptor::from_str(&wallet.descriptor_main)?.is_for_network(network) {
+        return Err(format!("wallet keys are not for network {}", network).into());
+    }
+    let context = Context {
+        network,
+        wallet_name: wallet.name.clone(),
+        firma_datadir: datadir.to_string(),
+    };
+    let file = context.save_wallet(&wallet)?;
+    let qr_files = context.save_wallet_qrs(&wallet, qr_version, QrEncoding::StructuredAppend)?;
+
+    Ok(ImportQrOutput {
+        kind: Kind::Wallet,
+        name: wallet.name,
+        file,
+        qr_files,
+    })
+}
+
+fn save_xpub(
+    datadir: &str,
+    network: Network,
+    key: PublicMasterKey,
+    name: Option<&str>,
+    qr_version: i16,
+) -> Result<ImportQrOutput> {
+    let key_network = key.xpub.network;
+    if key_network != network && !(network == Network::Regtest && key_network == Network::Testnet) {
+        return Err(format!("xpub is not for network {}", network).into());
+    }
+    let name = match (name, &key.key_origin) {
+        (Some(name), _) => name.to_string(),
+        (None, Some(origin)) => origin.fingerprint.to_string(),
+        (None, None) => key.xpub.fingerprint().to_string(),
+    };
+
+    let path_builder = PathBuilder::new(datadir, network, Kind::Key, Some(name.clone()));
+    let file = path_builder.file("public.json")?;
+    save_public(&key, &file)?;
+    let qr_path = path_builder.file_with_subdir(Some("qr"), "filename")?;
+    let xpub: XpubWithOrigin = key.into();
+    let qr_files = qr::save_qrs(xpub.to_string().as_bytes().to_vec(), qr_path, qr_version)?;
+
+    Ok(ImportQrOutput {
+        kind: Kind::Key,
+        name,
+        file,
+        qr_files,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::qr::{save_psbt_qrs, save_qrs, QrEncoding};
+    use crate::offline::import_qr::{start, ImportQrOptions};
+    use crate::offline::import_wallet::{self, ImportWalletOptions};
+    use crate::{read_wallet, save_keys, Kind, PrivateMasterKey, PsbtJson, PublicMasterKey};
+    use bitcoin::Network;
+    use std::path::PathBuf;
+    use tempdir::TempDir;
+
+    fn options(files: Vec<PathBuf>, name: Option<&str>) -> ImportQrOptions {
+        ImportQrOptions {
+            files,
+            name: name.map(|s| s.to_string()),
+            qr_version: 14,
+        }
+    }
+
+    #[test]
+    fn test_import_qr() {
+        let temp_dir = TempDir::new("test_import_qr").unwrap();
+        let source = format!("{}/source/", temp_dir.path().display());
+        let datadir = format!("{}/datadir/", temp_dir.path().display());
+        std::fs::create_dir(&source).unwrap();
+
+        // master xpub in a single qr, named with its fingerprint
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let key = PrivateMasterKey::new(Network::Testnet, &seed, "bip32-vector-1").unwrap();
+        let key = save_keys(&source, Network::Testnet, "bip32-vector-1", key, 14, None).unwrap();
+        let opt = options(key.public_qr_files.clone(), None);
+        let result = start(&datadir, Network::Testnet, &opt).unwrap();
+        assert_eq!(result.kind, Kind::Key);
+        assert_eq!(result.name, "3442193e");
+        let public: PublicMasterKey =
+            serde_json::from_slice(&std::fs::read(&result.file).unwrap()).unwrap();
+        assert_eq!(public.xpub, key.key.xpub);
+        assert!(start(&datadir, Network::Bitcoin, &opt).is_err());
+
+        // compact psbt in structured append qr codes, given as their directory
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.json");
+        let psbt: PsbtJson = serde_json::from_slice(bytes).unwrap();
+        let qr_dir = temp_dir.path().join("psbt-qr");
+        std::fs::create_dir(&qr_dir).unwrap();
+        let qr_files = save_psbt_qrs(
+            &psbt,
+            qr_dir.join("filename"),
+            5,
+            QrEncoding::StructuredAppend,
+            false,
+        )
+        .unwrap();
+        assert!(qr_files.len() > 1);
+        let opt = options(vec![qr_dir], None);
+        let result = start(&datadir, Network::Testnet, &opt).unwrap();
+        assert_eq!(result.kind, Kind::PSBT);
+        let imported: PsbtJson =
+            serde_json::from_slice(&std::fs::read(&result.file).unwrap()).unwrap();
+        assert_eq!(imported, psbt);
+        assert!(start(&datadir, Network::Testnet, &opt)
+            .unwrap_err()
+            .to_string()
+            .contains("already exist"));
+        let opt = options(qr_files[1..].to_vec(), None);
+        assert!(start(&datadir, Network::Testnet, &opt).is_err());
+
+        // psbt json in qr codes created before the compact encoding
+        let json_dir = temp_dir.path().join("psbt-json");
+        std::fs::create_dir(&json_dir).unwrap();
+        let json = serde_json::to_vec(&psbt).unwrap();
+        save_qrs(json, json_dir.join("filename"), 5).unwrap();
+        let opt = options(vec![json_dir], Some("from-json"));
+        let result = start(&datadir, Network::Testnet, &opt).unwrap();
+        let imported: PsbtJson =
+            serde_json::from_slice(&std::fs::read(&result.file).unwrap()).unwrap();
+        assert_eq!(imported.name, "from-json");
+        assert_eq!(imported.psbt, psbt.psbt);
+
+        // names in the qr content cannot point outside the datadir
+        let evil_dir = temp_dir.path().join("psbt-evil");
+        std::fs::create_dir(&evil_dir).unwrap();
+        let mut evil = psbt.clone();
+        evil.name = "../../../evil".to_string();
+        save_qrs(
+            serde_json::to_vec(&evil).unwrap(),
+            evil_dir.join("filename"),
+            5,
+        )
+        .unwrap();
+        let opt = options(vec![evil_dir], None);
+        assert!(start(&datadir, Network::Testnet, &opt)
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid name"));
+        assert!(!temp_dir.path().join("evil").exists());
+
+        // raw psbt in UR qr codes
+        let ur_dir = temp_dir.path().join("psbt-ur");
+        std::fs::create_dir(&ur_dir).unwrap();
+        save_psbt_qrs(&psbt, ur_dir.join("filename"), 5, QrEncoding::Ur, false).unwrap();
+        let opt = options(vec![ur_dir], Some("from-ur"));
+        let result = start(&datadir, Network::Testnet, &opt).unwrap();
+        let imported: PsbtJson =
+            serde_json::from_slice(&std::fs::read(&result.file).unwrap()).unwrap();
+        assert_eq!(imported.name, "from-ur");
+        assert_eq!(imported.psbt, psbt.psbt);
+
+        // wallet json
+        let file = temp_dir.path().join("coldcard.txt");
+        std::fs::write(&file, include_str!("../../test_data/import/coldcard.txt")).unwrap();
+        let wallet_opt = ImportWalletOptions {
+            file,
+            format: None,
+            wallet_name: None,
+            script_type: None,
+            qr_version: 14,
+        };
+        let wallet = import_wallet::start(&source, Network::Testnet, &wallet_opt).unwrap();
+        let opt = options(wallet.qr_files.clone(), None);
+        let result = start(&datadir, Network::Testnet, &opt).unwrap();
+        assert_eq!(result.kind, Kind::Wallet);
+        assert_eq!(read_wallet(&result.file).unwrap(), wallet.wallet);
+    }
+}
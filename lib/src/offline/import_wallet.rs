@@ -0,0 +1,119 @@
+use crate::common::coordinator::{self, WalletFormat};
+use crate::common::descriptor::WalletDescriptor;
+use crate::common::qr::QrEncoding;
+use crate::*;
+use bitcoin::Network;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Import a wallet from a Coldcard, Specter, Sparrow (Electrum) or BSMS file, Specter and BSMS
+/// descriptors can also be single signature or miniscript policy wallets
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "import_wallet")]
+pub struct ImportWalletOptions {
+    /// File exported by the other coordinator
+    #[structopt(short, long, parse(from_os_str))]
+    pub file: PathBuf,
+
+    /// Format of the file: coldcard, specter, sparrow (electrum) or bsms, detected if missing
+    #[structopt(long)]
+    #[serde(default)]
+    pub format: Option<WalletFormat>,
+
+    /// Name of the wallet, required if the file does not contain one
+    #[structopt(long)]
+    #[serde(default)]
+    pub wallet_name: Option<String>,
+
+    /// Script type of the wallet: wsh, sh-wsh or sh, required for electrum files with bare
+    /// xpubs, Zpub/Vpub and Ypub/Upub keys imply wsh and sh-wsh
+    #[structopt(long)]
+    #[serde(default)]
+    pub script_type: Option<ScriptType>,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+}
+
+pub fn start(
+    datadir: &str,
+    network: Network,
+    opt: &ImportWalletOptions,
+) -> Result<CreateWalletOutput> {
+    let content = fs::read_to_string(&opt.file)?;
+    let format = match opt.format {
+        Some(format) => format,
+        None => WalletFormat::detect(&content)?,
+    };
+    let name = opt.wallet_name.as_ref().map(|s| s.as_str());
+    let wallet = coordinator::import(&content, format, name, opt.script_type)?;
+    if !WalletDescriptor::from_str(&wallet.descriptor_main)?.is_for_network(network) {
+        return Err(format!("wallet keys are not for network {}", network).into());
+    }
+
+    let context = Context {
+        network,
+        wallet_name: wallet.name.clone(),
+        firma_datadir: datadir.to_string(),
+    };
+    let wallet_file = context.save_wallet(&wallet)?;
+    let qr_files =
+        context.save_wallet_qrs(&wallet, opt.qr_version, QrEncoding::StructuredAppend)?;
+
+    Ok(CreateWalletOutput {
+        qr_files,
+        wallet_file,
+        wallet,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::list::{list, ListOptions};
+    use crate::offline::import_wallet::{start, ImportWalletOptions};
+    use crate::Kind;
+    use bitcoin::Network;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_import_wallet() {
+        let temp_dir = TempDir::new("test_import_wallet").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+        let file = temp_dir.path().join("coldcard.txt");
+        let content = include_str!("../../test_data/import/coldcard.txt");
+        std::fs::write(&file, content).unwrap();
+
+        let mut opt = ImportWalletOptions {
+            file,
+            format: None,
+            wallet_name: None,
+            script_type: None,
+            qr_version: 14,
+        };
+        let result = start(&temp_dir_str, Network::Bitcoin, &opt);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "wallet keys are not for network bitcoin"
+        );
+
+        let imported = start(&temp_dir_str, Network::Testnet, &opt).unwrap();
+        assert_eq!(imported.wallet.name, "cc-2of2");
+        assert!(!imported.qr_files.is_empty());
+        let listed = list(
+            &temp_dir_str,
+            Network::Testnet,
+            &ListOptions::new(Kind::Wallet),
+        )
+        .unwrap();
+        assert_eq!(listed.wallets[0].wallet, imported.wallet);
+
+        let result = start(&temp_dir_str, Network::Testnet, &opt);
+        assert!(result.unwrap_err().to_string().contains("already exist"));
+        opt.wallet_name = Some("cc-copy".to_string());
+        assert!(start(&temp_dir_str, Network::Testnet, &opt).is_ok());
+    }
+}
@@ -1,6 +1,18 @@
+pub mod address;
+pub mod backup_sheet;
+pub mod bsms;
+pub mod create_wallet;
 pub mod derive_key;
 pub mod dice;
+pub mod encrypt_key;
+pub mod export_wallet;
+pub mod export_xpub;
+pub mod finalize;
+pub mod import_qr;
+pub mod import_wallet;
 pub mod print;
 pub mod random;
 pub mod restore;
 pub mod sign;
+pub mod spending_policy;
+pub mod split_key;
@@ -1,9 +1,10 @@
+use crate::common::signatures;
 use crate::list::ListOptions;
 use crate::*;
 use bitcoin::consensus::serialize;
-use bitcoin::util::bip32::{DerivationPath, Fingerprint};
+use bitcoin::util::bip32::{ChildNumber, DerivationPath, Fingerprint};
 use bitcoin::util::key;
-use bitcoin::{Address, Amount, Network, OutPoint, Script, SignedAmount, TxOut};
+use bitcoin::{Address, Amount, Network, OutPoint, Script, SigHashType, SignedAmount, TxOut};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::PathBuf;
@@ -21,8 +22,7 @@ pub struct PrintOptions {
 
 pub fn start(datadir: &str, network: Network, opt: &PrintOptions) -> Result<PsbtPrettyPrint> {
     let psbt = read_psbt(&opt.psbt_file)?;
-    let kind = Kind::Wallet;
-    let opt = ListOptions { kind };
+    let opt = ListOptions::new(Kind::Wallet);
     let result = common::list::list(datadir, network, &opt)?;
     let wallets: Vec<WalletJson> = result.wallets.iter().map(|w| w.wallet.clone()).collect();
     let output = pretty_print(&psbt, network, &wallets)?;
@@ -35,55 +35,82 @@ pub fn pretty_print(
     wallets: &[WalletJson],
 ) -> Result<PsbtPrettyPrint> {
     let mut result = PsbtPrettyPrint::default();
-    let mut previous_outputs: Vec<TxOut> = vec![];
     let mut output_values: Vec<u64> = vec![];
     let tx = &psbt.global.unsigned_tx;
-    let vouts: Vec<OutPoint> = tx.input.iter().map(|el| el.previous_output).collect();
-    for (i, input) in psbt.inputs.iter().enumerate() {
-        let previous_output = match (&input.non_witness_utxo, &input.witness_utxo) {
-            (Some(prev_tx), None) => {
-                let outpoint = *vouts.get(i).ok_or_else(fn_err("can't find outpoint"))?;
-                assert_eq!(prev_tx.txid(), outpoint.txid);
-                prev_tx
-                    .output
-                    .get(outpoint.vout as usize)
-                    .ok_or_else(fn_err("can't find txout"))?
-            }
-            (None, Some(val)) => val,
-            _ => return Err("witness_utxo and non_witness_utxo are both None or both Some".into()),
-        };
-        previous_outputs.push(previous_output.clone());
-    }
+    let previous_outputs = previous_outputs(psbt)?;
     let input_values: Vec<u64> = previous_outputs.iter().map(|o| o.value).collect();
     let mut balances = HashMap::new();
 
     for (i, input) in tx.input.iter().enumerate() {
         let keypaths = &psbt.inputs[i].hd_keypaths;
-        let wallets = which_wallet(keypaths, &wallets);
+        let single_sig = is_single_sig(
+            &psbt.inputs[i].witness_script,
+            &psbt.inputs[i].redeem_script,
+        );
+        let input_wallets = which_wallet(keypaths, &wallets, single_sig);
+        let (signed_by, invalid_signatures) = signatures::check_partial_sigs(psbt, i)?;
+        let missing_signatures = match input_wallets.as_slice() {
+            [name] => wallets
+                .iter()
+                .find(|w| &w.name == name)
+                .and_then(|w| w.required_sig)
+                .map(|required_sig| required_sig.saturating_sub(signed_by.len())),
+            _ => None,
+        };
         let txin = TxInOut {
             outpoint: Some(input.previous_output.to_string()),
             address: None,
             value: Amount::from_sat(previous_outputs[i].value).to_string(),
             path: derivation_paths(keypaths),
-            wallet: wallets.join(", "),
+            wallet: input_wallets.join(", "),
+            signed_by,
+            invalid_signatures,
+            missing_signatures,
         };
-        for wallet in wallets {
+        for wallet in input_wallets {
             *balances.entry(wallet).or_insert(0i64) -= previous_outputs[i].value as i64
         }
+        for invalid in txin.invalid_signatures.iter() {
+            result.info.push(format!("WARNING: {}", invalid));
+        }
         result.inputs.push(txin);
+        match psbt.inputs[i].sighash_type {
+            Some(sighash_type) if sighash_type != SigHashType::All => result.info.push(format!(
+                "WARNING: input {} requests sighash type {:?}, its signature doesn't commit to the whole transaction",
+                i, sighash_type
+            )),
+            _ => (),
+        }
+    }
+    if result
+        .inputs
+        .iter()
+        .all(|i| i.missing_signatures == Some(0))
+    {
+        result
+            .info
+            .push("All inputs have the required signatures".to_string());
     }
 
     for (i, output) in tx.output.iter().enumerate() {
-        let addr = Address::from_script(&output.script_pubkey, network)
-            .ok_or_else(fn_err("non default script"))?;
+        let addr = address(&output.script_pubkey, network)?;
         let keypaths = &psbt.outputs[i].hd_keypaths;
-        let wallets = which_wallet(keypaths, &wallets);
+        let (wallets, mismatched) = output_wallets(psbt, i, &wallets, network);
+        for wallet in mismatched {
+            result.info.push(format!(
+                "WARNING: output {} has key paths of wallet {} but its script is not derived from the wallet descriptor",
+                i, wallet
+            ));
+        }
         let txout = TxInOut {
             outpoint: None,
-            address: Some(addr.to_string()),
+            address: Some(addr),
             value: Amount::from_sat(output.value).to_string(),
             path: derivation_paths(keypaths),
             wallet: wallets.join(" ,"),
+            signed_by: vec![],
+            invalid_signatures: vec![],
+            missing_signatures: None,
         };
         for wallet in wallets {
             *balances.entry(wallet).or_insert(0i64) += output.value as i64
@@ -162,6 +189,41 @@ pub fn pretty_print(
     Ok(result)
 }
 
+/// Outputs spent by the inputs of the PSBT
+pub(crate) fn previous_outputs(psbt: &PSBT) -> Result<Vec<TxOut>> {
+    let mut previous_outputs: Vec<TxOut> = vec![];
+    let tx = &psbt.global.unsigned_tx;
+    let vouts: Vec<OutPoint> = tx.input.iter().map(|el| el.previous_output).collect();
+    for (i, input) in psbt.inputs.iter().enumerate() {
+        let previous_output = match (&input.non_witness_utxo, &input.witness_utxo) {
+            (Some(prev_tx), None) => {
+                let outpoint = *vouts.get(i).ok_or_else(fn_err("can't find outpoint"))?;
+                if prev_tx.txid() != outpoint.txid {
+                    return Err("prevout doesn't match non_witness_utxo".into());
+                }
+                prev_tx
+                    .output
+                    .get(outpoint.vout as usize)
+                    .ok_or_else(fn_err("can't find txout"))?
+            }
+            (_, Some(val)) => {
+                signatures::check_witness_utxo(psbt, i)?;
+                val
+            }
+            (None, None) => return Err("witness_utxo and non_witness_utxo are both None".into()),
+        };
+        previous_outputs.push(previous_output.clone());
+    }
+    Ok(previous_outputs)
+}
+
+/// Address of the output script
+pub(crate) fn address(script: &Script, network: Network) -> Result<String> {
+    Ok(Address::from_script(script, network)
+        .ok_or_else(fn_err("non default script"))?
+        .to_string())
+}
+
 fn biggest_dividing_pow(num: u64) -> u8 {
     let mut start = 10u64;
     let mut count = 0u8;
@@ -195,10 +257,22 @@ pub fn derivation_paths(hd_keypaths: &HDKeypaths) -> String {
     vec.join(", ")
 }
 
-fn which_wallet(hd_keypaths: &HDKeypaths, wallets: &[WalletJson]) -> Vec<String> {
+/// Scripts of single signature inputs and outputs (p2wpkh or p2sh-p2wpkh) have no witness script
+fn is_single_sig(witness_script: &Option<Script>, redeem_script: &Option<Script>) -> bool {
+    witness_script.is_none()
+        && redeem_script
+            .as_ref()
+            .map(|s| s.is_v0_p2wpkh())
+            .unwrap_or(true)
+}
+
+fn which_wallet(hd_keypaths: &HDKeypaths, wallets: &[WalletJson], single_sig: bool) -> Vec<String> {
     // TODO this should be done with miniscript
     let mut result = vec![];
     for wallet in wallets {
+        if wallet.is_single_sig() != single_sig {
+            continue;
+        }
         if !hd_keypaths.is_empty()
             && hd_keypaths
                 .iter()
@@ -210,9 +284,341 @@ fn which_wallet(hd_keypaths: &HDKeypaths, wallets: &[WalletJson]) -> Vec<String>
     result
 }
 
+/// Wallets the output belongs to, the fingerprints in the output key paths must be of the wallet
+/// and the output script must be derived from the wallet descriptors at the index of the paths.
+/// Returns also the wallets matching the fingerprints but not the script, which a coordinator
+/// could use to show an output of someone else as change
+fn output_wallets(
+    psbt: &PSBT,
+    index: usize,
+    wallets: &[WalletJson],
+    network: Network,
+) -> (Vec<String>, Vec<String>) {
+    let output = &psbt.outputs[index];
+    let script_pubkey = &psbt.global.unsigned_tx.output[index].script_pubkey;
+    let single_sig = is_single_sig(&output.witness_script, &output.redeem_script);
+    let mut verified = vec![];
+    let mut mismatched = vec![];
+    for wallet in wallets {
+        let wallet_slice = std::slice::from_ref(wallet);
+        if which_wallet(&output.hd_keypaths, wallet_slice, single_sig).is_empty() {
+            continue;
+        }
+        if derives_script(wallet, &output.hd_keypaths, script_pubkey, network) {
+            verified.push(wallet.name.clone());
+        } else {
+            mismatched.push(wallet.name.clone());
+        }
+    }
+    (verified, mismatched)
+}
+
+/// True if the main or change descriptor of the wallet derives `script_pubkey` at the last
+/// derivation step of one of the paths, descriptors failing to derive don't match
+pub(crate) fn derives_script(
+    wallet: &WalletJson,
+    hd_keypaths: &HDKeypaths,
+    script_pubkey: &Script,
+    network: Network,
+) -> bool {
+    let indexes: HashSet<u32> = hd_keypaths
+        .values()
+        .filter_map(|(_, path)| match path.as_ref().last() {
+            Some(ChildNumber::Normal { index }) => Some(*index),
+            _ => None,
+        })
+        .collect();
+    let (main, change) = match wallet.parse_descriptors() {
+        Ok(descriptors) => descriptors,
+        Err(_) => return false,
+    };
+    indexes.iter().any(|index| {
+        [&main, &change].iter().any(|descriptor| {
+            descriptor
+                .derive_address(*index, network)
+                .map(|address| address.script_pubkey() == *script_pubkey)
+                .unwrap_or(false)
+        })
+    })
+}
+
 #[cfg(test)]
-mod tests {
-    use crate::offline::print::{biggest_dividing_pow, script_type};
+pub(crate) mod tests {
+    use crate::common::fixtures::{self, ACCOUNT_1_XPUB, ACCOUNT_XPUB};
+    use crate::common::key_origin::XpubWithOrigin;
+    use crate::common::signatures;
+    use crate::offline::print::{
+        biggest_dividing_pow, is_single_sig, output_wallets, pretty_print, previous_outputs,
+        script_type, which_wallet, HDKeypaths,
+    };
+    use crate::{psbt_from_base64, PsbtJson, WalletJson, PSBT};
+    use bitcoin::secp256k1::Secp256k1;
+    use bitcoin::util::bip32::{ChildNumber, DerivationPath, Fingerprint};
+    use bitcoin::util::key;
+    use bitcoin::{Address, Network, OutPoint, Script, SigHashType, Transaction, TxIn, TxOut};
+    use std::str::FromStr;
+
+    pub const DESTINATION: &str = "tb1qmpwzkuwsqc9snjvgdt4czhjsnywa5yjdzglap9";
+
+    fn keypath(change: u32, index: u32) -> (key::PublicKey, (Fingerprint, DerivationPath)) {
+        let secp = Secp256k1::verification_only();
+        let xpub = XpubWithOrigin::from_str(ACCOUNT_XPUB).unwrap().xpub;
+        let path = [
+            ChildNumber::from_normal_idx(change).unwrap(),
+            ChildNumber::from_normal_idx(index).unwrap(),
+        ];
+        let key = xpub.derive_pub(&secp, &path).unwrap().public_key;
+        let path = format!("m/48'/1'/0'/2'/{}/{}", change, index);
+        (
+            key,
+            (
+                Fingerprint::from_str("3442193e").unwrap(),
+                DerivationPath::from_str(&path).unwrap(),
+            ),
+        )
+    }
+
+    /// Single signature wallet and a PSBT spending 100_000 sat of it, paying 60_000 sat to
+    /// `DESTINATION` and 39_000 sat back to the change address at index 3
+    pub fn wallet_psbt() -> (WalletJson, PSBT) {
+        let wallet = fixtures::wallet("single", &format!("wpkh({}/0/*)", ACCOUNT_XPUB));
+        let address = |index: u32, change: bool| {
+            wallet
+                .derive_address(index, change, Network::Testnet)
+                .unwrap()
+                .script_pubkey()
+        };
+        let prev_tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![],
+            output: vec![TxOut {
+                value: 100_000,
+                script_pubkey: address(0, false),
+            }],
+        };
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(prev_tx.txid(), 0),
+                script_sig: Script::new(),
+                sequence: 0xffff_fffd,
+                witness: vec![],
+            }],
+            output: vec![
+                TxOut {
+                    value: 60_000,
+                    script_pubkey: Address::from_str(DESTINATION).unwrap().script_pubkey(),
+                },
+                TxOut {
+                    value: 39_000,
+                    script_pubkey: address(3, true),
+                },
+            ],
+        };
+        let mut psbt = PSBT::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(prev_tx.output[0].clone());
+        psbt.inputs[0].non_witness_utxo = Some(prev_tx);
+        let (key, path) = keypath(0, 0);
+        psbt.inputs[0].hd_keypaths.insert(key, path);
+        let (key, path) = keypath(1, 3);
+        psbt.outputs[1].hd_keypaths.insert(key, path);
+        (wallet, psbt)
+    }
+
+    #[test]
+    fn test_output_wallets() {
+        let (wallet, mut psbt) = wallet_psbt();
+        let wallets = vec![wallet];
+        let verified = (vec!["single".to_string()], vec![]);
+        let mismatched = (vec![], vec!["single".to_string()]);
+        assert_eq!(
+            output_wallets(&psbt, 0, &wallets, Network::Testnet),
+            (vec![], vec![])
+        );
+        assert_eq!(
+            output_wallets(&psbt, 1, &wallets, Network::Testnet),
+            verified
+        );
+        let print = pretty_print(&psbt, Network::Testnet, &wallets).unwrap();
+        assert_eq!(print.outputs[1].wallet, "single");
+        assert_eq!(print.balances, "single: -0.00061000 BTC");
+        assert!(!print.info.iter().any(|i| i.starts_with("WARNING")));
+
+        // the coordinator attaches our key path to the payment output to show it as change
+        let (key, path) = keypath(1, 3);
+        psbt.outputs[0].hd_keypaths.insert(key, path);
+        assert_eq!(
+            output_wallets(&psbt, 0, &wallets, Network::Testnet),
+            mismatched
+        );
+        let print = pretty_print(&psbt, Network::Testnet, &wallets).unwrap();
+        assert_eq!(print.outputs[0].wallet, "");
+        assert_eq!(print.balances, "single: -0.00061000 BTC");
+        assert!(print.info.contains(&"WARNING: output 0 has key paths of wallet single but its script is not derived from the wallet descriptor".to_string()));
+
+        // the change address is at another index than the one in the path
+        let (key, path) = keypath(1, 4);
+        psbt.outputs[1].hd_keypaths.clear();
+        psbt.outputs[1].hd_keypaths.insert(key, path);
+        assert_eq!(
+            output_wallets(&psbt, 1, &wallets, Network::Testnet),
+            mismatched
+        );
+    }
+
+    #[test]
+    fn test_witness_and_non_witness_utxo() {
+        let (wallet, mut psbt) = wallet_psbt();
+        let wallets = vec![wallet];
+        let prev_tx = psbt.inputs[0].non_witness_utxo.clone().unwrap();
+        let print = pretty_print(&psbt, Network::Testnet, &wallets).unwrap();
+        assert_eq!(print.fee.absolute, 1_000);
+        assert!(signatures::script_code(&psbt, 0).is_ok());
+
+        // the amount of witness_utxo is not the one of the spent output
+        let mut other_tx = prev_tx;
+        other_tx.output[0].value = 200_000;
+        psbt.inputs[0].non_witness_utxo = Some(other_tx.clone());
+        let error = "witness_utxo of input 0 doesn't match the spent output of non_witness_utxo";
+        assert_eq!(
+            pretty_print(&psbt, Network::Testnet, &wallets)
+                .unwrap_err()
+                .to_string(),
+            error
+        );
+        assert_eq!(
+            signatures::script_code(&psbt, 0).unwrap_err().to_string(),
+            error
+        );
+        psbt.global.unsigned_tx.input[0].previous_output = OutPoint::new(other_tx.txid(), 0);
+        psbt.inputs[0].witness_utxo = Some(other_tx.output[0].clone());
+        assert!(signatures::script_code(&psbt, 0).is_ok());
+
+        // non_witness_utxo only, not the transaction spent by the input
+        let mut unrelated_tx = other_tx;
+        unrelated_tx.lock_time = 1;
+        psbt.inputs[0].witness_utxo = None;
+        psbt.inputs[0].non_witness_utxo = Some(unrelated_tx);
+        let error = "prevout doesn't match non_witness_utxo";
+        assert_eq!(previous_outputs(&psbt).unwrap_err().to_string(), error);
+        assert_eq!(
+            pretty_print(&psbt, Network::Testnet, &wallets)
+                .unwrap_err()
+                .to_string(),
+            error
+        );
+    }
+
+    #[test]
+    fn test_sighash_warning() {
+        let (wallet, mut psbt) = wallet_psbt();
+        let wallets = vec![wallet];
+        let warning = "WARNING: input 0 requests sighash type SinglePlusAnyoneCanPay, its signature doesn't commit to the whole transaction".to_string();
+        psbt.inputs[0].sighash_type = Some(SigHashType::All);
+        let print = pretty_print(&psbt, Network::Testnet, &wallets).unwrap();
+        assert!(!print.info.iter().any(|i| i.starts_with("WARNING")));
+        psbt.inputs[0].sighash_type = Some(SigHashType::SinglePlusAnyoneCanPay);
+        let print = pretty_print(&psbt, Network::Testnet, &wallets).unwrap();
+        assert!(print.info.contains(&warning));
+    }
+
+    #[test]
+    fn test_which_wallet() {
+        let fingerprint = Fingerprint::from_str("3442193e").unwrap();
+        let other = ACCOUNT_1_XPUB.replace("3442193e", "0badf00d");
+        let wallets = vec![
+            fixtures::wallet("single", &format!("wpkh({}/0/*)", ACCOUNT_XPUB)),
+            fixtures::wallet(
+                "multi",
+                &format!("wsh(sortedmulti(1,{}/0/*,{}/0/*))", ACCOUNT_XPUB, other),
+            ),
+        ];
+        let key = key::PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let mut keypaths = HDKeypaths::new();
+        keypaths.insert(
+            key,
+            (fingerprint, DerivationPath::from_str("m/0/0").unwrap()),
+        );
+
+        assert!(is_single_sig(&None, &None));
+        assert_eq!(which_wallet(&keypaths, &wallets, true), vec!["single"]);
+        assert_eq!(which_wallet(&keypaths, &wallets, false), vec!["multi"]);
+        let witness_script = Some(Script::new());
+        assert!(!is_single_sig(&witness_script, &None));
+    }
+
+    #[test]
+    fn test_signatures_status() {
+        // keys with the fingerprint of the BIP174 test vector
+        let descriptor = format!(
+            "wsh(multi(2,{}/0/*,{}/0/*))",
+            ACCOUNT_XPUB.replace("3442193e", "d90c6a4f"),
+            ACCOUNT_1_XPUB.replace("3442193e", "d90c6a4f")
+        );
+        let wallet = fixtures::wallet("bip", &descriptor);
+        let wallets = vec![wallet];
+        let all_signed = "All inputs have the required signatures".to_string();
+
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.json");
+        let psbt_json: PsbtJson = serde_json::from_slice(bytes).unwrap();
+        let (_, psbt) = psbt_from_base64(&psbt_json.psbt).unwrap();
+        let print = pretty_print(&psbt, Network::Testnet, &wallets).unwrap();
+        for input in print.inputs.iter() {
+            assert!(input.signed_by.is_empty());
+            assert_eq!(input.missing_signatures, Some(2));
+        }
+        assert!(!print.info.contains(&all_signed));
+
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.signed.json");
+        let psbt_json: PsbtJson = serde_json::from_slice(bytes).unwrap();
+        let (_, mut psbt) = psbt_from_base64(&psbt_json.psbt).unwrap();
+        let print = pretty_print(&psbt, Network::Testnet, &wallets).unwrap();
+        for input in print.inputs.iter() {
+            assert_eq!(input.signed_by, vec!["d90c6a4f", "d90c6a4f"]);
+            assert_eq!(input.missing_signatures, Some(0));
+        }
+        assert!(print.info.contains(&all_signed));
+        let print = pretty_print(&psbt, Network::Testnet, &[]).unwrap();
+        assert_eq!(print.inputs[0].missing_signatures, None);
+        assert!(!print.info.contains(&all_signed));
+
+        // policy wallets have no fixed number of required signatures
+        let policy = format!(
+            "wsh(or_d(pk({}/0/*),and_v(v:pk({}/0/*),older(144))))",
+            ACCOUNT_XPUB.replace("3442193e", "d90c6a4f"),
+            ACCOUNT_1_XPUB.replace("3442193e", "d90c6a4f")
+        );
+        let policy_wallets = vec![fixtures::wallet("policy", &policy)];
+        let print = pretty_print(&psbt, Network::Testnet, &policy_wallets).unwrap();
+        for input in print.inputs.iter() {
+            assert_eq!(input.wallet, "policy");
+            assert_eq!(input.missing_signatures, None);
+        }
+        assert!(!print.info.contains(&all_signed));
+
+        // invalid signatures are reported, the other signatures are still counted
+        let (key, signature) = psbt.inputs[1].partial_sigs.iter_mut().next().unwrap();
+        signature[10] ^= 1;
+        let invalid = format!("invalid signature of key {} in input 1", key);
+        let print = pretty_print(&psbt, Network::Testnet, &wallets).unwrap();
+        assert!(print.inputs[0].invalid_signatures.is_empty());
+        assert_eq!(print.inputs[1].invalid_signatures, vec![invalid.clone()]);
+        assert_eq!(print.inputs[1].signed_by.len(), 1);
+        assert_eq!(print.inputs[1].missing_signatures, Some(1));
+        assert!(print.info.contains(&format!("WARNING: {}", invalid)));
+        assert!(!print.info.contains(&all_signed));
+        assert_eq!(
+            signatures::verify_partial_sigs(&psbt, 1)
+                .unwrap_err()
+                .to_string(),
+            invalid
+        );
+    }
 
     #[test]
     fn test_biggest_dividing_pow() {
@@ -16,6 +16,32 @@ pub struct RandomOptions {
     #[structopt(long, default_value = "14")]
     #[serde(default)]
     pub qr_version: i16,
+
+    /// Generate a bip39 mnemonic with the given number of words (12 or 24) instead of a raw seed
+    #[structopt(long)]
+    #[serde(default)]
+    pub mnemonic_words: Option<usize>,
+
+    /// Protect the mnemonic with a bip39 passphrase asked at the prompt, used only with
+    /// --mnemonic-words
+    #[structopt(long)]
+    #[serde(default)]
+    pub passphrase: bool,
+
+    /// bip39 passphrase, the command line asks it with --passphrase
+    #[structopt(skip)]
+    #[serde(default)]
+    pub mnemonic_passphrase: Option<String>,
+
+    /// Encrypt the private key file with a passphrase asked at the prompt
+    #[structopt(long)]
+    #[serde(default)]
+    pub encrypt: bool,
+
+    /// Passphrase to encrypt the private key file, the command line asks it with --encrypt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
 }
 
 impl RandomOptions {
@@ -23,14 +49,48 @@ impl RandomOptions {
         RandomOptions {
             key_name,
             qr_version: 20,
+            mnemonic_words: None,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        match self.mnemonic_words {
+            None if self.passphrase => Err("--passphrase requires --mnemonic-words".into()),
+            Some(words) if words != 12 && words != 24 => {
+                Err("--mnemonic-words valid values are: 12, 24".into())
+            }
+            _ => Ok(()),
         }
     }
 }
 
 pub fn create_key(datadir: &str, network: Network, opt: &RandomOptions) -> Result<MasterKeyOutput> {
+    opt.validate()?;
     let sec = rand::thread_rng().gen::<[u8; 32]>();
-    let master_key = PrivateMasterKey::new(network, &sec, &opt.key_name)?;
-    let output = save_keys(datadir, network, &opt.key_name, master_key, opt.qr_version)?;
+    let master_key = match opt.mnemonic_words {
+        Some(words) => {
+            let entropy = &sec[..words / 3 * 4]; // 12 words -> 16 bytes, 24 words -> 32 bytes
+            PrivateMasterKey::from_entropy_mnemonic(
+                network,
+                entropy,
+                opt.mnemonic_passphrase.as_ref().map(|s| s.as_str()),
+                &opt.key_name,
+            )?
+        }
+        None => PrivateMasterKey::new(network, &sec, &opt.key_name)?,
+    };
+    let output = save_keys(
+        datadir,
+        network,
+        &opt.key_name,
+        master_key,
+        opt.qr_version,
+        opt.encryption_passphrase.as_ref().map(|s| s.as_str()),
+    )?;
 
     Ok(output)
 }
@@ -62,4 +122,35 @@ mod tests {
         assert_ne!(key_1.public_file, key_2.public_file);
         assert_ne!(key_1.private_file, key_2.private_file);
     }
+
+    #[test]
+    fn test_random_mnemonic() {
+        let temp_dir = TempDir::new("test_random_mnemonic").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+
+        for words in &[12usize, 24] {
+            let mut rand_opts = RandomOptions::new(format!("mnemonic_{}", words));
+            rand_opts.mnemonic_words = Some(*words);
+            let key = random::create_key(&temp_dir_str, Network::Testnet, &rand_opts).unwrap();
+            let mnemonic = key.key.mnemonic.unwrap();
+            assert_eq!(mnemonic.words.split(' ').count(), *words);
+            assert!(!mnemonic.passphrase_protected);
+            assert!(key.key.seed.is_none());
+        }
+
+        let mut rand_opts = RandomOptions::new("mnemonic_passphrase".to_string());
+        rand_opts.mnemonic_words = Some(12);
+        rand_opts.passphrase = true;
+        rand_opts.mnemonic_passphrase = Some("TREZOR".to_string());
+        let key = random::create_key(&temp_dir_str, Network::Testnet, &rand_opts).unwrap();
+        assert!(key.key.mnemonic.unwrap().passphrase_protected);
+
+        let mut rand_opts = RandomOptions::new("mnemonic_wrong".to_string());
+        rand_opts.mnemonic_words = Some(13);
+        let result = random::create_key(&temp_dir_str, Network::Testnet, &rand_opts);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "--mnemonic-words valid values are: 12, 24"
+        );
+    }
 }
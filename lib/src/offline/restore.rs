@@ -1,3 +1,4 @@
+use crate::offline::split_key::combine_shares;
 use crate::{save_keys, MasterKeyOutput, PrivateMasterKey};
 use crate::{Result, ToHrp};
 use bitcoin::bech32::{self, FromBase32};
@@ -25,7 +26,29 @@ pub struct RestoreOptions {
     #[structopt(long, default_value = "14")]
     pub qr_version: i16,
 
-    /// value of the secret component, could be xprv or seed in hex or bech32
+    /// The mnemonic or the slip39 shares are protected by a passphrase, asked at the prompt
+    #[structopt(long)]
+    #[serde(default)]
+    pub passphrase: bool,
+
+    /// Passphrase of the mnemonic or of the slip39 shares, the command line asks it with
+    /// --passphrase
+    #[structopt(skip)]
+    #[serde(default)]
+    pub mnemonic_passphrase: Option<String>,
+
+    /// Encrypt the private key file with a passphrase asked at the prompt
+    #[structopt(long)]
+    #[serde(default)]
+    pub encrypt: bool,
+
+    /// Passphrase to encrypt the private key file, the command line asks it with --encrypt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+
+    /// value of the secret component, could be xprv, seed in hex or bech32, bip39 mnemonic words
+    /// or slip39 shares (words of different shares separated by comma)
     value: String,
 }
 
@@ -34,6 +57,8 @@ enum Nature {
     Xprv,
     HexSeed,
     Bech32Seed,
+    Mnemonic,
+    Slip39,
 }
 
 impl FromStr for Nature {
@@ -44,9 +69,14 @@ impl FromStr for Nature {
             "xprv" => Ok(Nature::Xprv),
             "hex-seed" => Ok(Nature::HexSeed),
             "bech32-seed" => Ok(Nature::Bech32Seed),
+            "mnemonic" => Ok(Nature::Mnemonic),
+            "slip39" => Ok(Nature::Slip39),
             _ => Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
-                format!("({}) valid values are: xprv, hex-seed, bech32-seed", s),
+                format!(
+                    "({}) valid values are: xprv, hex-seed, bech32-seed, mnemonic, slip39",
+                    s
+                ),
             )),
         }
     }
@@ -54,6 +84,10 @@ impl FromStr for Nature {
 
 pub fn start(datadir: &str, network: Network, opt: &RestoreOptions) -> Result<MasterKeyOutput> {
     debug!("restore {:?}", &opt);
+    match (&opt.nature, opt.passphrase) {
+        (Nature::Mnemonic, _) | (Nature::Slip39, _) | (_, false) => (),
+        _ => return Err("--passphrase is valid only with mnemonic or slip39 nature".into()),
+    }
     let master_key = match opt.nature {
         Nature::Xprv => {
             PrivateMasterKey::from_xprv(ExtendedPrivKey::from_str(&opt.value)?, &opt.key_name)
@@ -76,9 +110,26 @@ pub fn start(datadir: &str, network: Network, opt: &RestoreOptions) -> Result<Ma
             let sec = hex::decode(&opt.value)?;
             PrivateMasterKey::new(network, &sec, &opt.key_name)?
         }
+        Nature::Mnemonic => {
+            let passphrase = opt.mnemonic_passphrase.as_ref().map(|s| s.as_str());
+            PrivateMasterKey::from_mnemonic(network, &opt.value, passphrase, &opt.key_name)?
+        }
+        Nature::Slip39 => {
+            let shares: Vec<&str> = opt.value.split(',').map(|s| s.trim()).collect();
+            let passphrase = opt.mnemonic_passphrase.as_ref().map(|s| s.as_str());
+            let sec = combine_shares(&shares, passphrase)?;
+            PrivateMasterKey::new(network, &sec, &opt.key_name)?
+        }
     };
 
-    let output = save_keys(datadir, network, &opt.key_name, master_key, opt.qr_version)?;
+    let output = save_keys(
+        datadir,
+        network,
+        &opt.key_name,
+        master_key,
+        opt.qr_version,
+        opt.encryption_passphrase.as_ref().map(|s| s.as_str()),
+    )?;
     Ok(output)
 }
 
@@ -86,6 +137,7 @@ pub fn start(datadir: &str, network: Network, opt: &RestoreOptions) -> Result<Ma
 mod tests {
     use crate::offline::random::RandomOptions;
     use crate::offline::restore::{Nature, RestoreOptions};
+    use crate::offline::split_key::SplitKeyOptions;
     use bitcoin::Network;
     use tempdir::TempDir;
 
@@ -106,6 +158,10 @@ mod tests {
             nature: Nature::Xprv,
             value: key_orig.key.xprv.to_string(),
             qr_version: 14,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
         };
         let key_restored =
             crate::offline::restore::start(&temp_dir_str, Network::Testnet, &restore_opts).unwrap();
@@ -119,6 +175,10 @@ mod tests {
             nature: Nature::Bech32Seed,
             value: key_orig.key.seed.as_ref().unwrap().bech32.clone(),
             qr_version: 14,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
         };
         let key_restored =
             crate::offline::restore::start(&temp_dir_str, Network::Testnet, &restore_opts).unwrap();
@@ -130,6 +190,10 @@ mod tests {
             nature: Nature::HexSeed,
             value: key_orig.key.seed.as_ref().unwrap().hex.clone(),
             qr_version: 14,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
         };
         let key_restored =
             crate::offline::restore::start(&temp_dir_str, Network::Testnet, &restore_opts).unwrap();
@@ -141,6 +205,10 @@ mod tests {
             nature: Nature::HexSeed,
             value: "X".to_string(),
             qr_version: 14,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
         };
         let result = crate::offline::restore::start(&temp_dir_str, Network::Testnet, &restore_opts);
         assert!(result.is_err());
@@ -151,6 +219,10 @@ mod tests {
             nature: Nature::Xprv,
             value: key_orig.key.xpub.to_string(),
             qr_version: 14,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
         };
         let result = crate::offline::restore::start(&temp_dir_str, Network::Testnet, &restore_opts);
         assert!(result.is_err());
@@ -161,6 +233,10 @@ mod tests {
             nature: Nature::Bech32Seed,
             value: "bc1q5lx5j4vedq9vj8rjm577annwxrppfda9hexah6".to_string(),
             qr_version: 14,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
         };
         let result = crate::offline::restore::start(&temp_dir_str, Network::Testnet, &restore_opts);
         assert!(result.is_err());
@@ -169,4 +245,137 @@ mod tests {
             "in network testnet bech32 seed must start with 'ts'"
         );
     }
+
+    #[test]
+    fn test_restore_mnemonic() {
+        let temp_dir = TempDir::new("test_restore_mnemonic").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+
+        // (mnemonic, xprv) from the official bip39 test vectors, all with passphrase "TREZOR"
+        let vectors = [
+            (
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+                "xprv9s21ZrQH143K3h3fDYiay8mocZ3afhfULfb5GX8kCBdno77K4HiA15Tg23wpbeF1pLfs1c5SPmYHrEpTuuRhxMwvKDwqdKiGJS9XFKzUsAF",
+            ),
+            (
+                "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong",
+                "xprv9s21ZrQH143K2V4oox4M8Zmhi2Fjx5XK4Lf7GKRvPSgydU3mjZuKGCTg7UPiBUD7ydVPvSLtg9hjp7MQTYsW67rZHAXeccqYqrsx8LcXnyd",
+            ),
+            (
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon art",
+                "xprv9s21ZrQH143K32qBagUJAMU2LsHg3ka7jqMcV98Y7gVeVyNStwYS3U7yVVoDZ4btbRNf4h6ibWpY22iRmXq35qgLs79f312g2kj5539ebPM",
+            ),
+            (
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic avoid letter advice cage absurd amount doctor acoustic bless",
+                "xprv9s21ZrQH143K3CSnQNYC3MqAAqHwxeTLhDbhF43A4ss4ciWNmCY9zQGvAKUSqVUf2vPHBTSE1rB2pg4avopqSiLVzXEU8KziNnVPauTqLRo",
+            ),
+        ];
+
+        for (i, (words, xprv)) in vectors.iter().enumerate() {
+            let restore_opts = RestoreOptions {
+                key_name: format!("test_restore_mnemonic_{}", i),
+                nature: Nature::Mnemonic,
+                value: words.to_string(),
+                qr_version: 14,
+                passphrase: true,
+                mnemonic_passphrase: Some("TREZOR".to_string()),
+                encrypt: false,
+                encryption_passphrase: None,
+            };
+            let key_restored =
+                crate::offline::restore::start(&temp_dir_str, Network::Bitcoin, &restore_opts)
+                    .unwrap();
+            assert_eq!(key_restored.key.xprv.to_string(), *xprv);
+            let mnemonic = key_restored.key.mnemonic.unwrap();
+            assert_eq!(mnemonic.words, *words);
+            assert!(mnemonic.passphrase_protected);
+        }
+
+        let restore_opts = RestoreOptions {
+            key_name: "test_restore_mnemonic_checksum".to_string(),
+            nature: Nature::Mnemonic,
+            value: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon".to_string(),
+            qr_version: 14,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
+        };
+        let result = crate::offline::restore::start(&temp_dir_str, Network::Bitcoin, &restore_opts);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid mnemonic: invalid checksum"
+        );
+
+        let restore_opts = RestoreOptions {
+            key_name: "test_restore_mnemonic_word".to_string(),
+            nature: Nature::Mnemonic,
+            value: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon xyzzy".to_string(),
+            qr_version: 14,
+            passphrase: false,
+            mnemonic_passphrase: None,
+            encrypt: false,
+            encryption_passphrase: None,
+        };
+        let result = crate::offline::restore::start(&temp_dir_str, Network::Bitcoin, &restore_opts);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid mnemonic: invalid word in phrase"
+        );
+    }
+
+    #[test]
+    fn test_restore_slip39() {
+        let temp_dir = TempDir::new("test_restore_slip39").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+
+        let rand_opts = RandomOptions::new("test_restore_slip39_orig".to_string());
+        let key_orig =
+            crate::offline::random::create_key(&temp_dir_str, Network::Testnet, &rand_opts)
+                .unwrap();
+        let split_opts = SplitKeyOptions {
+            key_file: key_orig.private_file.clone(),
+            group_threshold: 1,
+            groups: vec!["2of3".parse().unwrap()],
+            passphrase: true,
+            qr_dir: None,
+            qr_version: 14,
+            mnemonic_passphrase: Some("passphrase".to_string()),
+            encryption_passphrase: None,
+        };
+        let split = crate::offline::split_key::start(&split_opts).unwrap();
+        let shares = &split.groups[0].shares;
+
+        let restore_opts = RestoreOptions {
+            key_name: "test_restore_slip39_restored".to_string(),
+            nature: Nature::Slip39,
+            value: format!("{}, {}", shares[2], shares[0]),
+            qr_version: 14,
+            passphrase: true,
+            qr_dir: None,
+            qr_version: 14,
+            mnemonic_passphrase: Some("passphrase".to_string()),
+            encrypt: false,
+            encryption_passphrase: None,
+        };
+        let key_restored =
+            crate::offline::restore::start(&temp_dir_str, Network::Testnet, &restore_opts).unwrap();
+        assert_eq!(key_orig.key.fingerprint, key_restored.key.fingerprint);
+        assert_eq!(key_orig.key.xprv, key_restored.key.xprv);
+
+        let restore_opts = RestoreOptions {
+            key_name: "test_restore_slip39_one_share".to_string(),
+            nature: Nature::Slip39,
+            value: shares[1].clone(),
+            qr_version: 14,
+            passphrase: true,
+            qr_dir: None,
+            qr_version: 14,
+            mnemonic_passphrase: Some("passphrase".to_string()),
+            encrypt: false,
+            encryption_passphrase: None,
+        };
+        let result = crate::offline::restore::start(&temp_dir_str, Network::Testnet, &restore_opts);
+        assert!(result.is_err());
+    }
 }
@@ -1,13 +1,13 @@
+use crate::common::qr::QrEncoding;
+use crate::common::signatures;
 use crate::offline::print::pretty_print;
+use crate::offline::spending_policy::KeyPolicy;
 use crate::*;
-use bitcoin::blockdata::opcodes;
-use bitcoin::blockdata::script::Builder;
 use bitcoin::consensus::serialize;
-use bitcoin::hashes::Hash;
+use bitcoin::hashes::{hash160, Hash};
 use bitcoin::secp256k1::{self, Message, Secp256k1, SignOnly};
-use bitcoin::util::bip143::SighashComponents;
 use bitcoin::util::bip32::{DerivationPath, ExtendedPrivKey, ExtendedPubKey, Fingerprint};
-use bitcoin::util::psbt::Map;
+use bitcoin::util::key;
 use bitcoin::{Network, Script, SigHashType};
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
@@ -22,7 +22,7 @@ use structopt::StructOpt;
 pub struct SignOptions {
     /// File containing the master key (xpriv...)
     #[structopt(short, long, parse(from_os_str))]
-    key: PathBuf,
+    pub key: PathBuf,
 
     /// derivations to consider if psbt doesn't contain HD paths
     #[structopt(short, long, default_value = "1000")]
@@ -36,13 +36,40 @@ pub struct SignOptions {
     #[structopt(long, default_value = "14")]
     pub qr_version: i16,
 
+    /// Encoding of data not fitting a single QR code: structured-append (max 16 codes)
+    /// or ur (BC-UR animated QR of any size, `ur:crypto-psbt`)
+    #[structopt(long, default_value = "structured-append")]
+    #[serde(default)]
+    pub qr_encoding: QrEncoding,
+
+    /// Remove from the PSBT QR codes the `non_witness_utxo` of segwit inputs to save space,
+    /// signers with a spending policy refuse PSBTs without them
+    #[structopt(long)]
+    #[serde(default)]
+    pub strip_non_witness_utxo: bool,
+
+    /// Passphrase to decrypt the key file, if encrypted, the command line asks it at the prompt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+
+    /// Allow signing inputs requesting a sighash type other than ALL, their signatures don't
+    /// commit to all the inputs or outputs and could be used in a different transaction.
+    /// Keys with a spending policy refuse them anyway
+    #[structopt(long)]
+    #[serde(default)]
+    allow_any_sighash: bool,
+
     /// PSBT json file
     psbt_file: PathBuf,
 }
 
+#[derive(Debug)]
 pub struct SignResult {
     signed: bool,
     added_paths: bool,
+    /// Inputs signed with a sighash type other than ALL
+    non_all_sighash: Vec<(usize, SigHashType)>,
 }
 
 #[derive(Debug)]
@@ -54,7 +81,11 @@ struct PSBTSigner {
     secp: Secp256k1<SignOnly>,
     network: Network, // even if network is included in xprv, regtest is equal to testnet there, so we need this
     derivations: u32,
+    /// Paths of the key up to the wildcard of the wallet descriptors, eg. `m/48'/1'/0'/2'/0`,
+    /// used to deduce the key paths missing in the PSBT
+    key_paths: Vec<DerivationPath>,
     signed_by: HashSet<Fingerprint>,
+    allow_any_sighash: bool,
 }
 
 impl PSBTSigner {
@@ -71,6 +102,10 @@ impl PSBTSigner {
             );
         }
         let secp = Secp256k1::signing_only();
+        let key_paths = vec![
+            DerivationPath::from_str("m/0")?,
+            DerivationPath::from_str("m/1")?,
+        ];
 
         Ok(PSBTSigner {
             psbt: psbt.clone(),
@@ -79,8 +114,10 @@ impl PSBTSigner {
             xprv: *xprv,
             secp,
             derivations,
+            key_paths,
             network,
             signed_by: HashSet::new(),
+            allow_any_sighash: false,
         })
     }
 
@@ -89,97 +126,75 @@ impl PSBTSigner {
         let psbt_json = read_psbt_json(&opt.psbt_file)?;
         let psbt_file = opt.psbt_file.clone();
 
-        let xprv_json = read_key(&opt.key)?;
+        let passphrase = opt.encryption_passphrase.as_ref().map(|s| s.as_str());
+        let xprv_json = read_key(&opt.key, passphrase)?;
 
         let mut signer = PSBTSigner::new(&psbt, &xprv_json.xprv, network, opt.total_derivations)?;
         signer.psbt_json = Some(psbt_json);
         signer.psbt_file = Some(psbt_file);
+        signer.allow_any_sighash = opt.allow_any_sighash;
         Ok(signer)
     }
 
-    pub fn _get_partial_sigs(&self) -> Result<Vec<u8>> {
-        let mut only_partial_sigs = vec![];
-        for input in self.psbt.inputs.iter() {
-            for pair in input.get_pairs()?.iter() {
-                if pair.key.type_value == 2u8 {
-                    let vec = serialize(pair);
-                    debug!("partial sig pair {}", hex::encode(&vec));
-                    only_partial_sigs.extend(vec);
-                }
-            }
-        }
-        Ok(only_partial_sigs)
-    }
-
     pub fn sign(&mut self) -> Result<SignResult> {
         let initial_inputs = self.psbt.inputs.clone();
         let added_paths = self.init_hd_keypath_if_absent()?;
 
-        for (i, input) in self.psbt.inputs.clone().iter().enumerate() {
-            debug!("{} {:?}", i, input);
-            match input.non_witness_utxo.clone() {
-                Some(non_witness_utxo) => {
-                    let prevout = self.psbt.global.unsigned_tx.input[i].previous_output;
-                    if non_witness_utxo.txid() != prevout.txid {
-                        return Err("prevout doesn't match non_witness_utxo".into());
-                    }
-                    let script_pubkey = non_witness_utxo.output[prevout.vout as usize]
-                        .clone()
-                        .script_pubkey;
-                    match input.redeem_script.clone() {
-                        Some(redeem_script) => {
-                            if script_pubkey != redeem_script.to_p2sh() {
-                                return Err("script_pubkey does not match the redeem script converted to p2sh".into());
-                            }
-                            self.sign_input(&redeem_script, i)?;
-                        }
-                        None => {
-                            self.sign_input(&script_pubkey, i)?;
-                        }
-                    };
-                }
-                None => {
-                    let witness_utxo = input
-                        .clone()
-                        .witness_utxo
-                        .expect("both witness_utxo and non_witness_utxo are none");
-                    let script = match input.clone().redeem_script {
-                        Some(script) => {
-                            if witness_utxo.script_pubkey != script.to_p2sh() {
-                                return Err("witness_utxo script_pubkey doesn't match the redeem script converted to p2sh".into());
-                            }
-                            script
-                        }
-                        None => witness_utxo.script_pubkey,
-                    };
-                    if script.is_v0_p2wpkh() {
-                        let script = to_p2pkh(&script.as_bytes()[2..]);
-                        if !script.is_p2pkh() {
-                            return Err("it is not a p2pkh script".into());
-                        }
-                        self.sign_input(&script, i)?;
-                    } else {
-                        let wit_script = input
-                            .clone()
-                            .witness_script
-                            .expect("witness_script is none");
-                        if script != wit_script.to_v0_p2wsh() {
-                            return Err(
-                                "script and witness script to v0 p2wsh doesn't match".into()
-                            );
-                        }
-                        self.sign_input(&wit_script, i)?;
-                    }
-                }
-            }
+        for i in 0..self.psbt.inputs.len() {
+            debug!("{} {:?}", i, self.psbt.inputs[i]);
+            let script = signatures::script_code(&self.psbt, i)?;
+            self.sign_input(&script, i)?;
         }
-        let signed = self.psbt.inputs != initial_inputs;
+        // added key paths alone are not a signature, the spending ledger must not count them
+        let signed_inputs: Vec<usize> = self
+            .psbt
+            .inputs
+            .iter()
+            .zip(initial_inputs.iter())
+            .enumerate()
+            .filter(|(_, (input, initial))| input.partial_sigs != initial.partial_sigs)
+            .map(|(i, _)| i)
+            .collect();
+        let signed = !signed_inputs.is_empty();
+        let non_all_sighash = signed_inputs
+            .into_iter()
+            .map(|i| (i, &self.psbt.inputs[i]))
+            .filter_map(|(i, input)| match input.sighash_type {
+                Some(sighash_type) if sighash_type != SigHashType::All => Some((i, sighash_type)),
+                _ => None,
+            })
+            .collect();
         Ok(SignResult {
             added_paths,
             signed,
+            non_all_sighash,
         })
     }
 
+    /// Use the paths of the key in the wallet descriptors to deduce missing key paths, in place
+    /// of `m/0` and `m/1` of wallets using the master key, keys are recognized by the fingerprint
+    /// of their origin or, without origin, by being the master key
+    fn use_wallet_key_paths(&mut self, wallet: &WalletJson) -> Result<()> {
+        let fingerprint = self.xprv.fingerprint(&self.secp);
+        let master = ExtendedPubKey::from_private(&self.secp, &self.xprv);
+        let (main, change) = wallet.parse_descriptors()?;
+        let mut key_paths = vec![];
+        for key in main.script.keys().into_iter().chain(change.script.keys()) {
+            let mut path = match &key.xpub.origin {
+                Some(origin) if origin.fingerprint == fingerprint => origin.path[..].to_vec(),
+                None if key.xpub.xpub == master => vec![],
+                _ => continue,
+            };
+            path.extend(key.path.iter().cloned());
+            let path = DerivationPath::from(path);
+            if key.wildcard && !key_paths.contains(&path) {
+                key_paths.push(path);
+            }
+        }
+        self.key_paths = key_paths;
+        Ok(())
+    }
+
     fn init_hd_keypath_if_absent(&mut self) -> Result<bool> {
         // temp code for handling psbt generated from core without hd paths
         let outputs_empty = self.psbt.inputs.iter().any(|i| i.hd_keypaths.is_empty());
@@ -189,15 +204,14 @@ impl PSBTSigner {
         if outputs_empty || inputs_empty {
             info!("Provided PSBT does not contain all HD key paths, trying to deduce them...");
             let mut keys = HashMap::new();
-            for i in 0..=1 {
-                let derivation_path = DerivationPath::from_str(&format!("m/{}", i))?;
-                let first = self.xprv.derive_priv(&self.secp, &derivation_path)?;
+            for key_path in self.key_paths.iter() {
+                let first = self.xprv.derive_priv(&self.secp, key_path)?;
                 for j in 0..=self.derivations {
                     let derivation_path = DerivationPath::from_str(&format!("m/{}", j))?;
                     let derived = first.derive_priv(&self.secp, &derivation_path)?;
                     let derived_pubkey = ExtendedPubKey::from_private(&self.secp, &derived);
                     let complete_derivation_path =
-                        DerivationPath::from_str(&format!("m/{}/{}", i, j))?;
+                        DerivationPath::from_str(&format!("{}/{}", key_path, j))?;
                     keys.insert(
                         derived_pubkey.public_key,
                         (self.xprv.fingerprint(&self.secp), complete_derivation_path),
@@ -206,8 +220,12 @@ impl PSBTSigner {
             }
 
             for input in self.psbt.inputs.iter_mut() {
-                if let Some(ref witness_script) = input.witness_script {
-                    let script_keys = extract_pub_keys(&witness_script)?;
+                let script = input
+                    .witness_script
+                    .as_ref()
+                    .or(input.redeem_script.as_ref());
+                if let Some(script) = script {
+                    let script_keys = keys_in_script(script, &keys)?;
                     for key in script_keys {
                         if keys.contains_key(&key) {
                             input.hd_keypaths.insert(
@@ -221,8 +239,12 @@ impl PSBTSigner {
             }
 
             for output in self.psbt.outputs.iter_mut() {
-                if let Some(ref witness_script) = output.witness_script {
-                    let script_keys = extract_pub_keys(&witness_script)?;
+                let script = output
+                    .witness_script
+                    .as_ref()
+                    .or(output.redeem_script.as_ref());
+                if let Some(script) = script {
+                    let script_keys = keys_in_script(script, &keys)?;
                     for key in script_keys {
                         if keys.contains_key(&key) {
                             output.hd_keypaths.insert(
@@ -242,15 +264,30 @@ impl PSBTSigner {
     }
 
     fn sign_input(&mut self, script: &Script, input_index: usize) -> Result<()> {
-        let input = &mut self.psbt.inputs[input_index];
-        let tx = &self.psbt.global.unsigned_tx;
-        let is_witness = input.non_witness_utxo.is_none();
         let my_fing = self.xprv.fingerprint(&self.secp);
+        let input = &self.psbt.inputs[input_index];
+        let to_sign: Vec<(key::PublicKey, DerivationPath)> = input
+            .hd_keypaths
+            .iter()
+            .filter(|(_, (fing, _))| fing == &my_fing)
+            .map(|(pubkey, (_, child))| (*pubkey, child.clone()))
+            .collect();
+        if to_sign.is_empty() {
+            return Ok(());
+        }
 
-        for (pubkey, (fing, child)) in input.hd_keypaths.iter() {
-            if fing != &my_fing {
-                continue;
-            }
+        let sighash = input.sighash_type.unwrap_or(SigHashType::All);
+        if sighash != SigHashType::All && !self.allow_any_sighash {
+            return Err(format!(
+                "input {} requests sighash type {:?}, sign with --allow-any-sighash to accept it",
+                input_index, sighash
+            )
+            .into());
+        }
+        let hash = signatures::signature_hash(&self.psbt, input_index, script, sighash)?;
+        let msg = &Message::from_slice(&hash.into_inner()[..])?;
+
+        for (pubkey, child) in to_sign {
             let privkey = self.xprv.derive_priv(&self.secp, &child)?;
             let derived_pubkey =
                 secp256k1::PublicKey::from_secret_key(&self.secp, &privkey.private_key.key);
@@ -259,29 +296,24 @@ impl PSBTSigner {
                     "pubkey derived and expected differs even if fingerprint matches!".into(),
                 );
             }
-            let (hash, sighash);
-            if is_witness {
-                let wutxo = input.clone().witness_utxo;
-                let value = wutxo.ok_or_else(fn_err("witness_utxo is empty"))?.value;
-                let cmp = SighashComponents::new(tx);
-                hash = cmp.sighash_all(&tx.input[input_index], script, value);
-                sighash = input.sighash_type.unwrap_or(SigHashType::All);
-            } else {
-                sighash = input.sighash_type.ok_or_else(fn_err("sighash empty"))?;
-                hash = tx.signature_hash(input_index, &script, sighash.as_u32());
-            };
-            let msg = &Message::from_slice(&hash.into_inner()[..])?;
             let key = &privkey.private_key.key;
             let signature = self.secp.sign(msg, key);
             let mut signature = signature.serialize_der().to_vec();
-            signature.push(sighash.as_u32() as u8); // TODO how to properly do this?
-            input.partial_sigs.insert(pubkey.clone(), signature);
-            self.signed_by.insert(fing.clone());
+            signature.push(sighash.as_u32() as u8);
+            self.psbt.inputs[input_index]
+                .partial_sigs
+                .insert(pubkey, signature);
+            self.signed_by.insert(my_fing);
         }
         Ok(())
     }
 
-    fn save_signed_psbt_file(&self, qr_version: i16) -> Result<PathBuf> {
+    fn save_signed_psbt_file(
+        &self,
+        qr_version: i16,
+        qr_encoding: QrEncoding,
+        strip: bool,
+    ) -> Result<PathBuf> {
         match (&self.psbt_file, &self.psbt_json) {
             (Some(psbt_file), Some(psbt_json)) => {
                 let psbt_bytes = serialize(&self.psbt);
@@ -319,8 +351,8 @@ impl PSBTSigner {
                     fs::create_dir(&psbt_qr_path)?;
                 }
                 psbt_qr_path.push("filename");
-                let psbt_bytes = serde_json::to_vec(&psbt_json)?;
-                let _qr_files = qr::save_qrs(psbt_bytes, psbt_qr_path, qr_version)?;
+                let _qr_files =
+                    qr::save_psbt_qrs(&psbt_json, psbt_qr_path, qr_version, qr_encoding, strip)?;
 
                 /*let output = PsbtJsonOutput {
                     psbt: psbt_json,
@@ -342,18 +374,58 @@ impl PSBTSigner {
 pub fn start(opt: &SignOptions, network: Network) -> Result<PsbtPrettyPrint> {
     let wallet = read_wallet(&opt.wallet_descriptor_file)?;
     let mut psbt_signer = PSBTSigner::from_opt(opt, network)?;
+    psbt_signer.use_wallet_key_paths(&wallet)?;
     debug!("{:?}", psbt_signer);
 
+    let now = now()?;
+    let mut key_policy = KeyPolicy::read(&opt.key)?;
+    let mut amount = 0;
+    if let Some(key_policy) = key_policy.as_ref() {
+        let changepos = psbt_signer
+            .psbt_json
+            .as_ref()
+            .map(|p| p.changepos)
+            .unwrap_or(-1);
+        let (spent, violations) =
+            key_policy.check(&psbt_signer.psbt, changepos, &wallet, network, now)?;
+        if !violations.is_empty() {
+            return Err(Error::SpendingPolicy(violations));
+        }
+        amount = spent;
+    }
+
     let sign_result = psbt_signer.sign()?;
     let mut psbt_print = psbt_signer.pretty_print(&vec![wallet])?;
 
     if sign_result.added_paths {
         psbt_print.info.push("Added paths".to_string());
     }
+    for (i, sighash_type) in sign_result.non_all_sighash.iter() {
+        psbt_print.info.push(format!(
+            "WARNING: input {} signed with sighash type {:?}, the signature doesn't commit to the whole transaction",
+            i, sighash_type
+        ));
+    }
     if sign_result.signed {
-        let psbt_new_file = psbt_signer.save_signed_psbt_file(opt.qr_version)?;
+        // record the spending before the signature leaves the signer, so that a failure can
+        // only overcount
+        if let Some(key_policy) = key_policy.as_mut() {
+            let txid = psbt_signer.psbt.global.unsigned_tx.txid().to_string();
+            key_policy.record(&txid, amount, now)?;
+        }
+        let psbt_new_file = psbt_signer.save_signed_psbt_file(
+            opt.qr_version,
+            opt.qr_encoding,
+            opt.strip_non_witness_utxo,
+        )?;
         psbt_print.info.push("Added signatures".to_string());
         psbt_print.psbt_file = psbt_new_file;
+        if key_policy.is_some() {
+            psbt_print.info.push(format!(
+                "Spending policy satisfied, {} sat sent outside the wallet",
+                amount
+            ));
+        }
     } else {
         psbt_print.info.push("No signature added".to_string());
     }
@@ -361,36 +433,32 @@ pub fn start(opt: &SignOptions, network: Network) -> Result<PsbtPrettyPrint> {
     Ok(psbt_print)
 }
 
-pub fn read_key(path: &PathBuf) -> Result<PrivateMasterKey> {
-    let is_key = path
-        .file_name()
-        .ok_or_else(|| Error::Generic("no file_name".into()))?
-        .to_str()
-        .ok_or_else(|| Error::Generic("OsStr".into()))?
-        == "PRIVATE.json";
-    if !is_key {
-        return Err(Error::Generic("private name MUST be PRIVATE.json".into()));
+/// Keys used in the script, directly or by hash as in miniscript `pk_h`, only the hashes of the
+/// given `keys` can be resolved
+fn keys_in_script(
+    script: &Script,
+    keys: &HashMap<key::PublicKey, (Fingerprint, DerivationPath)>,
+) -> Result<Vec<key::PublicKey>> {
+    let mut result = extract_pub_keys(script)?;
+    let hashes = extract_pub_key_hashes(script);
+    for key in keys.keys() {
+        if hashes.contains(&hash160::Hash::hash(&key.to_bytes())) {
+            result.push(key.clone());
+        }
     }
-    let xprv_string = std::fs::read(path)?;
-    Ok(serde_json::from_slice(&xprv_string)?)
-}
-
-fn to_p2pkh(pubkey_hash: &[u8]) -> Script {
-    Builder::new()
-        .push_opcode(opcodes::all::OP_DUP)
-        .push_opcode(opcodes::all::OP_HASH160)
-        .push_slice(pubkey_hash)
-        .push_opcode(opcodes::all::OP_EQUALVERIFY)
-        .push_opcode(opcodes::all::OP_CHECKSIG)
-        .into_script()
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::common::fixtures::{self, ACCOUNT_1_XPUB, ACCOUNT_XPUB};
     use crate::offline::sign::*;
-    use crate::{psbt_from_base64, PsbtJson, PSBT};
+    use crate::{multisig_script, psbt_from_base64, PsbtJson, PSBT};
+    use bitcoin::blockdata::opcodes;
+    use bitcoin::blockdata::script::Builder;
     use bitcoin::consensus::serialize;
     use bitcoin::util::bip32::ExtendedPubKey;
+    use bitcoin::{OutPoint, Transaction, TxIn, TxOut};
     use flate2::write::ZlibEncoder;
     use flate2::Compression;
     use std::io::Write;
@@ -537,6 +605,192 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_sighash_types() {
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.json");
+        let (_, psbt) = extract_psbt(bytes);
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.key");
+        let key: crate::PrivateMasterKey = serde_json::from_slice(bytes).unwrap();
+        let types = vec![
+            SigHashType::None,
+            SigHashType::Single,
+            SigHashType::AllPlusAnyoneCanPay,
+            SigHashType::NonePlusAnyoneCanPay,
+            SigHashType::SinglePlusAnyoneCanPay,
+        ];
+        for sighash_type in types {
+            let mut psbt = psbt.clone();
+            for input in psbt.inputs.iter_mut() {
+                input.sighash_type = Some(sighash_type);
+            }
+            let mut psbt_signer = PSBTSigner::new(&psbt, &key.xprv, key.xprv.network, 10).unwrap();
+            assert_eq!(
+                psbt_signer.sign().unwrap_err().to_string(),
+                format!(
+                    "input 0 requests sighash type {:?}, sign with --allow-any-sighash to accept it",
+                    sighash_type
+                )
+            );
+
+            psbt_signer.allow_any_sighash = true;
+            let sign_result = psbt_signer.sign().unwrap();
+            assert!(sign_result.signed);
+            assert_eq!(
+                sign_result.non_all_sighash,
+                vec![(0, sighash_type), (1, sighash_type)]
+            );
+            for (i, input) in psbt_signer.psbt.inputs.iter().enumerate() {
+                assert_eq!(input.partial_sigs.len(), 2);
+                for signature in input.partial_sigs.values() {
+                    assert_eq!(*signature.last().unwrap() as u32, sighash_type.as_u32());
+                }
+                let signed_by = signatures::verify_partial_sigs(&psbt_signer.psbt, i).unwrap();
+                assert_eq!(signed_by.len(), 2);
+            }
+        }
+
+        // only sighash ALL signatures are unchanged by the flag
+        let mut psbt_signer = PSBTSigner::new(&psbt, &key.xprv, key.xprv.network, 10).unwrap();
+        psbt_signer.allow_any_sighash = true;
+        assert!(psbt_signer.sign().unwrap().non_all_sighash.is_empty());
+        let bytes = include_bytes!("../../test_data/sign/psbt_bip.signed.json");
+        let (_, psbt_signed) = extract_psbt(bytes);
+        assert_eq!(psbt_signer.psbt, psbt_signed);
+
+        // signing again adds no signature, the spending ledger must not record it
+        let sign_result = psbt_signer.sign().unwrap();
+        assert!(!sign_result.signed);
+        assert!(sign_result.non_all_sighash.is_empty());
+    }
+
+    #[test]
+    fn test_wallet_key_paths() {
+        // master key of the bip32 test vector 1, the one of the BIP48 accounts of the fixtures
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let xprv = ExtendedPrivKey::new_master(Network::Testnet, &seed).unwrap();
+        let descriptor = format!("wsh(multi(2,{}/0/*,{}/0/*))", ACCOUNT_XPUB, ACCOUNT_1_XPUB);
+        let wallet = fixtures::wallet("bip48", &descriptor);
+        let (main, _) = wallet.parse_descriptors().unwrap();
+        let secp = Secp256k1::verification_only();
+        let keys: Vec<key::PublicKey> = main
+            .script
+            .keys()
+            .iter()
+            .map(|k| k.derive(&secp, 5).unwrap())
+            .collect();
+        let witness_script = multisig_script(2, &keys, false);
+        let tx = Transaction {
+            version: 2,
+            lock_time: 0,
+            input: vec![TxIn {
+                previous_output: OutPoint::default(),
+                script_sig: Script::new(),
+                sequence: 0xffff_fffd,
+                witness: vec![],
+            }],
+            output: vec![TxOut {
+                value: 90_000,
+                script_pubkey: witness_script.to_v0_p2wsh(),
+            }],
+        };
+        let mut psbt = PSBT::from_unsigned_tx(tx).unwrap();
+        psbt.inputs[0].witness_utxo = Some(TxOut {
+            value: 100_000,
+            script_pubkey: witness_script.to_v0_p2wsh(),
+        });
+        psbt.inputs[0].witness_script = Some(witness_script);
+
+        // the master key paths m/0 and m/1 don't derive the keys of BIP48 accounts
+        let mut psbt_signer = PSBTSigner::new(&psbt, &xprv, Network::Testnet, 10).unwrap();
+        let sign_result = psbt_signer.sign().unwrap();
+        assert!(!sign_result.added_paths);
+        assert!(!sign_result.signed);
+
+        psbt_signer.use_wallet_key_paths(&wallet).unwrap();
+        assert_eq!(psbt_signer.key_paths.len(), 4);
+        let sign_result = psbt_signer.sign().unwrap();
+        assert!(sign_result.added_paths);
+        assert!(sign_result.signed);
+        let input = &psbt_signer.psbt.inputs[0];
+        let paths: Vec<String> = input
+            .hd_keypaths
+            .values()
+            .map(|p| p.1.to_string())
+            .collect();
+        assert!(paths.contains(&"m/48'/1'/0'/2'/0/5".to_string()));
+        assert!(paths.contains(&"m/48'/1'/1'/2'/0/5".to_string()));
+        assert_eq!(input.partial_sigs.len(), 2);
+
+        // a wallet without the key deduces no path
+        let other = fixtures::wallet("other", &format!("wsh(multi(1,{}/0/*))", ACCOUNT_1_XPUB));
+        let other_xprv = ExtendedPrivKey::new_master(Network::Testnet, &[1u8; 32]).unwrap();
+        let mut psbt_signer = PSBTSigner::new(&psbt, &other_xprv, Network::Testnet, 10).unwrap();
+        psbt_signer.use_wallet_key_paths(&other).unwrap();
+        assert!(psbt_signer.key_paths.is_empty());
+        assert!(!psbt_signer.sign().unwrap().added_paths);
+    }
+
+    #[test]
+    fn test_keys_in_script() {
+        let secp = Secp256k1::signing_only();
+        let xprv = ExtendedPrivKey::new_master(Network::Testnet, &[1u8; 32]).unwrap();
+        let mut keys = HashMap::new();
+        for i in 0..3 {
+            let path = DerivationPath::from_str(&format!("m/0/{}", i)).unwrap();
+            let derived = xprv.derive_priv(&secp, &path).unwrap();
+            let pubkey = ExtendedPubKey::from_private(&secp, &derived).public_key;
+            keys.insert(pubkey, (xprv.fingerprint(&secp), path));
+        }
+        let mut sorted: Vec<_> = keys.keys().cloned().collect();
+        sorted.sort_by_key(|k| k.to_bytes());
+
+        // miniscript or_d(pk(A),and_v(v:pkh(B),older(144)))
+        let script = Builder::new()
+            .push_key(&sorted[0])
+            .push_opcode(opcodes::all::OP_CHECKSIG)
+            .push_opcode(opcodes::all::OP_IFDUP)
+            .push_opcode(opcodes::all::OP_NOTIF)
+            .push_opcode(opcodes::all::OP_DUP)
+            .push_opcode(opcodes::all::OP_HASH160)
+            .push_slice(&hash160::Hash::hash(&sorted[1].to_bytes())[..])
+            .push_opcode(opcodes::all::OP_EQUALVERIFY)
+            .push_opcode(opcodes::all::OP_CHECKSIGVERIFY)
+            .push_int(144)
+            .push_opcode(opcodes::all::OP_CSV)
+            .push_opcode(opcodes::all::OP_ENDIF)
+            .into_script();
+        let found = keys_in_script(&script, &keys).unwrap();
+        assert_eq!(found, vec![sorted[0], sorted[1]]);
+    }
+
+    #[test]
+    fn test_multisig_order() {
+        let secp = Secp256k1::signing_only();
+        let xprv = ExtendedPrivKey::new_master(Network::Testnet, &[2u8; 32]).unwrap();
+        let keys: Vec<key::PublicKey> = (0..3)
+            .map(|i| {
+                let path = DerivationPath::from_str(&format!("m/0/{}", i)).unwrap();
+                let derived = xprv.derive_priv(&secp, &path).unwrap();
+                ExtendedPubKey::from_private(&secp, &derived).public_key
+            })
+            .collect();
+        let mut reversed = keys.clone();
+        reversed.reverse();
+
+        let sorted = multisig_script(2, &keys, true);
+        assert_eq!(sorted, multisig_script(2, &reversed, true));
+        let mut sorted_keys = keys.clone();
+        sorted_keys.sort_by_key(|k| k.to_bytes());
+        assert_eq!(sorted, multisig_script(2, &sorted_keys, false));
+        assert_eq!(extract_pub_keys(&sorted).unwrap(), sorted_keys);
+
+        let unsorted = multisig_script(2, &keys, false);
+        let other = multisig_script(2, &reversed, false);
+        assert_ne!(unsorted, other);
+        assert_eq!(extract_pub_keys(&unsorted).unwrap(), keys);
+        assert_eq!(extract_pub_keys(&other).unwrap(), reversed);
+    }
+
     pub fn psbt_to_base64(psbt: &PSBT) -> String {
         base64::encode(&serialize(psbt))
     }
@@ -0,0 +1,533 @@
+use crate::offline::print::{address, derives_script};
+use crate::*;
+use bitcoin::{Network, SigHashType, TxOut};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// Name of the spending policy file, in the directory of the key
+pub const POLICY_FILE: &str = "spending_policy.json";
+
+/// Name of the file recording the transactions signed under the policy, in the directory of the key
+pub const LEDGER_FILE: &str = "spending_ledger.json";
+
+/// Name of the file created while a signer uses the ledger, in the directory of the key
+pub const LOCK_FILE: &str = "spending_ledger.lock";
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// Rules a transaction must satisfy to be signed with the key, rules not set are not checked,
+/// inputs requesting a sighash type other than ALL are always refused since their signatures
+/// could be reused in a transaction the policy never saw, as are inputs without `non_witness_utxo`
+/// since the amounts of `witness_utxo` are not committed by the spent txid.
+/// Amounts are in satoshi and count the fee and the outputs not paying back to the signing wallet,
+/// that is without a script derived from the wallet descriptors at the index of their key paths
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SpendingPolicy {
+    /// Max amount sent by a single transaction
+    #[serde(default)]
+    pub max_amount_per_tx: Option<u64>,
+
+    /// Max amount sent by the transactions signed in the same UTC day
+    #[serde(default)]
+    pub daily_limit: Option<u64>,
+
+    /// Addresses the transaction may send to
+    #[serde(default)]
+    pub allowed_destinations: Option<Vec<String>>,
+
+    /// Require an output paying back to the signing wallet, which must be the change output
+    /// declared in the PSBT if any
+    #[serde(default)]
+    pub require_change_to_wallet: bool,
+
+    /// Max fee rate in satoshi per virtual byte
+    #[serde(default)]
+    pub max_fee_rate: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LedgerEntry {
+    pub txid: String,
+    pub timestamp: u64,
+    pub amount: u64,
+}
+
+/// Transactions signed under the spending policy, to enforce the daily limit
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SpendingLedger {
+    pub entries: Vec<LedgerEntry>,
+}
+
+/// Lock file held while the ledger is in use, removed when dropped
+#[derive(Debug)]
+struct LedgerLock(PathBuf);
+
+impl LedgerLock {
+    fn acquire(path: PathBuf) -> Result<Self> {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => Ok(LedgerLock(path)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(format!(
+                "spending ledger locked by another signer, remove {:?} if no other signer is running",
+                path
+            )
+            .into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Drop for LedgerLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Spending policy of a key with the ledger of the transactions signed
+#[derive(Debug)]
+pub struct KeyPolicy {
+    pub policy: SpendingPolicy,
+    pub ledger: SpendingLedger,
+    ledger_file: PathBuf,
+    _lock: Option<LedgerLock>,
+}
+
+impl KeyPolicy {
+    /// Read the policy in the directory of the key file, `None` if the key has no policy.
+    /// The ledger is locked until the returned value is dropped, so that concurrent signers
+    /// can't both spend the daily limit
+    pub fn read(key_file: &Path) -> Result<Option<Self>> {
+        let dir = key_file.parent().ok_or_else(fn_err("no parent"))?;
+        let policy_file = dir.join(POLICY_FILE);
+        if !policy_file.exists() {
+            return Ok(None);
+        }
+        let lock = LedgerLock::acquire(dir.join(LOCK_FILE))?;
+        let policy = serde_json::from_slice(&fs::read(&policy_file)?)?;
+        let ledger_file = dir.join(LEDGER_FILE);
+        let ledger = if ledger_file.exists() {
+            serde_json::from_slice(&fs::read(&ledger_file)?)?
+        } else {
+            SpendingLedger::default()
+        };
+        Ok(Some(KeyPolicy {
+            policy,
+            ledger,
+            ledger_file,
+            _lock: Some(lock),
+        }))
+    }
+
+    /// Check the transaction against the policy at time `now` (seconds since epoch),
+    /// returns the amount sent outside the wallet, fee included, and the rules violated
+    pub fn check(
+        &self,
+        psbt: &PSBT,
+        changepos: i32,
+        wallet: &WalletJson,
+        network: Network,
+        now: u64,
+    ) -> Result<(u64, Vec<PolicyViolation>)> {
+        let policy = &self.policy;
+        let tx = &psbt.global.unsigned_tx;
+        let mut violations = vec![];
+        for (input, psbt_input) in psbt.inputs.iter().enumerate() {
+            match psbt_input.sighash_type {
+                Some(sighash_type) if sighash_type != SigHashType::All => {
+                    violations.push(PolicyViolation::NonAllSighash {
+                        input,
+                        sighash_type: format!("{:?}", sighash_type),
+                    })
+                }
+                _ => (),
+            }
+        }
+        let input_value: u64 = verified_previous_outputs(psbt)?
+            .iter()
+            .map(|o| o.value)
+            .sum();
+        let output_value: u64 = tx.output.iter().map(|o| o.value).sum();
+        let fee = input_value
+            .checked_sub(output_value)
+            .ok_or_else(fn_err("outputs value is greater than inputs value"))?;
+        let mut amount = fee;
+        let mut to_wallet = vec![];
+        for (i, output) in tx.output.iter().enumerate() {
+            let keypaths = &psbt.outputs[i].hd_keypaths;
+            let mine = derives_script(wallet, keypaths, &output.script_pubkey, network);
+            to_wallet.push(mine);
+            if mine {
+                continue;
+            }
+            amount += output.value;
+            if let Some(allowed) = &policy.allowed_destinations {
+                let address = address(&output.script_pubkey, network)?;
+                if !allowed.contains(&address) {
+                    violations.push(PolicyViolation::DestinationNotAllowed { output: i, address });
+                }
+            }
+        }
+
+        if let Some(max) = policy.max_amount_per_tx {
+            if amount > max {
+                violations.push(PolicyViolation::MaxAmountPerTx { amount, max });
+            }
+        }
+
+        if let Some(limit) = policy.daily_limit {
+            let spent_today = self.ledger.spent_on_day(now, &tx.txid().to_string());
+            if spent_today + amount > limit {
+                violations.push(PolicyViolation::DailyLimit {
+                    amount,
+                    spent_today,
+                    limit,
+                });
+            }
+        }
+
+        if policy.require_change_to_wallet {
+            if changepos >= 0 {
+                let output = changepos as usize;
+                if !to_wallet.get(output).cloned().unwrap_or(false) {
+                    violations.push(PolicyViolation::ChangeNotToWallet { output });
+                }
+            } else if !to_wallet.contains(&true) {
+                violations.push(PolicyViolation::NoChangeToWallet);
+            }
+        }
+
+        if let Some(max) = policy.max_fee_rate {
+            let rate = fee as f64 / (estimate_weight(psbt)? / 4) as f64;
+            if rate > max {
+                violations.push(PolicyViolation::MaxFeeRate { rate, max });
+            }
+        }
+
+        Ok((amount, violations))
+    }
+
+    /// Record in the ledger the amount sent by the transaction signed at time `now`,
+    /// a transaction already recorded is not counted twice. The ledger is written in a temporary
+    /// file and then renamed, so that it is never left truncated
+    pub fn record(&mut self, txid: &str, amount: u64, now: u64) -> Result<()> {
+        if self.ledger.entries.iter().any(|e| e.txid == txid) {
+            return Ok(());
+        }
+        self.ledger.entries.push(LedgerEntry {
+            txid: txid.to_string(),
+            timestamp: now,
+            amount,
+        });
+        let mut temp_file = self.ledger_file.clone();
+        temp_file.set_extension("json.tmp");
+        fs::write(&temp_file, serde_json::to_string_pretty(&self.ledger)?)?;
+        fs::rename(&temp_file, &self.ledger_file)?;
+        Ok(())
+    }
+}
+
+/// Outputs spent by the inputs of the PSBT, read only from their `non_witness_utxo` after
+/// checking it is the spent transaction, an understated `witness_utxo` would hide part of the fee
+fn verified_previous_outputs(psbt: &PSBT) -> Result<Vec<TxOut>> {
+    let tx = &psbt.global.unsigned_tx;
+    let mut previous_outputs = vec![];
+    for (i, (input, txin)) in psbt.inputs.iter().zip(tx.input.iter()).enumerate() {
+        let prev_tx = input.non_witness_utxo.as_ref().ok_or_else(|| {
+            Error::Generic(format!(
+                "input {} without non_witness_utxo, required by the spending policy",
+                i
+            ))
+        })?;
+        let prevout = txin.previous_output;
+        if prev_tx.txid() != prevout.txid {
+            return Err("prevout doesn't match non_witness_utxo".into());
+        }
+        let previous_output = prev_tx
+            .output
+            .get(prevout.vout as usize)
+            .ok_or_else(fn_err("can't find txout"))?;
+        signatures::check_witness_utxo(psbt, i)?;
+        previous_outputs.push(previous_output.clone());
+    }
+    Ok(previous_outputs)
+}
+
+impl SpendingLedger {
+    /// Amount spent in the UTC day of `now` by transactions other than `txid`
+    fn spent_on_day(&self, now: u64, txid: &str) -> u64 {
+        let day = now / SECONDS_PER_DAY;
+        self.entries
+            .iter()
+            .filter(|e| e.timestamp / SECONDS_PER_DAY == day && e.txid != txid)
+            .map(|e| e.amount)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::offline::print::pretty_print;
+    use crate::offline::print::tests::{wallet_psbt, DESTINATION};
+    use crate::offline::spending_policy::{
+        KeyPolicy, LedgerEntry, SpendingLedger, SpendingPolicy, LEDGER_FILE, LOCK_FILE, POLICY_FILE,
+    };
+    use crate::PolicyViolation;
+    use bitcoin::{Network, SigHashType};
+    use tempdir::TempDir;
+
+    const NOW: u64 = 1_600_000_000;
+
+    fn key_policy(policy: SpendingPolicy, entries: Vec<LedgerEntry>) -> KeyPolicy {
+        KeyPolicy {
+            policy,
+            ledger: SpendingLedger { entries },
+            ledger_file: LEDGER_FILE.into(),
+            _lock: None,
+        }
+    }
+
+    #[test]
+    fn test_spending_policy() {
+        // first output is the payment, the second one the change
+        let (wallet, mut psbt) = wallet_psbt();
+        let txid = psbt.global.unsigned_tx.txid().to_string();
+        let check = |key_policy: &KeyPolicy, changepos: i32| {
+            key_policy
+                .check(&psbt, changepos, &wallet, Network::Testnet, NOW)
+                .unwrap()
+        };
+
+        let unlimited = key_policy(SpendingPolicy::default(), vec![]);
+        assert_eq!(check(&unlimited, 1), (61_000, vec![]));
+
+        let policy = SpendingPolicy {
+            max_amount_per_tx: Some(50_000),
+            ..SpendingPolicy::default()
+        };
+        assert_eq!(
+            check(&key_policy(policy, vec![]), 1).1,
+            vec![PolicyViolation::MaxAmountPerTx {
+                amount: 61_000,
+                max: 50_000
+            }]
+        );
+
+        // the fee is spent too, a high fee goes over the max amount
+        let policy = SpendingPolicy {
+            max_amount_per_tx: Some(70_000),
+            ..SpendingPolicy::default()
+        };
+        assert!(check(&key_policy(policy.clone(), vec![]), 1).1.is_empty());
+        let (_, mut high_fee) = wallet_psbt();
+        high_fee.global.unsigned_tx.output[1].value -= 20_000;
+        assert_eq!(
+            key_policy(policy, vec![])
+                .check(&high_fee, 1, &wallet, Network::Testnet, NOW)
+                .unwrap()
+                .1,
+            vec![PolicyViolation::MaxAmountPerTx {
+                amount: 81_000,
+                max: 70_000
+            }]
+        );
+
+        let policy = SpendingPolicy {
+            daily_limit: Some(100_000),
+            ..SpendingPolicy::default()
+        };
+        let entry = |txid: &str, timestamp: u64| LedgerEntry {
+            txid: txid.to_string(),
+            timestamp,
+            amount: 60_000,
+        };
+        let yesterday = key_policy(policy.clone(), vec![entry("other", NOW - 86_400)]);
+        assert!(check(&yesterday, 1).1.is_empty());
+        let same_tx = key_policy(policy.clone(), vec![entry(&txid, NOW - 3_600)]);
+        assert!(check(&same_tx, 1).1.is_empty());
+        let today = key_policy(policy, vec![entry("other", NOW - 3_600)]);
+        assert_eq!(
+            check(&today, 1).1,
+            vec![PolicyViolation::DailyLimit {
+                amount: 61_000,
+                spent_today: 60_000,
+                limit: 100_000
+            }]
+        );
+
+        let policy = SpendingPolicy {
+            allowed_destinations: Some(vec![DESTINATION.to_string()]),
+            ..SpendingPolicy::default()
+        };
+        assert!(check(&key_policy(policy, vec![]), 1).1.is_empty());
+        let policy = SpendingPolicy {
+            allowed_destinations: Some(vec![]),
+            ..SpendingPolicy::default()
+        };
+        assert_eq!(
+            check(&key_policy(policy, vec![]), 1).1,
+            vec![PolicyViolation::DestinationNotAllowed {
+                output: 0,
+                address: DESTINATION.to_string()
+            }]
+        );
+
+        let policy = SpendingPolicy {
+            require_change_to_wallet: true,
+            ..SpendingPolicy::default()
+        };
+        let change = key_policy(policy, vec![]);
+        assert!(check(&change, 1).1.is_empty());
+        assert!(check(&change, -1).1.is_empty());
+        assert_eq!(
+            check(&change, 0).1,
+            vec![PolicyViolation::ChangeNotToWallet { output: 0 }]
+        );
+        assert_eq!(
+            check(&change, 2).1,
+            vec![PolicyViolation::ChangeNotToWallet { output: 2 }]
+        );
+
+        let rate = pretty_print(&psbt, Network::Testnet, &[]).unwrap().fee.rate;
+        let mut policy = SpendingPolicy {
+            max_fee_rate: Some(rate),
+            ..SpendingPolicy::default()
+        };
+        assert!(check(&key_policy(policy.clone(), vec![]), 1).1.is_empty());
+        policy.max_fee_rate = Some(rate / 2.0);
+        assert_eq!(
+            check(&key_policy(policy, vec![]), 1).1,
+            vec![PolicyViolation::MaxFeeRate {
+                rate,
+                max: rate / 2.0
+            }]
+        );
+
+        // the payment output with our key path is not change, its script is not of the wallet
+        let keypaths = psbt.outputs[1].hd_keypaths.clone();
+        psbt.outputs[0].hd_keypaths = keypaths;
+        let (amount, violations) = change
+            .check(&psbt, 0, &wallet, Network::Testnet, NOW)
+            .unwrap();
+        assert_eq!(amount, 61_000);
+        assert_eq!(
+            violations,
+            vec![PolicyViolation::ChangeNotToWallet { output: 0 }]
+        );
+
+        // signatures not committing to the whole transaction are refused also without rules
+        let mut anyone_can_pay = psbt.clone();
+        anyone_can_pay.inputs[0].sighash_type = Some(SigHashType::AllPlusAnyoneCanPay);
+        assert_eq!(
+            unlimited
+                .check(&anyone_can_pay, 1, &wallet, Network::Testnet, NOW)
+                .unwrap()
+                .1,
+            vec![PolicyViolation::NonAllSighash {
+                input: 0,
+                sighash_type: "AllPlusAnyoneCanPay".to_string()
+            }]
+        );
+        anyone_can_pay.inputs[0].sighash_type = Some(SigHashType::All);
+        assert!(unlimited
+            .check(&anyone_can_pay, 1, &wallet, Network::Testnet, NOW)
+            .unwrap()
+            .1
+            .is_empty());
+
+        // the change output keeps our key path but its script is someone else's, it is spent
+        let (_, mut foreign_change) = wallet_psbt();
+        foreign_change.global.unsigned_tx.output[1].script_pubkey =
+            foreign_change.global.unsigned_tx.output[0]
+                .script_pubkey
+                .clone();
+        assert!(!foreign_change.outputs[1].hd_keypaths.is_empty());
+        let (amount, violations) = change
+            .check(&foreign_change, 1, &wallet, Network::Testnet, NOW)
+            .unwrap();
+        assert_eq!(amount, 100_000);
+        assert_eq!(
+            violations,
+            vec![PolicyViolation::ChangeNotToWallet { output: 1 }]
+        );
+
+        // nothing goes back to the wallet, everything is spent
+        psbt.outputs[0].hd_keypaths.clear();
+        psbt.outputs[1].hd_keypaths.clear();
+        let (amount, violations) = change
+            .check(&psbt, -1, &wallet, Network::Testnet, NOW)
+            .unwrap();
+        assert_eq!(amount, 100_000);
+        assert_eq!(violations, vec![PolicyViolation::NoChangeToWallet]);
+
+        // non_witness_utxo not spent by the input is refused instead of panicking the signer
+        let (_, mut mismatched) = wallet_psbt();
+        let mut prev_tx = mismatched.inputs[0].non_witness_utxo.clone().unwrap();
+        prev_tx.lock_time = 1;
+        mismatched.inputs[0].non_witness_utxo = Some(prev_tx);
+        assert_eq!(
+            unlimited
+                .check(&mismatched, 1, &wallet, Network::Testnet, NOW)
+                .unwrap_err()
+                .to_string(),
+            "prevout doesn't match non_witness_utxo"
+        );
+
+        // witness_utxo understating the spent amount hides part of the fee from the policy
+        let (_, mut understated) = wallet_psbt();
+        understated.inputs[0].witness_utxo.as_mut().unwrap().value = 99_100;
+        assert_eq!(
+            unlimited
+                .check(&understated, 1, &wallet, Network::Testnet, NOW)
+                .unwrap_err()
+                .to_string(),
+            "witness_utxo of input 0 doesn't match the spent output of non_witness_utxo"
+        );
+        understated.inputs[0].non_witness_utxo = None;
+        assert_eq!(
+            unlimited
+                .check(&understated, 1, &wallet, Network::Testnet, NOW)
+                .unwrap_err()
+                .to_string(),
+            "input 0 without non_witness_utxo, required by the spending policy"
+        );
+    }
+
+    #[test]
+    fn test_key_policy_files() {
+        let temp_dir = TempDir::new("test_key_policy_files").unwrap();
+        let key_file = temp_dir.path().join("PRIVATE.json");
+        assert!(KeyPolicy::read(&key_file).unwrap().is_none());
+
+        std::fs::write(
+            temp_dir.path().join(POLICY_FILE),
+            r#"{"daily_limit": 1000, "require_change_to_wallet": true}"#,
+        )
+        .unwrap();
+        let mut key_policy = KeyPolicy::read(&key_file).unwrap().unwrap();
+        assert_eq!(key_policy.policy.daily_limit, Some(1000));
+        assert!(key_policy.policy.require_change_to_wallet);
+        assert!(key_policy.policy.max_amount_per_tx.is_none());
+        assert!(key_policy.ledger.entries.is_empty());
+
+        let lock_file = temp_dir.path().join(LOCK_FILE);
+        assert!(lock_file.exists());
+        assert!(KeyPolicy::read(&key_file)
+            .unwrap_err()
+            .to_string()
+            .starts_with("spending ledger locked by another signer"));
+
+        key_policy.record("txid", 500, NOW).unwrap();
+        key_policy.record("txid", 500, NOW).unwrap();
+        assert!(!temp_dir.path().join("spending_ledger.json.tmp").exists());
+        drop(key_policy);
+        assert!(!lock_file.exists());
+        let key_policy = KeyPolicy::read(&key_file).unwrap().unwrap();
+        assert_eq!(key_policy.ledger.entries.len(), 1);
+        assert_eq!(key_policy.ledger.spent_on_day(NOW, "other"), 500);
+        assert_eq!(key_policy.ledger.spent_on_day(NOW, "txid"), 0);
+
+        let json =
+            serde_json::to_string(&PolicyViolation::ChangeNotToWallet { output: 1 }).unwrap();
+        assert_eq!(json, r#"{"rule":"change_not_to_wallet","output":1}"#);
+    }
+}
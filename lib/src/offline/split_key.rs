@@ -0,0 +1,261 @@
+use crate::*;
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Split the seed of a master key in SLIP-39 shares, printed only in the command output and
+/// optionally as QR codes in a directory given explicitly
+#[derive(StructOpt, Debug, Serialize, Deserialize)]
+#[structopt(name = "split_key")]
+pub struct SplitKeyOptions {
+    /// File containing the master key, it must contain the seed
+    #[structopt(short, long, parse(from_os_str))]
+    pub key_file: PathBuf,
+
+    /// Number of groups required to recover the seed
+    #[structopt(long, default_value = "1")]
+    pub group_threshold: u8,
+
+    /// Groups of shares specified as MofN (eg. 2of3), at least 1 is required
+    #[structopt(long = "group", required = true)]
+    pub groups: Vec<ShareGroup>,
+
+    /// Protect the shares with a SLIP-39 passphrase asked at the prompt, required also during
+    /// restore
+    #[structopt(long)]
+    #[serde(default)]
+    pub passphrase: bool,
+
+    /// Directory to create with the QR code of every share in its own subdirectory, the QR codes
+    /// contain the shares in clear: keep it out of the firma data directory and delete it once
+    /// printed
+    #[structopt(long, parse(from_os_str))]
+    #[serde(default)]
+    pub qr_dir: Option<PathBuf>,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+
+    /// SLIP-39 passphrase, the command line asks it with --passphrase
+    #[structopt(skip)]
+    #[serde(default)]
+    pub mnemonic_passphrase: Option<String>,
+
+    /// Passphrase to decrypt the key file, if encrypted, the command line asks it at the prompt
+    #[structopt(skip)]
+    #[serde(default)]
+    pub encryption_passphrase: Option<String>,
+}
+
+impl SplitKeyOptions {
+    fn validate(&self) -> Result<()> {
+        if self.groups.is_empty() {
+            return Err("At least one group is mandatory (--group)".into());
+        }
+        if self.group_threshold == 0 || self.group_threshold as usize > self.groups.len() {
+            return Err("group threshold must be between 1 and the number of groups".into());
+        }
+        for group in self.groups.iter() {
+            if group.threshold == 0 || group.threshold > group.count || group.count > 16 {
+                return Err(format!(
+                    "invalid group {}of{}, must be M of N with 1 <= M <= N <= 16",
+                    group.threshold, group.count
+                )
+                .into());
+            }
+            if group.threshold == 1 && group.count > 1 {
+                return Err("group with threshold 1 must have only 1 share (use 1of1)".into());
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn start(opt: &SplitKeyOptions) -> Result<SplitKeyOutput> {
+    opt.validate()?;
+    if let Some(qr_dir) = &opt.qr_dir {
+        if qr_dir.exists() {
+            return Err(Error::FileExist(qr_dir.clone()));
+        }
+    }
+    let key = read_key(
+        &opt.key_file,
+        opt.encryption_passphrase.as_ref().map(|s| s.as_str()),
+    )?;
+    if key.mnemonic.is_some() {
+        return Err(
+            "key created from a mnemonic cannot be split, back up the mnemonic words instead"
+                .into(),
+        );
+    }
+    let seed = key
+        .seed
+        .as_ref()
+        .ok_or_else(fn_err("key does not contain a seed, cannot split"))?;
+    let master_secret = hex::decode(&seed.hex)?;
+
+    let groups: Vec<(u8, u8)> = opt.groups.iter().map(|g| (g.threshold, g.count)).collect();
+    let passphrase = opt
+        .mnemonic_passphrase
+        .as_ref()
+        .map(|s| s.as_str())
+        .unwrap_or("");
+    let group_shares =
+        sssmc39::generate_mnemonics(opt.group_threshold, &groups, &master_secret, passphrase, 0)
+            .map_err(|e| Error::InvalidMnemonic(e.to_string()))?;
+
+    let mut output = SplitKeyOutput {
+        fingerprint: key.fingerprint,
+        group_threshold: opt.group_threshold,
+        groups: vec![],
+    };
+    for (i, group_share) in group_shares.iter().enumerate() {
+        let mnemonics = group_share
+            .mnemonic_list()
+            .map_err(|e| Error::InvalidMnemonic(e.to_string()))?;
+        let shares: Vec<String> = mnemonics.iter().map(|words| words.join(" ")).collect();
+        let mut qr_files = vec![];
+        if let Some(qr_dir) = &opt.qr_dir {
+            for (j, share) in shares.iter().enumerate() {
+                let share_dir = qr_dir.join(format!("share-{}-{}", i, j));
+                info!("Saving share qr codes in {:?}", &share_dir);
+                fs::create_dir_all(&share_dir)?;
+                let path = share_dir.join("share");
+                qr_files.extend(qr::save_qrs(
+                    share.as_bytes().to_vec(),
+                    path,
+                    opt.qr_version,
+                )?);
+            }
+        }
+        output.groups.push(ShareGroupOutput {
+            group: opt.groups[i],
+            shares,
+            qr_files,
+        });
+    }
+
+    Ok(output)
+}
+
+/// Recover the master secret from SLIP-39 shares, words of every share separated by spaces
+pub fn combine_shares(shares: &[&str], passphrase: Option<&str>) -> Result<Vec<u8>> {
+    let mnemonics: Vec<Vec<String>> = shares
+        .iter()
+        .map(|share| share.split_whitespace().map(|w| w.to_string()).collect())
+        .collect();
+    sssmc39::combine_mnemonics(&mnemonics, passphrase.unwrap_or(""))
+        .map_err(|e| Error::InvalidMnemonic(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::offline::random::RandomOptions;
+    use crate::offline::split_key::{combine_shares, start, SplitKeyOptions};
+    use crate::{PrivateMasterKey, ShareGroup};
+    use bitcoin::Network;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_split_key() {
+        let temp_dir = TempDir::new("test_split_key").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+
+        let rand_opts = RandomOptions::new("split".to_string());
+        let key = crate::offline::random::create_key(&temp_dir_str, Network::Testnet, &rand_opts)
+            .unwrap();
+        let secret = hex::decode(&key.key.seed.as_ref().unwrap().hex).unwrap();
+
+        let mut opt = SplitKeyOptions {
+            key_file: key.private_file.clone(),
+            group_threshold: 2,
+            groups: vec!["2of3".parse().unwrap(), "1of1".parse().unwrap()],
+            passphrase: false,
+            qr_dir: None,
+            qr_version: 14,
+            mnemonic_passphrase: None,
+            encryption_passphrase: None,
+        };
+        let split = start(&opt).unwrap();
+        assert_eq!(split.fingerprint, key.key.fingerprint);
+        assert_eq!(split.groups.len(), 2);
+        assert_eq!(split.groups[0].shares.len(), 3);
+        assert_eq!(split.groups[1].shares.len(), 1);
+        // the shares are only in the output, never next to the key
+        let key_dir = key.private_file.parent().unwrap();
+        assert!(!std::fs::read_dir(key_dir).unwrap().any(|e| e
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with("share")));
+        assert!(split.groups[0].qr_files.is_empty());
+
+        let first = split.groups[0].shares[0].as_str();
+        let third = split.groups[0].shares[2].as_str();
+        let single = split.groups[1].shares[0].as_str();
+        let recovered = combine_shares(&[first, third, single], None).unwrap();
+        assert_eq!(recovered, secret);
+
+        let result = combine_shares(&[first, single], None);
+        assert!(result.is_err(), "not enough shares in the first group");
+
+        // a QR code of every share in its own subdirectory of the given one
+        let qr_dir = temp_dir.path().join("shares");
+        opt.qr_dir = Some(qr_dir.clone());
+        let with_qrs = start(&opt).unwrap();
+        assert_eq!(with_qrs.groups[0].qr_files.len(), 3);
+        assert_eq!(with_qrs.groups[1].qr_files.len(), 1);
+        assert_eq!(
+            with_qrs.groups[0].qr_files[2],
+            qr_dir.join("share-0-2").join("qr.png")
+        );
+        assert!(with_qrs.groups[1].qr_files[0].exists());
+        assert_eq!(
+            start(&opt).unwrap_err().to_string(),
+            format!("file {} already exist", qr_dir.display())
+        );
+        opt.qr_dir = None;
+
+        opt.groups = vec![ShareGroup {
+            threshold: 3,
+            count: 2,
+        }];
+        opt.group_threshold = 1;
+        let result = start(&opt);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid group 3of2, must be M of N with 1 <= M <= N <= 16"
+        );
+    }
+
+    #[test]
+    fn test_split_mnemonic_key() {
+        let temp_dir = TempDir::new("test_split_mnemonic_key").unwrap();
+        let temp_dir_str = format!("{}/", temp_dir.path().display());
+
+        let words = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let key =
+            PrivateMasterKey::from_mnemonic(Network::Testnet, words, None, "mnemonic").unwrap();
+        let key =
+            crate::save_keys(&temp_dir_str, Network::Testnet, "mnemonic", key, 14, None).unwrap();
+
+        let opt = SplitKeyOptions {
+            key_file: key.private_file,
+            group_threshold: 1,
+            groups: vec!["2of3".parse().unwrap()],
+            passphrase: false,
+            qr_dir: None,
+            qr_version: 14,
+            mnemonic_passphrase: None,
+            encryption_passphrase: None,
+        };
+        let result = start(&opt);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "key created from a mnemonic cannot be split, back up the mnemonic words instead"
+        );
+    }
+}
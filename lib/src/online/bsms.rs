@@ -0,0 +1,118 @@
+use crate::common::bsms::{KeyRecord, TOKEN_NO_ENCRYPTION};
+use crate::common::coordinator::{self, WalletFormat};
+use crate::offline::create_wallet::WalletDescriptorOptions;
+use crate::offline::export_wallet::save_export;
+use crate::*;
+use log::info;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Start a BIP129 session as coordinator, the token must be given to the signers.
+/// Records are exchanged in clear text with the `00` token, they are authenticated by signatures
+#[derive(StructOpt, Debug)]
+pub struct BsmsStartOptions {
+    /// number of signatures required
+    #[structopt(short)]
+    pub r: usize,
+
+    /// number of signers taking part in the session
+    #[structopt(long)]
+    pub signers: usize,
+
+    /// Script type of the wallet: wsh (native segwit), sh-wsh (nested segwit) or sh (legacy)
+    #[structopt(long, default_value = "wsh")]
+    pub script_type: ScriptType,
+}
+
+/// Verify the key records of the signers and create the wallet with its descriptor record
+#[derive(StructOpt, Debug)]
+pub struct BsmsFinalizeOptions {
+    /// Key record created by a signer with `firma-offline bsms-key-record`
+    #[structopt(long = "key-record", parse(from_os_str))]
+    pub key_records: Vec<PathBuf>,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+}
+
+pub fn start(context: &Context, opt: &BsmsStartOptions) -> Result<BsmsSessionOutput> {
+    if opt.r == 0 || opt.r > opt.signers {
+        return Err("required signatures must be between 1 and the number of signers".into());
+    }
+    if opt.script_type.is_single_sig() {
+        return Err("bsms sessions are for multisig wallets, use wsh, sh-wsh or sh".into());
+    }
+    let session_file = context.filename_for_wallet("bsms-session.json")?;
+    if session_file.exists() {
+        return Err(Error::FileExist(session_file));
+    }
+    let session = BsmsSession {
+        token: TOKEN_NO_ENCRYPTION.to_string(),
+        required_sig: opt.r,
+        signers: opt.signers,
+        script_type: opt.script_type,
+    };
+    info!("Saving bsms session in {:?}", &session_file);
+    fs::write(&session_file, serde_json::to_string_pretty(&session)?)?;
+
+    Ok(BsmsSessionOutput {
+        session,
+        session_file,
+    })
+}
+
+pub fn finalize(context: &Context, opt: &BsmsFinalizeOptions) -> Result<BsmsDescriptorOutput> {
+    let session_file = context.filename_for_wallet("bsms-session.json")?;
+    let session: BsmsSession = serde_json::from_slice(&fs::read(&session_file).map_err(|_| {
+        Error::Generic(format!(
+            "bsms session of {} not started",
+            context.wallet_name
+        ))
+    })?)?;
+    if opt.key_records.len() != session.signers {
+        return Err(format!(
+            "session requires {} key records, found {}",
+            session.signers,
+            opt.key_records.len()
+        )
+        .into());
+    }
+
+    let mut xpubs = vec![];
+    for path in opt.key_records.iter() {
+        let record = KeyRecord::from_str(&fs::read_to_string(path)?)?;
+        record.verify(&session.token)?;
+        info!("key record of {} verified", record.description);
+        xpubs.push(record.key);
+    }
+    let descriptor_opts = WalletDescriptorOptions {
+        r: Some(session.required_sig),
+        policy: None,
+        unsorted: false,
+        script_type: session.script_type,
+        xpubs,
+        xpub_files: vec![],
+    };
+    let wallet = descriptor_opts.build(&context.wallet_name, context.network)?;
+    let wallet_file = context.save_wallet(&wallet)?;
+
+    let descriptor_record = coordinator::export(&wallet, WalletFormat::Bsms, context.network)?;
+    let (descriptor_record_file, qr_files) = save_export(
+        context,
+        WalletFormat::Bsms,
+        &descriptor_record,
+        opt.qr_version,
+    )?;
+    info!("Wallet created, import it in the node with firma-online import-wallet");
+
+    Ok(BsmsDescriptorOutput {
+        descriptor_record,
+        descriptor_record_file,
+        qr_files,
+        wallet,
+        wallet_file,
+    })
+}
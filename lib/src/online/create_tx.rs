@@ -1,3 +1,4 @@
+use crate::common::qr::QrEncoding;
 use crate::online::{save_psbt, Wallet};
 use crate::*;
 use bitcoin::{Address, Amount, OutPoint};
@@ -30,6 +31,16 @@ pub struct CreateTxOptions {
     /// QR code max version to use (max size)
     #[structopt(long, default_value = "14")]
     pub qr_version: i16,
+
+    /// Encoding of data not fitting a single QR code: structured-append (max 16 codes)
+    /// or ur (BC-UR animated QR of any size, `ur:crypto-psbt`)
+    #[structopt(long, default_value = "structured-append")]
+    pub qr_encoding: QrEncoding,
+
+    /// Remove from the PSBT QR codes the `non_witness_utxo` of segwit inputs to save space,
+    /// signers with a spending policy refuse PSBTs without them
+    #[structopt(long)]
+    pub strip_non_witness_utxo: bool,
 }
 
 #[derive(StructOpt, Debug)]
@@ -147,8 +158,13 @@ impl Wallet {
             fs::create_dir(&psbt_qr_path)?;
         }
         psbt_qr_path.push("filename");
-        let psbt_bytes = serde_json::to_vec(&funded_psbt)?;
-        let qr_files = qr::save_qrs(psbt_bytes, psbt_qr_path, opt.qr_version)?;
+        let qr_files = qr::save_psbt_qrs(
+            &funded_psbt,
+            psbt_qr_path,
+            opt.qr_version,
+            opt.qr_encoding,
+            opt.strip_non_witness_utxo,
+        )?;
 
         let create_tx = CreateTxOutput {
             funded_psbt,
@@ -1,27 +1,16 @@
-use crate::online::{read_xpubs_files, Wallet};
+use crate::common::qr::QrEncoding;
+use crate::offline::create_wallet::WalletDescriptorOptions;
 use crate::*;
-use bitcoin::util::bip32::ExtendedPubKey;
-use bitcoin::Network;
 use bitcoincore_rpc::bitcoincore_rpc_json::{ImportMultiOptions, ImportMultiRequest};
 use bitcoincore_rpc::RpcApi;
 use log::debug;
 use log::info;
-use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
 pub struct CreateWalletOptions {
-    /// number of signatures required
-    #[structopt(short)]
-    pub r: usize,
-
-    /// Extended Public Keys (xpub) that are composing the wallet, given as String (xprv...)
-    #[structopt(long = "xpub")]
-    pub xpubs: Vec<ExtendedPubKey>,
-
-    /// Extended Public Keys (xpub) that are composing the wallet, given as a json file
-    #[structopt(long = "xpub-file")]
-    pub xpub_files: Vec<PathBuf>,
+    #[structopt(flatten)]
+    pub descriptor_opts: WalletDescriptorOptions,
 
     #[structopt(flatten)]
     pub daemon_opts: DaemonOpts,
@@ -29,43 +18,12 @@ pub struct CreateWalletOptions {
     /// QR code max version to use (max size)
     #[structopt(long, default_value = "14")]
     pub qr_version: i16,
-}
-
-impl CreateWalletOptions {
-    fn validate(&self, network: Network) -> Result<()> {
-        if self.r == 0 {
-            return Err("required signatures cannot be 0".into());
-        }
-
-        if self.r > 15 {
-            return Err("required signatures cannot be greater than 15".into());
-        }
-
-        if self.r > (self.xpubs.len() + self.xpub_files.len()) {
-            return Err("required signatures cannot be greater than the number of xpubs".into());
-        }
-
-        let mut xpubs = read_xpubs_files(&self.xpub_files)?;
-        xpubs.extend(&self.xpubs);
-
-        for xpub in xpubs.iter() {
-            if !(network == xpub.network
-                || (network == Network::Regtest && xpub.network == Network::Testnet))
-            {
-                return Err(format!(
-                    "detected xpub of another network (cmd:{}) (xpub:{})",
-                    network, xpub.network
-                )
-                .into());
-            }
-
-            if xpubs.iter().filter(|xpub2| *xpub2 == xpub).count() > 1 {
-                return Err("Cannot use same xpub twice".into());
-            }
-        }
 
-        Ok(())
-    }
+    /// Encoding of data not fitting a single QR code: structured-append (max 16 codes)
+    /// or ur (BC-UR animated QR of any size, readable by mobile wallets, of the main descriptor
+    /// only, the receiver derives the change descriptor with /1/*)
+    #[structopt(long, default_value = "structured-append")]
+    pub qr_encoding: QrEncoding,
 }
 
 impl Wallet {
@@ -75,26 +33,47 @@ impl Wallet {
         opt: &CreateWalletOptions,
         height: u64,
     ) -> Result<CreateWalletOutput> {
-        opt.validate(self.context.network)?;
         debug!("create");
+        let wallet_file = self.context.filename_for_wallet("descriptor.json")?;
+        if wallet_file.exists() {
+            return Err(Error::FileExist(wallet_file));
+        }
+        let mut wallet = opt
+            .descriptor_opts
+            .build(&self.context.wallet_name, self.context.network)?;
+        wallet.daemon_opts = Some(daemon_opts.clone());
+        wallet.created_at_height = height;
 
-        let mut xpubs = read_xpubs_files(&opt.xpub_files)?;
-        xpubs.extend(&opt.xpubs);
+        self.import_descriptors(&wallet)?;
 
-        let mut descriptors = vec![];
-        for i in 0..=1 {
-            let mut xpub_paths = vec![];
-            for xpub in xpubs.iter() {
-                let xpub_path = format!("{}/{}/*", xpub, i);
-                xpub_paths.push(xpub_path)
-            }
-            let descriptor = format!("wsh(multi({},{}))", opt.r, xpub_paths.join(","));
-            descriptors.push(descriptor);
-        }
+        let indexes = WalletIndexes {
+            main: 0u32,
+            change: 0u32,
+        };
 
-        let descriptor_main = self.client.get_descriptor_info(&descriptors[0])?.descriptor;
-        let descriptor_change = self.client.get_descriptor_info(&descriptors[1])?.descriptor;
+        let wallet_file = self.context.save_wallet(&wallet)?;
+        self.context.save_index(&indexes)?;
+        let qr_files = self
+            .context
+            .save_wallet_qrs(&wallet, opt.qr_version, opt.qr_encoding)?;
 
+        let create_wallet = CreateWalletOutput {
+            qr_files,
+            wallet_file,
+            wallet,
+        };
+
+        Ok(create_wallet)
+    }
+
+    /// Create the watch-only wallet in the node and import the main and change descriptors
+    pub(crate) fn import_descriptors(&self, wallet: &WalletJson) -> Result<()> {
+        if wallet.policy.is_some() {
+            return Err(
+                "policy wallets are offline only, the node cannot import miniscript descriptors"
+                    .into(),
+            );
+        }
         self.client
             .create_wallet(&self.context.wallet_name, Some(true))?;
 
@@ -104,10 +83,10 @@ impl Wallet {
         multi_request.keypool = Some(true);
         multi_request.watchonly = Some(true);
         let mut main = multi_request.clone();
-        main.descriptor = Some(&descriptor_main);
+        main.descriptor = Some(&wallet.descriptor_main);
         main.internal = Some(false);
         let mut change = multi_request.clone();
-        change.descriptor = Some(&descriptor_change);
+        change.descriptor = Some(&wallet.descriptor_change);
         change.internal = Some(true);
 
         let multi_options = ImportMultiOptions {
@@ -116,41 +95,23 @@ impl Wallet {
 
         let import_multi_result = self
             .client
-            .import_multi(&[main, change], Some(&multi_options));
+            .import_multi(&[main, change], Some(&multi_options))?;
         info!("import_multi_result {:?}", import_multi_result);
-
-        let fingerprints = xpubs.iter().map(|x| x.fingerprint()).collect();
-
-        let wallet = WalletJson {
-            name: self.context.wallet_name.to_string(),
-            descriptor_main,
-            descriptor_change,
-            daemon_opts: Some(daemon_opts.clone()),
-            fingerprints,
-            required_sig: opt.r,
-            created_at_height: height,
-        };
-        let indexes = WalletIndexes {
-            main: 0u32,
-            change: 0u32,
-        };
-
-        let wallet_file = self.context.save_wallet(&wallet)?;
-        self.context.save_index(&indexes)?;
-
-        let mut wallet_for_qr = wallet.clone();
-        wallet_for_qr.daemon_opts = None; // no need of this info in the qr code
-        let qr_bytes = serde_json::to_vec(&wallet_for_qr)?;
-
-        let wallet_qr_path = self.context.path_for_wallet_qr()?;
-        let qr_files = common::qr::save_qrs(qr_bytes, wallet_qr_path, opt.qr_version)?;
-
-        let create_wallet = CreateWalletOutput {
-            qr_files,
-            wallet_file,
-            wallet,
-        };
-
-        Ok(create_wallet)
+        if import_multi_result.len() != 2 {
+            return Err("node did not import both main and change descriptors".into());
+        }
+        for (result, kind) in import_multi_result.iter().zip(["main", "change"].iter()) {
+            if !result.success {
+                let message = result
+                    .error
+                    .as_ref()
+                    .map(|e| e.message.clone())
+                    .unwrap_or_default();
+                return Err(
+                    format!("node failed to import the {} descriptor: {}", kind, message).into(),
+                );
+            }
+        }
+        Ok(())
     }
 }
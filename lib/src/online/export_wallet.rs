@@ -0,0 +1,27 @@
+use crate::common::coordinator::WalletFormat;
+use crate::offline::export_wallet;
+use crate::*;
+use structopt::StructOpt;
+
+/// Export the wallet to register it on hardware signers and watch-only apps
+#[derive(StructOpt, Debug)]
+pub struct ExportWalletOptions {
+    /// Format of the export: coldcard, specter, sparrow (electrum) or bsms
+    #[structopt(long)]
+    pub format: WalletFormat,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+}
+
+/// Export the wallet of the context as `firma-offline export-wallet`, the bitcoin node is not
+/// needed
+pub fn start(context: &Context, opt: &ExportWalletOptions) -> Result<ExportWalletOutput> {
+    let offline_opt = export_wallet::ExportWalletOptions {
+        wallet_name: context.wallet_name.clone(),
+        format: opt.format,
+        qr_version: opt.qr_version,
+    };
+    export_wallet::start(&context.firma_datadir, context.network, &offline_opt)
+}
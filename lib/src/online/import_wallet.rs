@@ -0,0 +1,96 @@
+use crate::common::descriptor::WalletDescriptor;
+use crate::common::qr::QrEncoding;
+use crate::*;
+use bitcoincore_rpc::RpcApi;
+use log::info;
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// Import in the node a wallet created offline with `firma-offline create-wallet`
+#[derive(StructOpt, Debug)]
+pub struct ImportWalletOptions {
+    /// Wallet descriptor file (descriptor.json) to copy in the datadir, if missing the
+    /// descriptor.json already present in the datadir for the given wallet name is imported
+    #[structopt(long, parse(from_os_str))]
+    pub wallet_descriptor_file: Option<PathBuf>,
+
+    /// Block height from which to rescan the blockchain for transactions of the wallet, if missing
+    /// the wallet creation height is used when known (wallets created online)
+    #[structopt(long)]
+    pub start_from: Option<u64>,
+
+    #[structopt(flatten)]
+    pub daemon_opts: DaemonOpts,
+
+    /// QR code max version to use (max size)
+    #[structopt(long, default_value = "14")]
+    pub qr_version: i16,
+}
+
+impl Wallet {
+    pub fn import_wallet(
+        &self,
+        daemon_opts: &DaemonOpts,
+        opt: &ImportWalletOptions,
+    ) -> Result<CreateWalletOutput> {
+        let wallet_file = self.context.filename_for_wallet("descriptor.json")?;
+        let mut wallet = match &opt.wallet_descriptor_file {
+            Some(path) => {
+                if wallet_file.exists() {
+                    return Err(Error::FileExist(wallet_file));
+                }
+                read_wallet(path)?
+            }
+            None => read_wallet(&wallet_file)?,
+        };
+        if wallet.name != self.context.wallet_name {
+            return Err(format!(
+                "wallet name {} does not match the given one {}",
+                wallet.name, self.context.wallet_name
+            )
+            .into());
+        }
+        if !WalletDescriptor::from_str(&wallet.descriptor_main)?
+            .is_for_network(self.context.network)
+        {
+            return Err(format!("wallet keys are not for network {}", self.context.network).into());
+        }
+
+        self.import_descriptors(&wallet)?;
+        let created_at_height = Some(wallet.created_at_height).filter(|h| *h > 0);
+        match opt.start_from.or(created_at_height) {
+            Some(height) => {
+                info!("Rescanning the blockchain from height {}", height);
+                self.client.rescan_blockchain(Some(height as usize), None)?;
+            }
+            None => info!(
+                "Wallet imported without rescan, if its addresses already received funds run \
+                 firma-online rescan --start-from <height before the first payment>"
+            ),
+        }
+
+        wallet.daemon_opts = Some(daemon_opts.clone());
+        info!("Saving wallet data in {:?}", &wallet_file);
+        fs::write(&wallet_file, serde_json::to_string_pretty(&wallet)?)?;
+        if !self.context.filename_for_wallet("indexes.json")?.exists() {
+            self.context
+                .save_index(&WalletIndexes { main: 0, change: 0 })?;
+        }
+        let qr_files = match opt.wallet_descriptor_file {
+            Some(_) => self.context.save_wallet_qrs(
+                &wallet,
+                opt.qr_version,
+                QrEncoding::StructuredAppend,
+            )?,
+            None => common::list::read_qrs(&wallet_file)?,
+        };
+
+        Ok(CreateWalletOutput {
+            qr_files,
+            wallet_file,
+            wallet,
+        })
+    }
+}
@@ -1,3 +1,4 @@
+use crate::common::signatures;
 use crate::*;
 use bitcoin::consensus::deserialize;
 use bitcoin::Transaction;
@@ -38,6 +39,8 @@ impl Wallet {
             psbts.push(json.psbt);
         }
         psbts.extend(opt.psbts.clone());
+        let (wallet, _) = self.context.load_wallet_and_index()?;
+        check_signatures(&wallet, &psbts)?;
         let combined = self.client.combine_psbt(&psbts)?;
         debug!("combined {:?}", combined);
 
@@ -66,3 +69,79 @@ impl Wallet {
         Ok(send_tx)
     }
 }
+
+/// Verify the partial signatures of every PSBT before combining them, and that together they
+/// have the signatures required by the wallet
+fn check_signatures(wallet: &WalletJson, psbts: &[String]) -> Result<()> {
+    let mut combined: Option<PSBT> = None;
+    for psbt in psbts {
+        let (_, psbt) = psbt_from_base64(psbt)?;
+        for i in 0..psbt.inputs.len() {
+            signatures::verify_partial_sigs(&psbt, i)?;
+        }
+        combined = match combined {
+            Some(mut combined) => {
+                combined.merge(psbt)?;
+                Some(combined)
+            }
+            None => Some(psbt),
+        };
+    }
+    let combined = combined.ok_or_else(fn_err("At least one psbt is mandatory"))?;
+    for i in 0..combined.inputs.len() {
+        let signed_by = signatures::verify_partial_sigs(&combined, i)?;
+        // policy wallets have no fixed number of signatures, the node checks them finalizing
+        let required_sig = match wallet.required_sig {
+            Some(required_sig) => required_sig,
+            None => continue,
+        };
+        if signed_by.len() < required_sig {
+            return Err(format!(
+                "input {} has {} of the {} required signatures, signed by: {}",
+                i,
+                signed_by.len(),
+                required_sig,
+                signed_by.join(", ")
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::fixtures::{self, ACCOUNT_1_XPUB, ACCOUNT_XPUB};
+    use crate::online::send_tx::check_signatures;
+    use crate::PsbtJson;
+
+    fn psbts(bytes: &[u8]) -> Vec<String> {
+        vec![serde_json::from_slice::<PsbtJson>(bytes).unwrap().psbt]
+    }
+
+    #[test]
+    fn test_check_signatures() {
+        // keys with the fingerprint of the BIP174 test vector
+        let a = ACCOUNT_XPUB.replace("3442193e", "d90c6a4f");
+        let b = ACCOUNT_1_XPUB.replace("3442193e", "d90c6a4f");
+        let unsigned = psbts(include_bytes!("../../test_data/sign/psbt_bip.json"));
+        let signed = psbts(include_bytes!("../../test_data/sign/psbt_bip.signed.json"));
+
+        let multisig = fixtures::wallet("multi", &format!("wsh(multi(2,{}/0/*,{}/0/*))", a, b));
+        check_signatures(&multisig, &signed).unwrap();
+        assert_eq!(
+            check_signatures(&multisig, &unsigned)
+                .unwrap_err()
+                .to_string(),
+            "input 0 has 0 of the 2 required signatures, signed by: "
+        );
+        assert!(check_signatures(&multisig, &[]).is_err());
+
+        // policy wallets leave the count of the signatures to the node
+        let policy = format!("wsh(or_d(pk({}/0/*),and_v(v:pk({}/0/*),older(144))))", a, b);
+        let policy = fixtures::wallet("policy", &policy);
+        assert_eq!(policy.required_sig, None);
+        check_signatures(&policy, &unsigned).unwrap();
+        check_signatures(&policy, &signed).unwrap();
+    }
+}